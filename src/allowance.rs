@@ -0,0 +1,112 @@
+use cosmwasm_std::{
+    Deps, DepsMut, Env, Response, StdResult, Event, Addr, CosmosMsg, WasmMsg, Uint128,
+    to_binary
+};
+use cw20::{AllowanceResponse, BalanceResponse, Cw20ExecuteMsg, Cw20QueryMsg};
+
+use crate::asset::Asset;
+use crate::error::ContractError;
+use crate::ibc::send_tokens;
+use crate::state::{OTCS, OTCInfo, IbcRecipient};
+
+// Offers created with an allowance leave the sold cw20s with the seller until filled.
+// Each fill pulls them with `TransferFrom`, so sellers can revoke the allowance or move
+// the tokens in the meantime which invalidates the offer on the next fill
+
+
+
+/// Amount of a cw20 the contract can currently pull from the owner
+pub fn available_allowance(
+    deps: Deps,
+    env: &Env,
+    token: &str,
+    owner: &Addr,
+) -> StdResult<Uint128> {
+
+    let allowance : AllowanceResponse = deps.querier.query_wasm_smart(token, &Cw20QueryMsg::Allowance {
+        owner: owner.to_string(),
+        spender: env.contract.address.to_string(),
+    })?;
+
+    if allowance.expires.is_expired(&env.block) {
+        return Ok(Uint128::zero());
+    }
+
+    let balance : BalanceResponse = deps.querier.query_wasm_smart(token, &Cw20QueryMsg::Balance {
+        address: owner.to_string(),
+    })?;
+
+    Ok(allowance.allowance.min(balance.balance))
+}
+
+
+
+/// Message pulling cw20s from the owner with the allowance given to the contract
+pub fn transfer_from(
+    token: &str,
+    owner: &Addr,
+    recipient: &str,
+    amount: Uint128,
+) -> StdResult<CosmosMsg> {
+
+    Ok(CosmosMsg::Wasm(WasmMsg::Execute {
+        contract_addr: token.to_string(),
+        funds: vec![],
+        msg: to_binary(&Cw20ExecuteMsg::TransferFrom {
+            owner: owner.to_string(),
+            recipient: recipient.to_string(),
+            amount,
+        })?,
+    }))
+}
+
+
+
+/// Message delivering sold tokens of an offer, pulled from the seller for allowance offers
+pub fn release_tokens(
+    env: &Env,
+    otc: &OTCInfo,
+    seller: &Addr,
+    recipient: &str,
+    amount: Uint128,
+) -> StdResult<CosmosMsg> {
+
+    if !otc.allowance {
+        return otc.sell.transfer(env, recipient, amount);
+    }
+
+    transfer_from(&otc.sell.token(), seller, recipient, amount)
+}
+
+
+
+/// Marks an offer whose allowance no longer covers a fill as invalid and refunds the payer.
+/// Returned as a success since an error would revert the invalidation
+pub fn invalidate_offer(
+    mut deps: DepsMut,
+    env: &Env,
+    otc_id: u32,
+    payer: &Addr,
+    refund_recipient: Option<&IbcRecipient>,
+    paid: Asset,
+    method: &str,
+) -> Result<Response, ContractError> {
+
+    let mut otc = OTCS.load(deps.storage, otc_id)?;
+    otc.invalid = true;
+    OTCS.save(deps.storage, otc_id, &otc)?;
+
+    Ok(Response::new()
+        .add_submessage(send_tokens(deps.branch(), env, &paid.info, payer, refund_recipient, paid.amount)?)
+        .add_event(
+            Event::new("peerswap_offer_invalidated")
+            .add_attributes(vec![
+                ("otc_id", otc_id.to_string()),
+                ("error", ContractError::AllowanceRevoked {}.to_string()),
+                ("refunded_amount", paid.amount.to_string()),
+                ("refunded_token", paid.info.token()),
+                ("method", method.to_string())
+            ])
+        )
+    )
+}
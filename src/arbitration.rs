@@ -0,0 +1,244 @@
+use cosmwasm_std::{
+    Deps, DepsMut, Env, Response, StdResult, Event, Addr, Decimal, Order, CosmosMsg, Storage
+};
+use cw_storage_plus::Bound;
+use cw_utils::Expiration;
+
+use crate::contract::{remove_otc, DEFAULT_LIMIT, MAX_LIMIT};
+use crate::error::ContractError;
+use crate::escrow::pay_buyer;
+use crate::msg::GetArbitratorsResponse;
+use crate::state::{State, STATE, OTCS, ARBITRATORS, ArbitratorInfo, EscrowStatus};
+
+// disputes the arbitrator doesn't resolve this long are refunded to the seller
+pub const DISPUTE_TIMEOUT: u64 = 30 * 24 * 60 * 60;
+
+
+pub fn ensure_admin(deps: Deps, sender: &Addr) -> Result<(), ContractError> {
+    let config = STATE.load(deps.storage)?;
+
+    if deps.api.addr_canonicalize(sender.as_str())? != config.admin {
+        return Err(ContractError::Unauthorized {});
+    }
+
+    Ok(())
+}
+
+
+
+/// Fee of an arbitrator chosen by a seller. Sellers can only choose from the registry 
+/// while the default arbitrator of the config may be unregistered
+pub fn arbitrator_fee(
+    storage: &dyn Storage,
+    config: &State,
+    arbitrator: &Addr,
+) -> Result<u16, ContractError> {
+    match ARBITRATORS.may_load(storage, arbitrator)? {
+        Some(info) => Ok(info.fee),
+        None if config.arbitrator.as_ref() == Some(arbitrator) => Ok(0),
+        None => Err(ContractError::NotArbitrator {})
+    }
+}
+
+
+
+pub fn try_register_arbitrator(
+    deps: DepsMut,
+    sender: &Addr,
+    address: Addr,
+    fee: u16,
+    description: Option<String>,
+) -> Result<Response, ContractError> {
+
+    ensure_admin(deps.as_ref(), sender)?;
+
+    if fee > 10000 {
+        return Err(ContractError::InvalidSplit {});
+    }
+
+    let address = deps.api.addr_validate(address.as_str())?;
+
+    ARBITRATORS.save(deps.storage, &address, &ArbitratorInfo { fee, description })?;
+
+    Ok(Response::new()
+        .add_attribute("method", "register_arbitrator")
+        .add_attribute("arbitrator", address)
+        .add_attribute("fee", fee.to_string())
+    )
+}
+
+
+
+pub fn try_remove_arbitrator(
+    deps: DepsMut,
+    sender: &Addr,
+    address: Addr,
+) -> Result<Response, ContractError> {
+
+    ensure_admin(deps.as_ref(), sender)?;
+
+    // escrows that chose the arbitrator keep it until resolved
+    ARBITRATORS.remove(deps.storage, &address);
+
+    Ok(Response::new()
+        .add_attribute("method", "remove_arbitrator")
+        .add_attribute("arbitrator", address)
+    )
+}
+
+
+
+pub fn try_dispute(
+    deps: DepsMut,
+    env: Env,
+    sender: &Addr,
+    otc_id: u32,
+    ) -> Result<Response, ContractError> {
+
+    let mut otc = OTCS.load(deps.storage, otc_id)
+        .map_err(|_| ContractError::NotFound {})?;
+
+    let counterparty = otc.counterparty()
+        .ok_or(ContractError::WrongEscrowStatus {})?;
+
+    if sender != counterparty && sender != &deps.api.addr_humanize(&otc.seller)? {
+        return Err(ContractError::Unauthorized {});
+    }
+
+    // the taker of a private offer has nothing in the deal before a fill
+    if otc.fiat_escrow.is_none() && otc.milestones.is_none() && otc.sell_amount == otc.initial_sell_amount {
+        return Err(ContractError::NotFilled {});
+    }
+
+    if otc.is_disputed() {
+        return Err(ContractError::WrongEscrowStatus {});
+    }
+
+    if otc.expires.is_expired(&env.block) {
+        return Err(ContractError::Expired {});
+    }
+
+    // the seller gets refunded if the arbitrator doesn't resolve in time
+    otc.expires = Expiration::AtTime(env.block.time.plus_seconds(DISPUTE_TIMEOUT));
+
+    if let Some(escrow) = otc.fiat_escrow.as_mut() {
+        escrow.status = EscrowStatus::Disputed;
+    } else if let Some(arbitration) = otc.arbitration.as_mut() {
+        arbitration.disputed = true;
+    }
+
+    OTCS.save(deps.storage, otc_id, &otc)?;
+
+    Ok(Response::new()
+        .add_event(
+            Event::new("peerswap_escrow_disputed")
+            .add_attributes(vec![
+                ("otc_id", otc_id.to_string()),
+                ("disputer", sender.to_string()),
+                ("dispute_until", otc.expires.to_string()),
+                ("method", "dispute".to_string())
+            ])
+        )
+    )
+}
+
+
+
+pub fn try_resolve(
+    deps: DepsMut,
+    env: Env,
+    sender: &Addr,
+    otc_id: u32,
+    split: Decimal,
+    ) -> Result<Response, ContractError> {
+
+    let config = STATE.load(deps.storage)?;
+
+    let otc = OTCS.load(deps.storage, otc_id)
+        .map_err(|_| ContractError::NotFound {})?;
+
+    let counterparty = otc.counterparty()
+        .cloned()
+        .ok_or(ContractError::WrongEscrowStatus {})?;
+
+    // fiat escrows can also be resolved once the payment is marked as sent
+    let (arbitrator, arbitrator_fee) = match (otc.fiat_escrow.as_ref(), otc.arbitration.as_ref()) {
+        (Some(escrow), _) if escrow.status != EscrowStatus::Locked => (
+            escrow.arbitrator.clone().or(config.arbitrator).ok_or(ContractError::NotArbitrator {})?,
+            escrow.arbitrator_fee
+        ),
+        (None, Some(arbitration)) if arbitration.disputed => (
+            arbitration.arbitrator.clone(),
+            arbitration.arbitrator_fee
+        ),
+        _ => return Err(ContractError::WrongEscrowStatus {})
+    };
+
+    if sender != &arbitrator {
+        return Err(ContractError::Unauthorized {});
+    }
+
+    if otc.expires.is_expired(&env.block) {
+        return Err(ContractError::Expired {});
+    }
+
+    if split > Decimal::one() {
+        return Err(ContractError::InvalidSplit {});
+    }
+
+    let seller = deps.api.addr_humanize(&otc.seller)?;
+
+    let fee = otc.sell_amount * Decimal::from_ratio(arbitrator_fee, 10000u16);
+    let to_split = otc.sell_amount - fee;
+    let to_buyer = to_split * split;
+    let to_seller = to_split - to_buyer;
+
+    let mut payments : Vec<CosmosMsg> = vec![];
+
+    if !fee.is_zero() {
+        payments.push(otc.sell.transfer(&env, arbitrator.as_str(), fee)?);
+    }
+    if !to_buyer.is_zero() {
+        payments.extend(pay_buyer(deps.as_ref(), &env, &otc, &counterparty, to_buyer)?);
+    }
+    if !to_seller.is_zero() {
+        payments.push(otc.sell.transfer(&env, seller.as_str(), to_seller)?);
+    }
+
+    remove_otc(deps.storage, otc_id)?;
+
+    Ok(Response::new()
+        .add_messages(payments)
+        .add_event(
+            Event::new("peerswap_escrow_resolved")
+            .add_attributes(vec![
+                ("otc_id", otc_id.to_string()),
+                ("arbitrator", sender.to_string()),
+                ("buyer_amount", to_buyer.to_string()),
+                ("seller_amount", to_seller.to_string()),
+                ("arbitrator_fee", fee.to_string()),
+                ("token", otc.sell.token()),
+                ("method", "resolve".to_string())
+            ])
+        )
+    )
+}
+
+
+
+pub fn query_arbitrators(
+    deps: Deps,
+    start_after: Option<Addr>,
+    limit: Option<u32>,
+) -> StdResult<GetArbitratorsResponse> {
+
+    let limit = limit.unwrap_or(DEFAULT_LIMIT).min(MAX_LIMIT) as usize;
+    let start = start_after.as_ref().map(Bound::exclusive);
+
+    let arbitrators = ARBITRATORS
+        .range(deps.storage, start, None, Order::Ascending)
+        .take(limit)
+        .collect::<StdResult<Vec<_>>>()?;
+
+    Ok(GetArbitratorsResponse { arbitrators })
+}
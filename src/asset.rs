@@ -0,0 +1,166 @@
+use std::fmt;
+
+use cosmwasm_std::{
+    Api, Env, StdResult, StdError, CosmosMsg, BankMsg, WasmMsg, Coin, Addr, Uint128, to_binary
+};
+use cw20::{Balance, Cw20ExecuteMsg};
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+
+use crate::cw1155::send_from;
+use crate::error::ContractError;
+
+
+
+/// Message sending `amount` of a native denom or a cw20 token to the recipient
+pub fn transfer_tokens(
+    native: bool,
+    token: &str,
+    recipient: &str,
+    amount: Uint128
+) -> StdResult<CosmosMsg> {
+
+    let msg = if native {
+        CosmosMsg::Bank(BankMsg::Send {
+            to_address: recipient.to_string(),
+            amount: vec![Coin {
+                denom: token.to_string(),
+                amount,
+            }],
+        })
+    } else {
+        CosmosMsg::Wasm(WasmMsg::Execute {
+            contract_addr: token.to_string(),
+            funds: vec![],
+            msg: to_binary(&Cw20ExecuteMsg::Transfer {
+                recipient: recipient.to_string(),
+                amount,
+            })?,
+        })
+    };
+
+    Ok(msg)
+}
+
+
+/// Single native coin or cw20 token as (native, denom or address, amount)
+pub fn parse_single_balance(balance: Balance) -> Result<(bool, String, Uint128), ContractError> {
+    match balance {
+        Balance::Native(mut balance) => {
+            let coin = balance.0.pop().ok_or(ContractError::TooSmall {})?;
+
+            if !balance.0.is_empty() {
+                return Err(ContractError::TooManyGiveTokens {});
+            }
+
+            Ok((true, coin.denom, coin.amount))
+        },
+        Balance::Cw20(token) => Ok((false, token.address.to_string(), token.amount))
+    }
+}
+
+
+
+/// Token sold or asked for by an offer
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, Eq, JsonSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum AssetInfo {
+    Native(String),
+    Cw20(Addr),
+    Cw1155 {
+        address: Addr,
+        token_id: String,
+    },
+}
+
+
+impl AssetInfo {
+    /// Builds the asset from the (native, denom or address) pairs of balances,
+    /// a token contract with a token id is a cw1155
+    pub fn from_parts(native: bool, token: String, token_id: Option<String>) -> Self {
+        match (native, token_id) {
+            (true, _) => AssetInfo::Native(token),
+            (false, Some(token_id)) => AssetInfo::Cw1155 { address: Addr::unchecked(token), token_id },
+            (false, None) => AssetInfo::Cw20(Addr::unchecked(token)),
+        }
+    }
+
+    pub fn is_native(&self) -> bool {
+        matches!(self, AssetInfo::Native(_))
+    }
+
+    /// Denom of a native token or address of a token contract
+    pub fn token(&self) -> String {
+        match self {
+            AssetInfo::Native(denom) => denom.clone(),
+            AssetInfo::Cw20(address) => address.to_string(),
+            AssetInfo::Cw1155 { address, .. } => address.to_string(),
+        }
+    }
+
+    /// Checks the denom or the contract address given by a user
+    pub fn validate(&self, api: &dyn Api) -> StdResult<Self> {
+        match self {
+            AssetInfo::Native(denom) if denom.is_empty() => Err(StdError::generic_err("empty denom")),
+            AssetInfo::Native(_) => Ok(self.clone()),
+            AssetInfo::Cw20(address) => Ok(AssetInfo::Cw20(api.addr_validate(address.as_str())?)),
+            AssetInfo::Cw1155 { address, token_id } => Ok(AssetInfo::Cw1155 {
+                address: api.addr_validate(address.as_str())?,
+                token_id: token_id.clone(),
+            }),
+        }
+    }
+
+    /// Message sending `amount` held by the contract to the recipient
+    pub fn transfer(&self, env: &Env, recipient: &str, amount: Uint128) -> StdResult<CosmosMsg> {
+        match self {
+            AssetInfo::Native(denom) => transfer_tokens(true, denom, recipient, amount),
+            AssetInfo::Cw20(address) => transfer_tokens(false, address.as_str(), recipient, amount),
+            AssetInfo::Cw1155 { address, token_id } => send_from(env, address, token_id, recipient, amount),
+        }
+    }
+}
+
+
+/// The denom, `cw20:<address>` or `cw1155:<address>:<token id>` as shown in events
+impl fmt::Display for AssetInfo {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            AssetInfo::Native(denom) => write!(f, "{}", denom),
+            AssetInfo::Cw20(address) => write!(f, "cw20:{}", address),
+            AssetInfo::Cw1155 { address, token_id } => write!(f, "cw1155:{}:{}", address, token_id),
+        }
+    }
+}
+
+
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, Eq, JsonSchema)]
+pub struct Asset {
+    pub info: AssetInfo,
+    pub amount: Uint128,
+}
+
+
+impl Asset {
+    /// Single coin or token sent with a message, a cw1155 if the token id is given
+    pub fn from_balance(balance: Balance, token_id: Option<String>) -> Result<Self, ContractError> {
+        let (native, token, amount) = parse_single_balance(balance)?;
+
+        Ok(Asset {
+            info: AssetInfo::from_parts(native, token, token_id),
+            amount,
+        })
+    }
+
+    pub fn transfer(&self, env: &Env, recipient: &str) -> StdResult<CosmosMsg> {
+        self.info.transfer(env, recipient, self.amount)
+    }
+}
+
+
+impl fmt::Display for Asset {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{} {}", self.amount, self.info)
+    }
+}
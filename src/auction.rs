@@ -0,0 +1,286 @@
+use cosmwasm_std::{
+    Deps, DepsMut, Env, Response, StdResult, Event, Addr, Decimal, Order, Uint128,
+    CosmosMsg, to_binary
+};
+use cw20::Balance;
+use cw_storage_plus::Bound;
+
+use crate::asset::Asset;
+use crate::contract::{parse_sell_balance, DEFAULT_LIMIT, MAX_LIMIT};
+use crate::error::ContractError;
+use crate::msg::{NewAuction, NewAuctionResponse, GetAuctionsResponse};
+use crate::registry::ensure_allowed;
+use crate::state::{STATE, AUCTIONS, AUCTION_INDEX, AuctionInfo, Bid};
+
+
+
+pub fn try_create_auction(
+    deps: DepsMut,
+    env: Env,
+    seller: &Addr,
+    sell_balance: Balance,
+    msg: NewAuction,
+    ) -> Result<Response, ContractError> {
+
+    let config = STATE.load(deps.storage)?;
+
+    if !config.active {
+        return Err(ContractError::Stopped {});
+    }
+
+    if msg.end_time <= env.block.time.seconds() {
+        return Err(ContractError::Expired {});
+    }
+
+    let sell = parse_sell_balance(sell_balance)?;
+    let min_bid = Asset::from_balance(msg.min_bid, None)
+        .map_err(|_| ContractError::NoAskTokens {})?;
+
+    if sell.info == min_bid.info {
+        return Err(ContractError::SameToken {});
+    }
+
+    let bid = min_bid.info.validate(deps.api)?;
+
+    ensure_allowed(deps.storage, &sell.info)?;
+    ensure_allowed(deps.storage, &bid)?;
+
+    let auction = AuctionInfo {
+        seller: deps.api.addr_canonicalize(seller.as_str())?,
+        sell: sell.info,
+        sell_amount: sell.amount,
+        bid,
+        min_bid: min_bid.amount,
+        min_increment: msg.min_increment.unwrap_or(Uint128::one()).max(Uint128::one()),
+        highest_bid: None,
+        end_time: msg.end_time,
+        extension: msg.extension.unwrap_or_default(),
+        description: msg.description,
+    };
+
+    let id = AUCTION_INDEX.may_load(deps.storage)?.unwrap_or_default();
+
+    AUCTIONS.save(deps.storage, id, &auction)?;
+    AUCTION_INDEX.save(deps.storage, &(id + 1))?;
+
+    let data = NewAuctionResponse {
+        id,
+        auction: auction.clone()
+    };
+
+    Ok(Response::new()
+        .set_data(to_binary(&data)?)
+        .add_event(
+            Event::new("peerswap_auction_created")
+            .add_attributes(vec![
+                ("auction_id", id.to_string()),
+                ("seller", seller.to_string()),
+                ("amount", auction.sell_amount.to_string()),
+                ("token", auction.sell.token()),
+                ("bid_token", auction.bid.token()),
+                ("min_bid", auction.min_bid.to_string()),
+                ("end_time", auction.end_time.to_string()),
+                ("method", "create_auction".to_string())
+            ])
+        )
+    )
+}
+
+
+
+pub fn try_bid(
+    deps: DepsMut,
+    env: Env,
+    bidder: &Addr,
+    auction_id: u32,
+    balance: Balance,
+    ) -> Result<Response, ContractError> {
+
+    let mut auction = AUCTIONS.load(deps.storage, auction_id)
+        .map_err(|_| ContractError::NotFound {})?;
+
+    let now = env.block.time.seconds();
+
+    if now >= auction.end_time {
+        return Err(ContractError::Expired {});
+    }
+
+    let amount = Asset::from_balance(balance, None)
+        .ok()
+        .filter(|bid| bid.info == auction.bid)
+        .ok_or(ContractError::WrongDenom {})?
+        .amount;
+
+    let min_amount = match auction.highest_bid.as_ref() {
+        Some(highest) => highest.amount + auction.min_increment,
+        None => auction.min_bid
+    };
+
+    if amount < min_amount {
+        return Err(ContractError::BidTooLow {});
+    }
+
+    let mut refunds : Vec<CosmosMsg> = vec![];
+
+    if let Some(previous) = auction.highest_bid.take() {
+        refunds.push(auction.bid.transfer(&env, previous.bidder.as_str(), previous.amount)?);
+    }
+
+    auction.highest_bid = Some(Bid {
+        bidder: bidder.clone(),
+        amount
+    });
+
+    if auction.end_time - now < auction.extension {
+        auction.end_time = now + auction.extension;
+    }
+
+    AUCTIONS.save(deps.storage, auction_id, &auction)?;
+
+    Ok(Response::new()
+        .add_messages(refunds)
+        .add_event(
+            Event::new("peerswap_bid")
+            .add_attributes(vec![
+                ("auction_id", auction_id.to_string()),
+                ("bidder", bidder.to_string()),
+                ("amount", amount.to_string()),
+                ("token", auction.bid.token()),
+                ("end_time", auction.end_time.to_string()),
+                ("method", "bid".to_string())
+            ])
+        )
+    )
+}
+
+
+
+pub fn try_settle_auction(
+    deps: DepsMut,
+    env: Env,
+    auction_id: u32,
+    ) -> Result<Response, ContractError> {
+
+    let config = STATE.load(deps.storage)?;
+
+    let auction = AUCTIONS.load(deps.storage, auction_id)
+        .map_err(|_| ContractError::NotFound {})?;
+
+    if env.block.time.seconds() < auction.end_time {
+        return Err(ContractError::NotExpired {});
+    }
+
+    let seller = deps.api.addr_humanize(&auction.seller)?;
+    let admin = deps.api.addr_humanize(&config.admin)?.to_string();
+
+    let mut payments : Vec<CosmosMsg> = vec![];
+
+    let winner = match auction.highest_bid.as_ref() {
+        Some(highest) => {
+            let taker_fee = highest.amount * Decimal::from_ratio(config.taker_fee, 10000u16);
+            let maker_fee = auction.sell_amount * Decimal::from_ratio(config.maker_fee, 10000u16);
+
+            payments.push(auction.bid.transfer(&env, seller.as_str(), highest.amount - taker_fee)?);
+            payments.push(auction.sell.transfer(&env, highest.bidder.as_str(), auction.sell_amount - maker_fee)?);
+
+            // fees of small lots and bids round down to nothing
+            if !taker_fee.is_zero() {
+                payments.push(auction.bid.transfer(&env, &admin, taker_fee)?);
+            }
+            if !maker_fee.is_zero() {
+                payments.push(auction.sell.transfer(&env, &admin, maker_fee)?);
+            }
+
+            highest.bidder.clone()
+        },
+        // nobody bid so the lot goes back
+        None => {
+            payments.push(auction.sell.transfer(&env, seller.as_str(), auction.sell_amount)?);
+            seller.clone()
+        }
+    };
+
+    AUCTIONS.remove(deps.storage, auction_id);
+
+    Ok(Response::new()
+        .add_messages(payments)
+        .add_event(
+            Event::new("peerswap_auction_settled")
+            .add_attributes(vec![
+                ("auction_id", auction_id.to_string()),
+                ("seller", seller.to_string()),
+                ("winner", winner.to_string()),
+                ("amount", auction.sell_amount.to_string()),
+                ("token", auction.sell.token()),
+                ("price", auction.highest_bid.as_ref().map(|bid| bid.amount).unwrap_or_default().to_string()),
+                ("bid_token", auction.bid.token()),
+                ("method", "settle".to_string())
+            ])
+        )
+    )
+}
+
+
+
+pub fn try_cancel_auction(
+    deps: DepsMut,
+    env: Env,
+    sender: &Addr,
+    auction_id: u32,
+    ) -> Result<Response, ContractError> {
+
+    let auction = AUCTIONS.load(deps.storage, auction_id)
+        .map_err(|_| ContractError::NotFound {})?;
+
+    let seller = deps.api.addr_humanize(&auction.seller)?;
+    if sender != &seller {
+        return Err(ContractError::Unauthorized {});
+    }
+
+    if auction.highest_bid.is_some() {
+        return Err(ContractError::HasBids {});
+    }
+
+    AUCTIONS.remove(deps.storage, auction_id);
+
+    Ok(Response::new()
+        .add_message(auction.sell.transfer(&env, seller.as_str(), auction.sell_amount)?)
+        .add_event(
+            Event::new("peerswap_auction_cancelled")
+            .add_attributes(vec![
+                ("auction_id", auction_id.to_string()),
+                ("amount", auction.sell_amount.to_string()),
+                ("token", auction.sell.token()),
+                ("method", "cancel_auction".to_string())
+            ])
+        )
+    )
+}
+
+
+
+pub fn query_auction(
+    deps: Deps,
+    auction_id: u32
+) -> StdResult<AuctionInfo> {
+    AUCTIONS.load(deps.storage, auction_id)
+}
+
+
+
+pub fn query_auctions(
+    deps: Deps,
+    start_after: Option<u32>,
+    limit: Option<u32>,
+) -> StdResult<GetAuctionsResponse> {
+
+    let limit = limit.unwrap_or(DEFAULT_LIMIT).min(MAX_LIMIT) as usize;
+    let start = start_after.map(Bound::exclusive);
+
+    let auctions = AUCTIONS
+        .range(deps.storage, start, None, Order::Ascending)
+        .take(limit)
+        .collect::<StdResult<Vec<_>>>()?;
+
+    Ok(GetAuctionsResponse { auctions })
+}
@@ -1,29 +1,63 @@
 #[cfg(not(feature = "library"))]
 use cosmwasm_std::entry_point;
 use cosmwasm_std::{
-    Deps, DepsMut, Env, Response, StdResult, Event, Attribute, Addr,
-    MessageInfo, WasmMsg, BankMsg, CosmosMsg, Empty,
-    Coin, Order, Decimal, Uint128,
+    Deps, DepsMut, Env, Response, StdResult, StdError, Event, Attribute, Addr, Storage,
+    MessageInfo, SubMsg, Empty,
+    Order, Decimal, Uint128, Reply, SubMsgResult,
     Binary, to_binary, from_binary
 };
 use cw2::{get_contract_version, set_contract_version};
 
-use cw20::{Balance, Cw20ReceiveMsg, Cw20CoinVerified, Cw20ExecuteMsg};
+use cw20::{Balance, Cw20ReceiveMsg, Cw20CoinVerified};
 use cw_storage_plus::Bound;
 use cw_utils::Expiration;
 
 use semver::Version;
 
 use crate::error::ContractError;
-use crate::state::{State, STATE, OTCS, OTCInfo, UserInfo, AskFor};
-use crate::msg::{InstantiateMsg, QueryMsg, ExecuteMsg, ReceiveMsg, GetOTCsResponse, NewOTCResponse, GetConfigResponse};
+use crate::state::{State, STATE, OTCS, HTLCS, SEALED_AUCTIONS, WHITELIST_FILLED, IBC_CHANNELS, OTCInfo, AskFor, FiatEscrow, EscrowStatus, MilestoneEscrow, Milestone, Arbitration, IbcRecipient};
+use crate::ibc::{check_ibc_recipient, is_hook_sender, send_tokens, reply_ibc_transfer, IBC_TRANSFER_REPLY};
+use crate::htlc::{try_create_htlc, try_claim_htlc, try_refund_htlc, query_htlc};
+use crate::auction::{try_create_auction, try_bid, try_settle_auction, try_cancel_auction, query_auction, query_auctions};
+use crate::sealed_auction::{
+    try_create_sealed_auction, try_commit_bid, try_reveal_bid, try_settle_sealed_auction, try_withdraw_sealed_bid,
+    query_sealed_auction, query_sealed_bid
+};
+use crate::escrow::{try_payment_sent, try_release};
+use crate::arbitration::{
+    try_dispute, try_resolve, try_register_arbitrator, try_remove_arbitrator, query_arbitrators,
+    arbitrator_fee
+};
+use crate::registry::{
+    try_set_asset_list_mode, try_register_assets, try_remove_assets, try_set_cw20_code_ids, 
+    query_asset_list, ensure_allowed
+};
+use crate::metadata::{try_set_token_metadata, try_remove_token_metadata, cache_metadata, resolve_metadata, validate_cw20};
+use crate::remote::{try_remote_swap, query_remote_fill, is_remote_address};
+use crate::allowance::{available_allowance, release_tokens, invalidate_offer};
+use crate::asset::{Asset, AssetInfo};
+use crate::legacy::migrate_assets;
+use crate::cw1155::{Cw1155ReceiveMsg, Receiver};
+use crate::signed_order::{try_set_order_key, try_fill_signed_order, try_cancel_signed_order, query_order_status};
+use crate::milestone::{try_approve_milestone, query_milestones};
+use crate::market::{
+    try_create_market, try_stake, try_resolve_market, try_claim_winnings, query_market, query_stakes
+};
+use crate::counter::{try_propose_counter, try_accept_counter, try_withdraw_counter, query_counters, query_address_counters};
+use crate::vesting::{try_claim_vested, query_vesting_positions, new_vesting_position};
+use crate::raise::{
+    try_create_raise, try_contribute, try_finalize_raise, try_claim_tokens, try_claim_refund,
+    query_raise, query_contribution
+};
+use crate::msg::{InstantiateMsg, QueryMsg, ExecuteMsg, ReceiveMsg, NewOTC, SwapMsg, GetOTCsResponse, GetOTCResponse, NewOTCResponse, GetConfigResponse, SimulateSwapResponse};
+use crate::utils::{decode_hash, sha256, verify_merkle_proof};
 
 // version info for migration info
 const CONTRACT_NAME: &str = "crates.io:peerswap";
 const CONTRACT_VERSION: &str = env!("CARGO_PKG_VERSION");
 
-const DEFAULT_LIMIT: u32 = 20;
-const MAX_LIMIT: u32 = 60;
+pub const DEFAULT_LIMIT: u32 = 20;
+pub const MAX_LIMIT: u32 = 60;
 
 macro_rules! cast {
     ($target: expr, $pat: path) => {
@@ -50,12 +84,22 @@ pub fn instantiate(
 
     set_contract_version(deps.storage, CONTRACT_NAME, CONTRACT_VERSION)?;
 
+    // the reward is a share of the collected fees
+    if msg.keeper_reward.is_some_and(|reward| reward > 10000) {
+        return Err(ContractError::InvalidSplit {});
+    }
+
     let state = State {     
         active: true,
         index: 0,
         admin: deps.api.addr_canonicalize(info.sender.as_str())?,
         taker_fee: msg.taker_fee.unwrap_or(2u16),
         maker_fee: msg.maker_fee.unwrap_or(1u16),
+        keeper_reward: msg.keeper_reward.unwrap_or_default(),
+        arbitrator: msg.arbitrator
+            .map(|arbitrator| deps.api.addr_validate(&arbitrator))
+            .transpose()?,
+        cw20_code_ids: vec![],
     };
 
     STATE.save(deps.storage, &state)?;
@@ -82,19 +126,34 @@ pub fn execute(
             env,
             &info.sender,
             Balance::from(info.funds), 
-            msg.ask_balances,    
-            msg.expires,
-            msg.user_info,
-            msg.description
+            None,
+            msg
         ),
 
-        ExecuteMsg::Swap { otc_id } => try_swap(
+        ExecuteMsg::Swap(msg) => try_swap(
             deps,
             env,
             &info.sender, 
-            otc_id,
+            msg,
             Balance::from(info.funds),
-            true
+            true,
+            None
+        ),
+
+        ExecuteMsg::RemoteSwap(msg) => try_remote_swap(
+            deps,
+            env,
+            &info.sender,
+            msg,
+            Balance::from(info.funds)
+        ),
+
+        ExecuteMsg::Match { maker_otc, taker_otc } => try_match(
+            deps,
+            env,
+            &info.sender,
+            maker_otc,
+            taker_otc
         ),
 
         ExecuteMsg::Cancel { otc_id } => try_cancel_otc(
@@ -104,20 +163,318 @@ pub fn execute(
             otc_id
         ),
 
+        ExecuteMsg::SetOrderKey { pubkey } => try_set_order_key(
+            deps,
+            &info.sender,
+            pubkey
+        ),
+
+        ExecuteMsg::FillSignedOrder { order, signature } => try_fill_signed_order(
+            deps,
+            env,
+            &info.sender,
+            order,
+            signature,
+            Balance::from(info.funds)
+        ),
+
+        ExecuteMsg::CancelSignedOrder { nonce } => try_cancel_signed_order(
+            deps,
+            &info.sender,
+            nonce
+        ),
+
         ExecuteMsg::SetActive { active } => try_set_active(
             deps, 
             &info.sender, 
             active
         ),
 
+        ExecuteMsg::CreateHtlc(msg) => try_create_htlc(
+            deps,
+            env,
+            &info.sender,
+            Balance::from(info.funds),
+            msg
+        ),
+
+        ExecuteMsg::Claim { otc_id, preimage } => try_claim_htlc(
+            deps,
+            env,
+            otc_id,
+            preimage
+        ),
+
+        ExecuteMsg::Refund { otc_id } => try_refund_htlc(
+            deps,
+            env,
+            otc_id
+        ),
+
+        ExecuteMsg::CreateAuction(msg) => try_create_auction(
+            deps,
+            env,
+            &info.sender,
+            Balance::from(info.funds),
+            msg
+        ),
+
+        ExecuteMsg::Bid { auction_id } => try_bid(
+            deps,
+            env,
+            &info.sender,
+            auction_id,
+            Balance::from(info.funds)
+        ),
+
+        ExecuteMsg::Settle { auction_id } => {
+            if SEALED_AUCTIONS.has(deps.storage, auction_id) {
+                try_settle_sealed_auction(deps, env, auction_id)
+            } else {
+                try_settle_auction(deps, env, auction_id)
+            }
+        },
+
+        ExecuteMsg::CancelAuction { auction_id } => try_cancel_auction(
+            deps,
+            env,
+            &info.sender,
+            auction_id
+        ),
+
+        ExecuteMsg::CreateSealedAuction(msg) => try_create_sealed_auction(
+            deps,
+            env,
+            &info.sender,
+            Balance::from(info.funds),
+            msg
+        ),
+
+        ExecuteMsg::CommitBid { auction_id, commitment } => try_commit_bid(
+            deps,
+            env,
+            &info.sender,
+            auction_id,
+            commitment,
+            Balance::from(info.funds)
+        ),
+
+        ExecuteMsg::RevealBid { auction_id, amount, salt } => try_reveal_bid(
+            deps,
+            env,
+            &info.sender,
+            auction_id,
+            amount,
+            salt
+        ),
+
+        ExecuteMsg::WithdrawSealedBid { auction_id, bidder } => try_withdraw_sealed_bid(
+            deps,
+            env,
+            auction_id,
+            bidder
+        ),
+
+        ExecuteMsg::CreateRaise(msg) => try_create_raise(
+            deps,
+            env,
+            &info.sender,
+            Balance::from(info.funds),
+            msg
+        ),
+
+        ExecuteMsg::Contribute { raise_id } => try_contribute(
+            deps,
+            env,
+            &info.sender,
+            raise_id,
+            Balance::from(info.funds)
+        ),
+
+        ExecuteMsg::Finalize { raise_id } => try_finalize_raise(
+            deps,
+            env,
+            &info.sender,
+            raise_id
+        ),
+
+        ExecuteMsg::ClaimTokens { raise_id } => try_claim_tokens(
+            deps,
+            env,
+            &info.sender,
+            raise_id
+        ),
+
+        ExecuteMsg::ClaimRefund { raise_id } => try_claim_refund(
+            deps,
+            env,
+            &info.sender,
+            raise_id
+        ),
+
+        ExecuteMsg::ClaimVested {} => try_claim_vested(
+            deps,
+            env,
+            &info.sender
+        ),
+
+        ExecuteMsg::ProposeCounter { otc_id, amount } => try_propose_counter(
+            deps,
+            env,
+            &info.sender,
+            otc_id,
+            amount,
+            Balance::from(info.funds)
+        ),
+
+        ExecuteMsg::AcceptCounter { counter_id } => try_accept_counter(
+            deps,
+            env,
+            &info.sender,
+            counter_id
+        ),
+
+        ExecuteMsg::WithdrawCounter { counter_id } => try_withdraw_counter(
+            deps,
+            env,
+            &info.sender,
+            counter_id
+        ),
+
+        ExecuteMsg::PaymentSent { otc_id } => try_payment_sent(
+            deps,
+            env,
+            &info.sender,
+            otc_id
+        ),
+
+        ExecuteMsg::Release { otc_id } => try_release(
+            deps,
+            env,
+            &info.sender,
+            otc_id
+        ),
+
+        ExecuteMsg::ApproveMilestone { otc_id, milestone } => try_approve_milestone(
+            deps,
+            env,
+            &info.sender,
+            otc_id,
+            milestone
+        ),
+
+        ExecuteMsg::Dispute { otc_id } => try_dispute(
+            deps,
+            env,
+            &info.sender,
+            otc_id
+        ),
+
+        ExecuteMsg::Resolve { otc_id, split } => try_resolve(
+            deps,
+            env,
+            &info.sender,
+            otc_id,
+            split
+        ),
+
+        ExecuteMsg::RegisterArbitrator { address, fee, description } => try_register_arbitrator(
+            deps,
+            &info.sender,
+            address,
+            fee,
+            description
+        ),
+
+        ExecuteMsg::RemoveArbitrator { address } => try_remove_arbitrator(
+            deps,
+            &info.sender,
+            address
+        ),
+
+        ExecuteMsg::SetAssetListMode { mode } => try_set_asset_list_mode(
+            deps,
+            &info.sender,
+            mode
+        ),
+
+        ExecuteMsg::RegisterAssets { assets } => try_register_assets(
+            deps,
+            &info.sender,
+            assets
+        ),
+
+        ExecuteMsg::RemoveAssets { assets } => try_remove_assets(
+            deps,
+            &info.sender,
+            assets
+        ),
+
+        ExecuteMsg::SetCw20CodeIds { code_ids } => try_set_cw20_code_ids(
+            deps,
+            &info.sender,
+            code_ids
+        ),
+
+        ExecuteMsg::SetTokenMetadata { asset, name, symbol, decimals, logo } => try_set_token_metadata(
+            deps,
+            &info.sender,
+            asset,
+            name,
+            symbol,
+            decimals,
+            logo
+        ),
+
+        ExecuteMsg::RemoveTokenMetadata { asset } => try_remove_token_metadata(
+            deps,
+            &info.sender,
+            asset
+        ),
+
+        ExecuteMsg::CreateMarket(msg) => try_create_market(
+            deps,
+            env,
+            &info.sender,
+            msg
+        ),
+
+        ExecuteMsg::Stake { market_id, outcome } => try_stake(
+            deps,
+            env,
+            &info.sender,
+            market_id,
+            outcome,
+            Balance::from(info.funds)
+        ),
+
+        ExecuteMsg::ResolveMarket { market_id, outcome } => try_resolve_market(
+            deps,
+            env,
+            &info.sender,
+            market_id,
+            outcome
+        ),
+
+        ExecuteMsg::ClaimWinnings { market_id } => try_claim_winnings(
+            deps,
+            env,
+            &info.sender,
+            market_id
+        ),
+
         ExecuteMsg::RemoveExpired {} => remove_expired(
             deps, 
             env
         ),
         
-        ExecuteMsg::Receive(msg) => {
+        ExecuteMsg::Receive(Receiver::Cw20(msg)) => {
             execute_receive(deps, env, info, msg)
         }
+
+        ExecuteMsg::Receive(Receiver::Cw1155(msg)) => {
+            execute_receive_cw1155(deps, env, info, msg)
+        }
     }
 }
 
@@ -127,14 +484,32 @@ pub fn migrate(deps: DepsMut, _env: Env, _msg: Empty) -> Result<Response, Contra
     let version: Version = CONTRACT_VERSION.parse()?;
     let storage_version: Version = get_contract_version(deps.storage)?.version.parse()?;
 
+    let mut res = Response::new();
+
     if storage_version < version {
         set_contract_version(deps.storage, CONTRACT_NAME, CONTRACT_VERSION)?;
 
         // If state structure changed in any contract version in the way migration is needed, it
         // should occur here
+
+        // offers store typed assets since 0.4.0
+        if storage_version < Version::new(0, 4, 0) {
+            let migrated = migrate_assets(deps.storage)?;
+            res = res.add_attribute("migrated_otcs", migrated.to_string());
+        }
     }
 
-    Ok(Response::new())
+    Ok(res)
+}
+
+
+#[cfg_attr(not(feature = "library"), entry_point)]
+pub fn reply(deps: DepsMut, _env: Env, msg: Reply) -> Result<Response, ContractError> {
+    match (msg.id, msg.result) {
+        (IBC_TRANSFER_REPLY, SubMsgResult::Ok(response)) => reply_ibc_transfer(deps, response),
+        (_, SubMsgResult::Err(err)) => Err(StdError::generic_err(err).into()),
+        (id, _) => Err(StdError::generic_err(format!("unknown reply id {id}")).into())
+    }
 }
 
 
@@ -162,20 +537,114 @@ pub fn execute_receive(
                 env,
                 &api.addr_validate(&wrapper.sender)?,
                 balance,
-                msg.ask_balances, 
-                msg.expires,
-                msg.user_info,
-                msg.description
+                None,
+                msg
             )
         }
-        ReceiveMsg::Swap { otc_id } => {
+        ReceiveMsg::Swap(msg) => {
             try_swap(
                 deps, 
                 env,
                 &api.addr_validate(&wrapper.sender)?, 
-                otc_id,
+                msg,
+                balance,
+                false,
+                None
+            )
+        }
+        ReceiveMsg::CreateHtlc(msg) => {
+            try_create_htlc(
+                deps,
+                env,
+                &api.addr_validate(&wrapper.sender)?,
+                balance,
+                msg
+            )
+        }
+        ReceiveMsg::CreateAuction(msg) => {
+            try_create_auction(
+                deps,
+                env,
+                &api.addr_validate(&wrapper.sender)?,
+                balance,
+                msg
+            )
+        }
+        ReceiveMsg::Bid { auction_id } => {
+            try_bid(
+                deps,
+                env,
+                &api.addr_validate(&wrapper.sender)?,
+                auction_id,
+                balance
+            )
+        }
+        ReceiveMsg::CreateSealedAuction(msg) => {
+            try_create_sealed_auction(
+                deps,
+                env,
+                &api.addr_validate(&wrapper.sender)?,
+                balance,
+                msg
+            )
+        }
+        ReceiveMsg::CommitBid { auction_id, commitment } => {
+            try_commit_bid(
+                deps,
+                env,
+                &api.addr_validate(&wrapper.sender)?,
+                auction_id,
+                commitment,
+                balance
+            )
+        }
+        ReceiveMsg::CreateRaise(msg) => {
+            try_create_raise(
+                deps,
+                env,
+                &api.addr_validate(&wrapper.sender)?,
                 balance,
-                false
+                msg
+            )
+        }
+        ReceiveMsg::Contribute { raise_id } => {
+            try_contribute(
+                deps,
+                env,
+                &api.addr_validate(&wrapper.sender)?,
+                raise_id,
+                balance
+            )
+        }
+        ReceiveMsg::ProposeCounter { otc_id, amount } => {
+            try_propose_counter(
+                deps,
+                env,
+                &api.addr_validate(&wrapper.sender)?,
+                otc_id,
+                amount,
+                balance
+            )
+        }
+        ReceiveMsg::Stake { market_id, outcome } => {
+            try_stake(
+                deps,
+                env,
+                &api.addr_validate(&wrapper.sender)?,
+                market_id,
+                outcome,
+                balance
+            )
+        }
+
+        ReceiveMsg::FillSignedOrder { order, signature } => {
+            try_fill_signed_order(
+                deps,
+                env,
+                &api.addr_validate(&wrapper.sender)?,
+                order,
+                signature,
+                balance
             )
         }
     }
@@ -183,6 +652,48 @@ pub fn execute_receive(
 }
 
 
+/// Creates and swaps offers with cw1155 tokens, the other messages only take native tokens or cw20s
+pub fn execute_receive_cw1155(
+    deps: DepsMut,
+    env: Env,
+    info: MessageInfo,
+    wrapper: Cw1155ReceiveMsg,
+) -> Result<Response, ContractError> {
+    let msg : ReceiveMsg = from_binary(&wrapper.msg)?;
+
+    // the address of the cw1155 contract stands in for the token like for cw20s
+    let balance = Balance::Cw20(Cw20CoinVerified {
+        address: info.sender,
+        amount: wrapper.amount,
+    });
+
+    let sender = deps.api.addr_validate(wrapper.from.as_ref().unwrap_or(&wrapper.operator))?;
+
+    match msg {
+        ReceiveMsg::Create(msg) => try_create_otc(
+            deps,
+            env,
+            &sender,
+            balance,
+            Some(wrapper.token_id),
+            msg
+        ),
+
+        ReceiveMsg::Swap(msg) => try_swap(
+            deps,
+            env,
+            &sender,
+            msg,
+            balance,
+            false,
+            Some(wrapper.token_id)
+        ),
+
+        _ => Err(ContractError::InvalidCw1155Offer {})
+    }
+}
+
+
 pub fn try_set_active(
     deps: DepsMut,
     sender: &Addr,
@@ -199,79 +710,68 @@ pub fn try_set_active(
 
     STATE.save(deps.storage, &state)?;
 
-    return Ok(Response::new()
+    Ok(Response::new()
         .add_attribute("method", "set_active")
         .add_attribute("active", active.to_string())
-    );
+    )
 }
 
 
+/// Returns the tokens left in an offer, nothing for allowance offers that never held them
 pub fn refund_payment(
-    deps: Deps,
-    _env: Env,
+    deps: DepsMut,
+    env: Env,
     otc: &OTCInfo,
     seller: &Addr
-) -> CosmosMsg {
-    
-    let payment = if otc.sell_native {
-        CosmosMsg::Bank(BankMsg::Send {
-            to_address: seller.clone().to_string(),
-            amount: vec![Coin {
-                denom: otc.sell_denom.clone().unwrap().to_string(),
-                amount: otc.sell_amount.into(),
-            }],
-        })
-    } else {
-        CosmosMsg::Wasm(WasmMsg::Execute {
-            contract_addr: otc.sell_address.clone().unwrap().to_string(),
-            funds: vec![],
-            msg: to_binary(&Cw20ExecuteMsg::Transfer {
-                recipient: deps.api.addr_humanize(&otc.seller).unwrap().to_string(),
-                amount: otc.sell_amount.into(),
-            }).unwrap(),
-        })
-    };
+) -> StdResult<Option<SubMsg>> {
+    if otc.allowance {
+        return Ok(None);
+    }
 
-    payment
+    send_tokens(
+        deps,
+        &env,
+        &otc.sell,
+        seller,
+        otc.ibc_recipient.as_ref(),
+        otc.sell_amount
+    ).map(Some)
 }
 
 
 
 pub fn try_cancel_otc(
-    deps: DepsMut,
+    mut deps: DepsMut,
     env: Env,
     sender: &Addr,
     otc_id: u32,
     ) -> Result<Response, ContractError> {
 
-    let res = OTCS.load(deps.storage, otc_id);
-    if !res.is_ok() { return  Err(ContractError::NotFound {  }) }; 
-
-    let otc = res.unwrap();
+    let otc = OTCS.load(deps.storage, otc_id)
+        .map_err(|_| ContractError::NotFound {})?;
 
     let seller = deps.api.addr_humanize(&otc.seller)?;
     if sender != &seller {
         return Err(ContractError::Unauthorized {});
     };
 
-    let payment = refund_payment(deps.as_ref(), env, &otc, &seller);
+    // the counterparty may be paying or working already so escrows can only be cancelled after a timeout
+    if (otc.fiat_escrow.is_some() || otc.milestones.is_some() || otc.is_disputed()) && !otc.expires.is_expired(&env.block) {
+        return Err(ContractError::NotExpired {});
+    }
+
+    let payment = refund_payment(deps.branch(), env, &otc, &seller)?;
 
-    OTCS.remove(deps.storage, otc_id);
+    remove_otc(deps.storage, otc_id)?;
 
     Ok(Response::new()
-        .add_messages(vec!(
-            payment
-        ))
+        .add_submessages(payment)
         .add_event(
             Event::new("peerswap_cancel")
             .add_attributes(vec![
                 ("otc_id", otc_id.to_string()),
                 ("amount", otc.sell_amount.to_string()),
-                ("token", if otc.sell_native { 
-                    otc.sell_denom.unwrap() 
-                } else { 
-                    String::from("cw20:") + &otc.sell_address.unwrap().to_string() 
-                }),
+                ("token", otc.sell.to_string()),
                 ("method", "cancel".to_string())
             ])
 
@@ -282,7 +782,7 @@ pub fn try_cancel_otc(
 
 
 pub fn remove_expired(
-    deps: DepsMut,
+    mut deps: DepsMut,
     env: Env
 ) -> Result<Response, ContractError> {
 
@@ -306,16 +806,19 @@ pub fn remove_expired(
         ("method", String::from("remove_expired")),
     ];
 
+    let mut refunds : Vec<SubMsg> = vec![];
+
     for (id, otc) in expired_otcs {
         
-        refund_payment(deps.as_ref(), env.clone(), &otc, &deps.api.addr_humanize(&otc.seller).unwrap());
+        let seller = deps.api.addr_humanize(&otc.seller)?;
+        refunds.extend(refund_payment(deps.branch(), env.clone(), &otc, &seller)?);
         
-        OTCS.remove(deps.storage, id);
+        remove_otc(deps.storage, id)?;
         
         let log_text = format!("{} : {} {} to {}", 
                 id, 
                 otc.sell_amount, 
-                if otc.sell_native { otc.sell_denom.unwrap() } else { String::from("cw20:") + &otc.sell_address.unwrap().to_string() }, 
+                otc.sell, 
                 otc.seller
         );
 
@@ -324,6 +827,7 @@ pub fn remove_expired(
 
 
     Ok(Response::new()
+        .add_submessages(refunds)
         .add_event(
             Event::new("peerswap_remove_expired")
             .add_attributes(logs)
@@ -332,15 +836,53 @@ pub fn remove_expired(
 }
 
 
+/// Checks the tokens sent to be sold
+pub fn parse_sell_balance(balance: Balance) -> Result<Asset, ContractError> {
+
+    let sell = Asset::from_balance(balance, None)?;
+
+    if sell.amount < Uint128::from(10000u128) {
+        return Err(ContractError::TooSmall {});
+    }
+
+    Ok(sell)
+}
+
+
+/// Moves the index to the next id not taken by any kind of offer
+/// Removes an offer along with the whitelisted fills of its id, which can be reused
+pub fn remove_otc(storage: &mut dyn Storage, otc_id: u32) -> StdResult<()> {
+    OTCS.remove(storage, otc_id);
+
+    let takers = WHITELIST_FILLED
+        .prefix(otc_id)
+        .keys(storage, None, None, Order::Ascending)
+        .collect::<StdResult<Vec<Addr>>>()?;
+
+    for taker in takers {
+        WHITELIST_FILLED.remove(storage, (otc_id, &taker));
+    }
+
+    Ok(())
+}
+
+
+
+pub fn next_otc_id(storage: &dyn Storage, config: &mut State) {
+    while OTCS.has(storage, config.index) || HTLCS.has(storage, config.index) {
+        // rotate around ~4 billion
+        config.index = (config.index + 1) % u32::MAX;
+    }
+}
+
+
 pub fn try_create_otc(
-    deps: DepsMut,
+    mut deps: DepsMut,
     env: Env,
     seller: &Addr,
     sell_balance: Balance,
-    ask_balances: Vec<Balance>,
-    expires: Option<Expiration>,
-    user_info: Option<UserInfo>,
-    description: Option<String>,
+    sell_token_id: Option<String>,
+    msg: NewOTC,
     ) -> Result<Response, ContractError> {
     
 
@@ -351,114 +893,271 @@ pub fn try_create_otc(
         return Err(ContractError::Stopped {});
     }
 
-    let expires = expires.unwrap_or_default();
+    let mut expires = msg.expires.unwrap_or_default();
     if expires.is_expired(&env.block) {
         return Err(ContractError::Expired {});
     }
 
+    let fiat_escrow = match msg.fiat_escrow {
+        Some(escrow) => {
+            let arbitrator = escrow.arbitrator
+                .or_else(|| config.arbitrator.clone())
+                .ok_or(ContractError::InvalidEscrow {})?;
+
+            if !msg.ask_balances.is_empty() {
+                return Err(ContractError::InvalidEscrow {});
+            }
+
+            if escrow.payment_window == 0 || escrow.dispute_window == 0 {
+                return Err(ContractError::InvalidEscrowWindow {});
+            }
+
+            let arbitrator_fee = arbitrator_fee(deps.storage, &config, &arbitrator)?;
+
+            // the seller gets refunded if the buyer doesn't pay in time
+            expires = Expiration::AtTime(env.block.time.plus_seconds(escrow.payment_window));
+
+            Some(FiatEscrow {
+                buyer: deps.api.addr_validate(escrow.buyer.as_str())?,
+                status: EscrowStatus::Locked,
+                dispute_window: escrow.dispute_window,
+                arbitrator: Some(arbitrator),
+                arbitrator_fee,
+            })
+        },
+        None => None
+    };
+
+    let allowed_takers = msg.allowed_takers
+        .unwrap_or_default()
+        .iter()
+        .map(|taker| deps.api.addr_validate(taker.as_str()))
+        .collect::<StdResult<Vec<Addr>>>()?;
+
+    for hash in [msg.whitelist_root.as_ref(), msg.secret_hash.as_ref()].into_iter().flatten() {
+        decode_hash(hash).ok_or(ContractError::InvalidHash {})?;
+    }
+
+    if let Some(auction) = msg.dutch_auction.as_ref() {
+        if auction.start_time >= auction.end_time || auction.start_multiplier < Decimal::one() {
+            return Err(ContractError::InvalidAuction {});
+        }
+    }
+
+    let milestones = match msg.milestones {
+        Some(milestones) => {
+            if !msg.ask_balances.is_empty() || fiat_escrow.is_some() ||
+                matches!(expires, Expiration::Never {}) || milestones.milestones.is_empty() ||
+                milestones.milestones.iter().any(|milestone| milestone.amount.is_zero()) {
+                return Err(ContractError::InvalidMilestones {});
+            }
+
+            if let Some(arbitrator) = milestones.arbitrator.as_ref() {
+                arbitrator_fee(deps.storage, &config, arbitrator)?;
+            }
+
+            Some(MilestoneEscrow {
+                payee: deps.api.addr_validate(milestones.payee.as_str())?,
+                milestones: milestones.milestones
+                    .into_iter()
+                    .map(|milestone| Milestone { 
+                        amount: milestone.amount, 
+                        description: milestone.description, 
+                        released: false 
+                    })
+                    .collect(),
+                arbitrator: milestones.arbitrator,
+            })
+        },
+        None => None
+    };
+
+    // fiat escrows keep their own arbitrator
+    let arbitrator = msg.arbitrator.or_else(|| milestones.as_ref()
+        .and_then(|escrow| escrow.arbitrator.clone().or_else(|| config.arbitrator.clone())));
+
+    let arbitration = match arbitrator {
+        Some(arbitrator) => {
+            if fiat_escrow.is_some() || msg.remote.is_some() || msg.allowance.is_some() ||
+                (milestones.is_none() && allowed_takers.len() != 1) {
+                return Err(ContractError::InvalidArbitration {});
+            }
+
+            let arbitrator = deps.api.addr_validate(arbitrator.as_str())?;
+
+            Some(Arbitration {
+                arbitrator_fee: arbitrator_fee(deps.storage, &config, &arbitrator)?,
+                arbitrator,
+                disputed: false,
+            })
+        },
+        None => None
+    };
+
+    if let Some(vesting) = msg.vesting.as_ref() {
+        if vesting.duration == 0 || vesting.cliff > vesting.duration {
+            return Err(ContractError::InvalidVesting {});
+        }
+    }
+
+    let ask_balances = msg.ask_balances;
+
     let mut new_otc = OTCInfo {
         seller: deps.api.addr_canonicalize(seller.as_str())?,
         expires,
-        user_info,
-        description,
+        user_info: msg.user_info,
+        description: msg.description,
+        allowed_takers,
+        whitelist_root: msg.whitelist_root,
+        secret_hash: msg.secret_hash,
+        dutch_auction: msg.dutch_auction,
+        vesting: msg.vesting,
+        fiat_escrow,
+        milestones,
+        ibc_recipient: msg.ibc_recipient,
+        remote: msg.remote,
+        allowance: msg.allowance.is_some(),
+        invalid: false,
+        arbitration,
         // default feilds
-        sell_native: true,
+        sell: AssetInfo::Native(String::default()),
         sell_amount: Uint128::zero(),
         initial_sell_amount: Uint128::zero(),
-        sell_denom: None,
-        sell_address: None,
         ask_for: vec![],
     };
 
 
-    if ask_balances.len() == 0 {
+    if ask_balances.is_empty() && msg.ask_cw1155.as_ref().is_none_or(|asks| asks.is_empty()) &&
+        new_otc.fiat_escrow.is_none() && new_otc.milestones.is_none() {
         return Err(ContractError::NoAskTokens {});
     }
 
     
-    match sell_balance {
-        Balance::Native(mut balance) => {
-            let coin = balance.0.pop().unwrap();
-
-
-            if balance.0.len() != 0 {
-                return Err(ContractError::TooManyGiveTokens {});
+    // the seller keeps approved tokens until the offer is filled
+    let sell_balance = match msg.allowance {
+        Some(allowance) => {
+            if !sell_balance.is_empty() || new_otc.vesting.is_some() || new_otc.fiat_escrow.is_some() || 
+                new_otc.milestones.is_some() || new_otc.remote.is_some() {
+                return Err(ContractError::InvalidAllowanceOffer {});
             }
 
-            if coin.amount < Uint128::from(10000u128) {
-                return Err(ContractError::TooSmall {});
+            let address = deps.api.addr_validate(&allowance.address)?;
+
+            if available_allowance(deps.as_ref(), &env, address.as_str(), seller)? < allowance.amount {
+                return Err(ContractError::InsufficientAllowance {});
             }
 
-            new_otc.sell_native = true;
-            new_otc.sell_amount = coin.amount;
-            new_otc.initial_sell_amount = coin.amount;
-            new_otc.sell_denom = Some(coin.denom);
+            Balance::Cw20(Cw20CoinVerified { address, amount: allowance.amount })
         },
-        Balance::Cw20(token) => {
+        None => sell_balance
+    };
 
-            if token.amount < Uint128::from(10000u128) {
-                return Err(ContractError::TooSmall {});
-            }
+    // cw1155 tokens are usually sold in small whole amounts
+    let sell = match sell_token_id {
+        Some(_) => Asset::from_balance(sell_balance, sell_token_id)?,
+        None => parse_sell_balance(sell_balance)?
+    };
+    let sell_amount = sell.amount;
 
-            new_otc.sell_native = false;
-            new_otc.sell_amount = token.amount;
-            new_otc.initial_sell_amount = token.amount;
-            new_otc.sell_address = Some(token.address);
+    if let Some(milestones) = new_otc.milestones.as_ref() {
+        if milestones.milestones.iter().map(|milestone| milestone.amount).sum::<Uint128>() != sell_amount {
+            return Err(ContractError::InvalidMilestones {});
         }
-    };
+    }
 
-    
+    ensure_allowed(deps.storage, &sell.info)?;
+
+    new_otc.sell = sell.info;
+    new_otc.sell_amount = sell_amount;
+    new_otc.initial_sell_amount = sell_amount;
+
+    let mut asks : Vec<Asset> = vec![];
 
     for ask_balance in ask_balances {
         match ask_balance {
             Balance::Native(balance) => {
 
-                if balance.0.len() == 0 {
+                if balance.0.is_empty() {
                     return Err(ContractError::NoAskTokens {});
                 }
 
-                for coin in balance.0 {
+                asks.extend(balance.0.into_iter().map(|coin| Asset { 
+                    info: AssetInfo::Native(coin.denom), 
+                    amount: coin.amount 
+                }));
+            },
+
+            Balance::Cw20(token) => asks.push(Asset { 
+                info: AssetInfo::Cw20(token.address), 
+                amount: token.amount 
+            })
+        };
+    }
+
+    asks.extend(msg.ask_cw1155.unwrap_or_default().into_iter().map(|token| Asset {
+        info: AssetInfo::Cw1155 { address: Addr::unchecked(token.address), token_id: token.token_id },
+        amount: token.amount
+    }));
 
-                    if new_otc.sell_native && new_otc.sell_denom.clone().unwrap() == coin.denom {
-                        return Err(ContractError::SameToken {});
-                    } 
+    for ask in asks {
+        let info = ask.info.validate(deps.api).map_err(|err| match &ask.info {
+            AssetInfo::Cw20(address) => ContractError::InvalidToken { address: address.to_string() },
+            _ => err.into()
+        })?;
 
-                    new_otc.ask_for.push(AskFor {
-                        native: true,
-                        amount: coin.amount,
-                        initial_amount: coin.amount,
-                        denom: Some(coin.denom),
-                        address: None
-                    });
-                }
+        if info == new_otc.sell {
+            return Err(ContractError::SameToken {});
+        }
 
-            },
+        ensure_allowed(deps.storage, &info)?;
 
+        new_otc.ask_for.push(AskFor {
+            amount: ask.amount,
+            initial_amount: ask.amount,
+            info
+        });
+    }
 
-            Balance::Cw20(token) => {
+    // cw1155 tokens are only sent back and forth with `SendFrom`
+    let cw1155 = |info: &AssetInfo| matches!(info, AssetInfo::Cw1155 { .. });
 
-                if !new_otc.sell_native && new_otc.sell_address.clone().unwrap() == token.address {
-                    return Err(ContractError::SameToken {});
-                }
+    if (cw1155(&new_otc.sell) || new_otc.ask_for.iter().any(|ask| cw1155(&ask.info))) &&
+        (new_otc.vesting.is_some() || new_otc.fiat_escrow.is_some() || new_otc.milestones.is_some() || new_otc.remote.is_some()) {
+        return Err(ContractError::InvalidCw1155Offer {});
+    }
 
-                new_otc.ask_for.push(AskFor {
-                    native: false,
-                    amount: token.amount,
-                    initial_amount: token.amount,
-                    denom: None,
-                    address: Some(token.address)
-                })
-            }
-        };
+    if let Some(recipient) = new_otc.ibc_recipient.as_ref() {
+        check_ibc_recipient(recipient, new_otc.sell.is_native() && new_otc.ask_for.iter().all(|ask| ask.info.is_native()))?;
+    }
+
+    // the asks are denoms of the counterparty chain paid through the escrow there
+    if let Some(remote) = new_otc.remote.as_ref() {
+        if !is_remote_address(&remote.seller) || !new_otc.ask_for.iter().all(|ask| ask.info.is_native()) ||
+            new_otc.vesting.is_some() || new_otc.fiat_escrow.is_some() || new_otc.milestones.is_some() ||
+            new_otc.whitelist_root.is_some() || new_otc.secret_hash.is_some() || new_otc.ibc_recipient.is_some() {
+            return Err(ContractError::InvalidRemoteOffer {});
+        }
+
+        if !IBC_CHANNELS.has(deps.storage, &remote.channel) {
+            return Err(ContractError::WrongChannel {});
+        }
     }
 
  
 
-    while OTCS.has(deps.storage, config.index) {
-        // rotate around ~4 billion
-        config.index = config.index + 1 % u32::MAX;    
+    // asked cw20s must be able to pay the offer
+    for ask in new_otc.ask_for.iter() {
+        if let AssetInfo::Cw20(address) = &ask.info {
+            validate_cw20(deps.branch(), &config, address)?;
+        }
     }
 
+    for asset in new_otc.assets() {
+        cache_metadata(deps.branch(), asset)?;
+    }
+
+    next_otc_id(deps.storage, &mut config);
+
     OTCS.save(deps.storage, config.index, &new_otc)?;
     STATE.save(deps.storage, &config)?; 
    
@@ -476,7 +1175,7 @@ pub fn try_create_otc(
                 ("otc_id", &config.index.to_string()),
                 ("seller", &seller.to_string()),
                 ("amount", &new_otc.sell_amount.to_string()),
-                ("token", &new_otc.sell_denom.unwrap_or_else(|| String::from("cw20:") + &new_otc.sell_address.unwrap().to_string())),
+                ("token", &new_otc.sell.to_string()),
                 ("method", &"create_otc".to_string())
             ])
         )
@@ -489,210 +1188,206 @@ pub fn try_swap(
     deps: DepsMut,
     env: Env,
     payer: &Addr,
-    otc_id: u32,
+    msg: SwapMsg,
     balance: Balance,
     native: bool,
+    token_id: Option<String>,
     ) -> Result<Response, ContractError> {
 
 
-
-    let config = STATE.load(deps.storage)?;
+    let otc_id = msg.otc_id;
     let mut otc_info = OTCS.load(deps.storage, otc_id)?;
 
-    let seller = deps.api.addr_humanize(&otc_info.seller)?;
-
 
     let expires = otc_info.expires;
     if expires.is_expired(&env.block) {
         return Err(ContractError::Expired {});
     }
-   
-   let to_sell_amount : Uint128;
-   let swapped_amount : Uint128; 
-   let swapped_token : String;
 
-   let mut payments : Vec<CosmosMsg> = Vec::with_capacity(4);
+    if otc_info.remote.is_some() {
+        return Err(ContractError::RemoteOnly {});
+    }
 
-   let admin = deps.api.addr_humanize(&config.admin)?.to_string();
+    if otc_info.invalid {
+        return Err(ContractError::AllowanceRevoked {});
+    }
 
+    if !otc_info.is_allowed_taker(payer) {
+        return Err(ContractError::NotAllowedTaker {});
+    }
 
-   if native {
+    // vested tokens are claimed on this chain
+    if let Some(recipient) = msg.ibc_recipient.as_ref() {
+        check_ibc_recipient(recipient, otc_info.sell.is_native() && otc_info.vesting.is_none())?;
+    }
 
-        let mut casted =  cast!(balance, Balance::Native);
+    if let Some(secret_hash) = otc_info.secret_hash.as_ref() {
+        let secret = msg.secret.ok_or(ContractError::WrongSecret {})?;
+        if Some(sha256(secret.as_bytes())) != decode_hash(secret_hash) {
+            return Err(ContractError::WrongSecret {});
+        }
+    }
 
-        if casted.0.len() == 0 { return Err(ContractError::WrongDenom {} ); }
+    // whitelisted offers require a proof of the taker allocation
+    let allocation = match otc_info.whitelist_root.as_ref() {
+        Some(root) => {
+            let whitelist = msg.whitelist.ok_or(ContractError::NotAllowedTaker {})?;
+            let leaf = to_binary(&(payer, whitelist.allocation))?;
+            verify_merkle_proof(root, &leaf, &whitelist.proof)?;
+            Some(whitelist.allocation)
+        },
+        None => None
+    };
 
-        let coin = casted.0.pop().unwrap();
+    let swapped_amount : Uint128;
+    let swapped_token : String;
 
-        if casted.0.len() != 0 { return Err(ContractError::TooManyDenoms{}); }
+    if native {
 
-        if coin.amount != otc_info.sell_amount && coin.amount < Uint128::from(10000u128) {
-            return Err(ContractError::TooSmall {});
-        }
+        let mut casted =  cast!(balance, Balance::Native);
 
-        let to_pay = otc_info.ask_for
-            .iter()
-            .find(|ask| matches!(ask.native, true) && ask.denom.as_ref() == Some(&coin.denom))
-            .and_then(|ask| Some(ask))
-            .ok_or(ContractError::WrongDenom {})?;
+        if casted.0.is_empty() { return Err(ContractError::WrongDenom {} ); }
 
+        let coin = casted.0.pop().unwrap();
 
+        if !casted.0.is_empty() { return Err(ContractError::TooManyDenoms{}); }
 
-        let ratio = if to_pay.amount  > coin.amount  {
-                Decimal::from_ratio(to_pay.amount - coin.amount, to_pay.amount)
-            }
-            else {
-                Decimal::zero()
-        };
-        
-        to_sell_amount =  otc_info.sell_amount - otc_info.sell_amount * ratio;
-        otc_info.sell_amount -= to_sell_amount;
+        swapped_amount = coin.amount;
+        swapped_token = coin.denom;
 
-        swapped_amount = coin.amount.clone();
-        swapped_token = coin.denom.clone();
+    } else {
+        let casted = cast!(balance, Balance::Cw20);
 
-        otc_info.ask_for = otc_info.ask_for
-            .iter()
-            .map(|ask|  AskFor { amount: ask.amount * ratio, ..ask.clone() }
-            )
-            .collect();
+        swapped_amount = casted.amount;
+        swapped_token = casted.address.to_string();
+    };
 
-        let fee = coin.amount * Decimal::from_ratio(config.taker_fee, 10000u16);
 
-        payments.push(
-            CosmosMsg::Bank(BankMsg::Send { 
-                to_address: seller.clone().into_string(), 
-                amount: vec!(Coin { denom: coin.denom.clone(), amount: coin.amount - fee }) })
-        );
+    if swapped_amount != otc_info.sell_amount && swapped_amount < Uint128::from(10000u128) && token_id.is_none() {
+        return Err(ContractError::TooSmall {});
+    }
 
-        payments.push(
-            CosmosMsg::Bank(BankMsg::Send { 
-                to_address: admin.clone(), 
-                amount: vec!(Coin { denom: coin.denom, amount: fee }) })
-        )
-        
+    let paid = Asset {
+        info: AssetInfo::from_parts(native, swapped_token, token_id),
+        amount: swapped_amount
+    };
 
-    } else {
-        let casted = cast!(balance, Balance::Cw20);
+    let ask_index = otc_info.ask_for
+        .iter()
+        .position(|ask| ask.info == paid.info)
+        .ok_or(ContractError::WrongDenom {})?;
 
-        let to_pay = otc_info.ask_for
-            .iter()
-            .find(|ask| matches!(ask.native, false) && ask.address.as_ref() == Some(&casted.address))
-            .and_then(|ask| Some(ask))
-            .ok_or(ContractError::WrongDenom {})?;
 
-    
-        let ratio = if to_pay.amount  > casted.amount  {
-            //Decimal::one()
-            Decimal::from_ratio(to_pay.amount - casted.amount, to_pay.amount)
-        }
-        else {
-            Decimal::zero()
-        };
+    let to_sell_amount = otc_info.fill(ask_index, swapped_amount, env.block.time.seconds());
 
+    if let Some(allocation) = allocation {
+        let filled = WHITELIST_FILLED
+            .may_load(deps.storage, (otc_id, payer))?
+            .unwrap_or_default() + to_sell_amount;
 
-        if casted.amount != otc_info.sell_amount && casted.amount < Uint128::from(10000u128) {
-            return Err(ContractError::TooSmall {});
+        if filled > allocation {
+            return Err(ContractError::AllocationExceeded {});
         }
 
+        WHITELIST_FILLED.save(deps.storage, (otc_id, payer), &filled)?;
+    }
 
-        to_sell_amount =  otc_info.sell_amount - otc_info.sell_amount * ratio;
-        
-        otc_info.sell_amount -= to_sell_amount;
-
+    settle_fill(
+        deps, 
+        env, 
+        payer, 
+        otc_id, 
+        otc_info, 
+        paid, 
+        to_sell_amount, 
+        msg.ibc_recipient.as_ref(),
+        "swap"
+    )
+}
 
 
-        swapped_amount = casted.amount;
-        swapped_token = casted.address.to_string();
 
+/// Pays the seller of an offer the `paid` tokens and delivers `to_sell_amount` 
+/// released from it to the payer, vesting them if the offer has a schedule
+#[allow(clippy::too_many_arguments)]
+pub fn settle_fill(
+    mut deps: DepsMut,
+    env: Env,
+    payer: &Addr,
+    otc_id: u32,
+    otc_info: OTCInfo,
+    paid: Asset,
+    to_sell_amount: Uint128,
+    ibc_recipient: Option<&IbcRecipient>,
+    method: &str,
+    ) -> Result<Response, ContractError> {
 
-        otc_info.ask_for = otc_info.ask_for
-            .iter()
-            .map(|ask|  AskFor { amount: ask.amount * ratio, ..ask.clone() }
-            )
-            .collect();
+    let config = STATE.load(deps.storage)?;
+    let seller = deps.api.addr_humanize(&otc_info.seller)?;
 
-        let fee = casted.amount * Decimal::from_ratio(config.taker_fee, 10000u16);
+    if to_sell_amount.is_zero() {
+        return Err(ContractError::TooSmall {});
+    }
 
+    if otc_info.is_disputed() {
+        return Err(ContractError::WrongEscrowStatus {});
+    }
 
-        payments.push(
-            CosmosMsg::Wasm(WasmMsg::Execute { 
-                contract_addr: casted.address.to_string(), 
-                msg: to_binary(&Cw20ExecuteMsg::Transfer { 
-                    recipient: seller.to_string(), 
-                    amount: casted.amount - fee 
-                })?, 
-                funds: vec!()
-            })
-        );
+    // assets blocked after the offer was created can't be traded
+    ensure_allowed(deps.storage, &otc_info.sell)?;
+    ensure_allowed(deps.storage, &paid.info)?;
 
-        payments.push(
-            CosmosMsg::Wasm(WasmMsg::Execute { 
-                contract_addr: casted.address.to_string(), 
-                msg: to_binary(&Cw20ExecuteMsg::Transfer { 
-                    recipient: admin.clone(),
-                    amount: fee 
-                })?, 
-                funds: vec!()
-            })
-        )
-        
+    // intermediaries of ibc-hooks get their refunds over IBC
+    let refund_recipient = match ibc_recipient {
+        Some(remote) if is_hook_sender(deps.as_ref(), payer, remote)? => Some(remote),
+        _ => None
     };
 
+    if otc_info.allowance && available_allowance(deps.as_ref(), &env, &otc_info.sell.token(), &seller)? < to_sell_amount {
+        return invalidate_offer(deps, &env, otc_id, payer, refund_recipient, paid, method);
+    }
 
-    let fee = to_sell_amount * Decimal::from_ratio(config.maker_fee, 10000u16);
-
-
-    if otc_info.sell_native {
+    let swapped_amount = paid.amount;
 
-        payments.push(
-            CosmosMsg::Bank(BankMsg::Send { 
-                to_address: payer.clone().into_string(), 
-                amount: vec!(Coin { 
-                    denom: otc_info.sell_denom.clone().unwrap(), 
-                    amount: to_sell_amount - fee
-                }) 
-            })
-        );
+    let admin = deps.api.addr_humanize(&config.admin)?.to_string();
 
-        payments.push(
-            CosmosMsg::Bank(BankMsg::Send { 
-                to_address: admin, 
-                amount: vec!(Coin { 
-                    denom: otc_info.sell_denom.clone().unwrap(), 
-                    amount: fee
-                }) 
-            })
-        )
+    let taker_fee = swapped_amount * Decimal::from_ratio(config.taker_fee, 10000u16);
+    let maker_fee = to_sell_amount * Decimal::from_ratio(config.maker_fee, 10000u16);
 
-    } else {
+    let mut payments : Vec<SubMsg> = vec![
+        send_tokens(deps.branch(), &env, &paid.info, &seller, otc_info.ibc_recipient.as_ref(), swapped_amount - taker_fee)?,
+    ];
 
+    // fees of small cw1155 fills round down to nothing
+    if !taker_fee.is_zero() {
+        payments.push(SubMsg::new(paid.info.transfer(&env, &admin, taker_fee)?));
+    }
 
-        payments.push(
-            CosmosMsg::Wasm(WasmMsg::Execute { 
-                contract_addr: otc_info.sell_address.clone().unwrap().to_string(), 
-                msg: to_binary(&Cw20ExecuteMsg::Transfer { 
-                    recipient: payer.to_string(), 
-                    amount: to_sell_amount - fee
-                })?, 
-                funds: vec!()
-            })
-        );
-        
+    // vested tokens stay in the contract until claimed
+    let vesting_id = match otc_info.vesting.as_ref() {
+        Some(_) => Some(new_vesting_position(
+            deps.storage, 
+            payer, 
+            otc_id, 
+            &otc_info, 
+            to_sell_amount - maker_fee, 
+            env.block.time.seconds()
+        )?),
+        None => {
+            payments.push(match ibc_recipient {
+                // refunded to the payer if the transfer fails
+                Some(remote) => send_tokens(deps.branch(), &env, &otc_info.sell, payer, Some(remote), to_sell_amount - maker_fee)?,
+                None => SubMsg::new(release_tokens(&env, &otc_info, &seller, payer.as_str(), to_sell_amount - maker_fee)?)
+            });
+            None
+        }
+    };
 
-        payments.push(
-            CosmosMsg::Wasm(WasmMsg::Execute { 
-                contract_addr: otc_info.sell_address.clone().unwrap().to_string(), 
-                msg: to_binary(&Cw20ExecuteMsg::Transfer { 
-                    recipient: admin, 
-                    amount: fee 
-                })?, 
-                funds: vec!()
-            })
-        )
-    };  
+    if !maker_fee.is_zero() {
+        payments.push(SubMsg::new(release_tokens(&env, &otc_info, &seller, &admin, maker_fee)?));
+    }
 
-    let attributes: Vec<Attribute> = vec![
+    let mut attributes: Vec<Attribute> = vec![
         Attribute {
             key: String::from("seller"),
             value: seller.to_string()
@@ -710,11 +1405,7 @@ pub fn try_swap(
 
         Attribute {
             key: String::from("given_token"),
-            value: if otc_info.sell_native { 
-                otc_info.sell_denom.clone().unwrap() 
-            } else { 
-                otc_info.sell_address.clone().unwrap().to_string() 
-            }
+            value: otc_info.sell.token()
         },
 
         Attribute {
@@ -724,19 +1415,26 @@ pub fn try_swap(
 
         Attribute {
             key: String::from("sent_token"),
-            value: swapped_token
+            value: paid.info.token()
         },
 
         Attribute {
             key: String::from("method"),
-            value: String::from("swap")
+            value: String::from(method)
         }
     ];
 
+    if let Some(vesting_id) = vesting_id {
+        attributes.push(Attribute {
+            key: String::from("vesting_id"),
+            value: vesting_id.to_string()
+        });
+    }
+
 
 
-    let event_type = if otc_info.sell_amount <= Uint128::zero() {
-        OTCS.remove(deps.storage, otc_id);
+    let event_type = if otc_info.sell_amount.is_zero() {
+        remove_otc(deps.storage, otc_id)?;
         "peerswap_swap_completed"
     } else {
         OTCS.save(deps.storage, otc_id, &otc_info)?;
@@ -745,26 +1443,182 @@ pub fn try_swap(
 
 
     Ok(Response::new()
-        .add_messages(payments)
+        .add_submessages(payments)
         .add_event(Event::new(event_type).add_attributes(attributes))
     )
 }
 
 
 
+/// Settles two offers selling each other's ask tokens at the price of the maker offer.
+/// The taker offer gets the price improvement and the caller receives the keeper reward
+pub fn try_match(
+    mut deps: DepsMut,
+    env: Env,
+    keeper: &Addr,
+    maker_otc: u32,
+    taker_otc: u32,
+    ) -> Result<Response, ContractError> {
+
+    if maker_otc == taker_otc {
+        return Err(ContractError::NotMatching {});
+    }
+
+    let config = STATE.load(deps.storage)?;
+
+    if !config.active {
+        return Err(ContractError::Stopped {});
+    }
+
+    let mut maker = OTCS.load(deps.storage, maker_otc)
+        .map_err(|_| ContractError::NotFound {})?;
+    let mut taker = OTCS.load(deps.storage, taker_otc)
+        .map_err(|_| ContractError::NotFound {})?;
+
+    if maker.expires.is_expired(&env.block) || taker.expires.is_expired(&env.block) {
+        return Err(ContractError::Expired {});
+    }
+
+    if maker.is_disputed() || taker.is_disputed() {
+        return Err(ContractError::WrongEscrowStatus {});
+    }
+
+    ensure_allowed(deps.storage, &maker.sell)?;
+    ensure_allowed(deps.storage, &taker.sell)?;
+
+    let maker_seller = deps.api.addr_humanize(&maker.seller)?;
+    let taker_seller = deps.api.addr_humanize(&taker.seller)?;
+
+    // private offers can only be matched with their counterparties
+    if !maker.is_allowed_taker(&taker_seller) || !taker.is_allowed_taker(&maker_seller) ||
+        maker.whitelist_root.is_some() || taker.whitelist_root.is_some() ||
+        maker.secret_hash.is_some() || taker.secret_hash.is_some() {
+        return Err(ContractError::NotAllowedTaker {});
+    }
+
+    // vested deliveries are only supported for direct swaps
+    if maker.vesting.is_some() || taker.vesting.is_some() {
+        return Err(ContractError::NotMatching {});
+    }
+
+    if maker.remote.is_some() || taker.remote.is_some() {
+        return Err(ContractError::RemoteOnly {});
+    }
+
+    // the tokens of allowance offers aren't held by the contract
+    if maker.allowance || taker.allowance {
+        return Err(ContractError::NotMatching {});
+    }
+
+    if matches!(maker.sell, AssetInfo::Cw1155 { .. }) || matches!(taker.sell, AssetInfo::Cw1155 { .. }) {
+        return Err(ContractError::InvalidCw1155Offer {});
+    }
+
+    // the maker asks for what the taker sells and vice versa
+    let maker_ask = maker.ask_for
+        .iter()
+        .position(|ask| ask.info == taker.sell)
+        .ok_or(ContractError::NotMatching {})?;
+
+    let taker_ask = taker.ask_for
+        .iter()
+        .position(|ask| ask.info == maker.sell)
+        .ok_or(ContractError::NotMatching {})?;
+
+    let now = env.block.time.seconds();
+    let maker_price = maker.current_asks(now)[maker_ask].amount;
+    let taker_price = taker.current_asks(now)[taker_ask].amount;
+
+    // maker price must not be worse than the taker price:
+    // maker.sell / maker.ask >= taker.ask / taker.sell
+    let maker_side = maker.sell_amount.full_mul(taker.sell_amount);
+    let taker_side = maker_price.full_mul(taker_price);
+
+    if maker_side < taker_side {
+        return Err(ContractError::NotMatching {});
+    }
+
+    let to_fill = maker_price.min(taker.sell_amount);
+
+    if to_fill.is_zero() {
+        return Err(ContractError::TooSmall {});
+    }
+
+    // what the taker offer releases pays for the maker offer
+    let taker_given = taker.release(to_fill);
+    let maker_given = maker.fill(maker_ask, taker_given, now);
+
+    if maker_given.is_zero() {
+        return Err(ContractError::TooSmall {});
+    }
+
+    let admin = deps.api.addr_humanize(&config.admin)?.to_string();
+
+    let taker_fee = taker_given * Decimal::from_ratio(config.taker_fee, 10000u16);
+    let maker_fee = maker_given * Decimal::from_ratio(config.maker_fee, 10000u16);
+
+    let taker_reward = taker_fee * Decimal::from_ratio(config.keeper_reward, 10000u16);
+    let maker_reward = maker_fee * Decimal::from_ratio(config.keeper_reward, 10000u16);
+
+    let mut payments : Vec<SubMsg> = vec![
+        send_tokens(deps.branch(), &env, &taker.sell, &maker_seller, maker.ibc_recipient.as_ref(), taker_given - taker_fee)?,
+        send_tokens(deps.branch(), &env, &maker.sell, &taker_seller, taker.ibc_recipient.as_ref(), maker_given - maker_fee)?,
+    ];
+
+    for (asset, fee, reward) in [
+        (&taker.sell, taker_fee, taker_reward),
+        (&maker.sell, maker_fee, maker_reward),
+    ] {
+        if !(fee - reward).is_zero() {
+            payments.push(SubMsg::new(asset.transfer(&env, &admin, fee - reward)?));
+        }
+        if !reward.is_zero() {
+            payments.push(SubMsg::new(asset.transfer(&env, keeper.as_str(), reward)?));
+        }
+    }
+
+    let attributes = vec![
+        ("maker_otc", maker_otc.to_string()),
+        ("taker_otc", taker_otc.to_string()),
+        ("maker_given_amount", maker_given.to_string()),
+        ("maker_given_token", maker.sell.token()),
+        ("taker_given_amount", taker_given.to_string()),
+        ("taker_given_token", taker.sell.token()),
+        ("keeper", keeper.to_string()),
+        ("method", String::from("match")),
+    ];
+
+    for (id, otc) in [(maker_otc, &maker), (taker_otc, &taker)] {
+        if otc.sell_amount.is_zero() {
+            remove_otc(deps.storage, id)?;
+        } else {
+            OTCS.save(deps.storage, id, otc)?;
+        }
+    }
+
+    Ok(Response::new()
+        .add_submessages(payments)
+        .add_event(Event::new("peerswap_match").add_attributes(attributes))
+    )
+}
+
+
+
 #[cfg_attr(not(feature = "library"), entry_point)]
 pub fn query(deps: Deps, env: Env, msg: QueryMsg) -> StdResult<Binary> {
     match msg {
         QueryMsg::GetOtcs {
             include_expired, 
             start_after, 
-            limit 
+            limit,
+            querier
         } => to_binary(&query_otcs(
             deps, 
             env, 
             include_expired.unwrap_or_default(),
             start_after,
-            limit
+            limit,
+            querier
         )?),
 
         QueryMsg::GetAddressOtcs { 
@@ -788,6 +1642,164 @@ pub fn query(deps: Deps, env: Env, msg: QueryMsg) -> StdResult<Binary> {
             otc_id
         )?),
 
+        QueryMsg::GetHtlc {
+            otc_id, 
+        } => to_binary(&query_htlc(
+            deps, 
+            otc_id
+        )?),
+
+        QueryMsg::GetAuction {
+            auction_id
+        } => to_binary(&query_auction(
+            deps,
+            auction_id
+        )?),
+
+        QueryMsg::GetAuctions {
+            start_after,
+            limit
+        } => to_binary(&query_auctions(
+            deps,
+            start_after,
+            limit
+        )?),
+
+        QueryMsg::GetSealedAuction {
+            auction_id
+        } => to_binary(&query_sealed_auction(
+            deps,
+            auction_id
+        )?),
+
+        QueryMsg::GetSealedBid {
+            auction_id,
+            bidder
+        } => to_binary(&query_sealed_bid(
+            deps,
+            auction_id,
+            bidder
+        )?),
+
+        QueryMsg::GetRaise {
+            raise_id
+        } => to_binary(&query_raise(
+            deps,
+            raise_id
+        )?),
+
+        QueryMsg::GetContribution {
+            raise_id,
+            contributor
+        } => to_binary(&query_contribution(
+            deps,
+            raise_id,
+            contributor
+        )?),
+
+        QueryMsg::GetVestingPositions {
+            owner,
+            start_after,
+            limit
+        } => to_binary(&query_vesting_positions(
+            deps,
+            env,
+            owner,
+            start_after,
+            limit
+        )?),
+
+        QueryMsg::GetArbitrators {
+            start_after,
+            limit
+        } => to_binary(&query_arbitrators(
+            deps,
+            start_after,
+            limit
+        )?),
+
+        QueryMsg::GetAssetList {
+            start_after,
+            limit
+        } => to_binary(&query_asset_list(
+            deps,
+            start_after,
+            limit
+        )?),
+
+        QueryMsg::GetMarket {
+            market_id
+        } => to_binary(&query_market(
+            deps,
+            market_id
+        )?),
+
+        QueryMsg::GetStakes {
+            market_id,
+            address
+        } => to_binary(&query_stakes(
+            deps,
+            market_id,
+            address
+        )?),
+
+        QueryMsg::GetMilestones {
+            otc_id
+        } => to_binary(&query_milestones(
+            deps,
+            otc_id
+        )?),
+
+        QueryMsg::GetRemoteFill {
+            fill_id
+        } => to_binary(&query_remote_fill(
+            deps,
+            fill_id
+        )?),
+
+        QueryMsg::GetOrderStatus {
+            maker,
+            nonce
+        } => to_binary(&query_order_status(
+            deps,
+            maker,
+            nonce
+        )?),
+
+        QueryMsg::GetCounters {
+            otc_id,
+            start_after,
+            limit
+        } => to_binary(&query_counters(
+            deps,
+            otc_id,
+            start_after,
+            limit
+        )?),
+
+        QueryMsg::GetAddressCounters {
+            proposer,
+            start_after,
+            limit
+        } => to_binary(&query_address_counters(
+            deps,
+            proposer,
+            start_after,
+            limit
+        )?),
+
+        QueryMsg::SimulateSwap {
+            otc_id,
+            token,
+            amount
+        } => to_binary(&query_simulate_swap(
+            deps,
+            env,
+            otc_id,
+            token,
+            amount
+        )?),
+
         QueryMsg::Config {} => to_binary(&query_config(deps)?),
     }
 }
@@ -800,14 +1812,16 @@ fn query_otcs(
     include_expired: bool,
     start_after: Option<u32>,
     limit: Option<u32>,
+    querier: Option<Addr>,
 ) -> StdResult<GetOTCsResponse> {
 
     let limit = limit.unwrap_or(DEFAULT_LIMIT).min(MAX_LIMIT) as usize;
     
-    let start = match start_after {
-        Some(start_after) => Some(Bound::exclusive(start_after)),
-        None => None
-    };
+    let start = start_after.map(Bound::exclusive);
+
+    let querier = querier
+        .map(|querier| deps.api.addr_canonicalize(querier.as_str()).map(|canon| (querier, canon)))
+        .transpose()?;
 
     let result : StdResult<Vec<_>> = OTCS
     .range(
@@ -824,11 +1838,21 @@ fn query_otcs(
             !otc.as_ref().unwrap().1.expires.is_expired(&env.block)
         } 
     )
+    // private offers are only shown to their sellers and allowed takers
+    .filter(|otc| {
+        let otc = &otc.as_ref().unwrap().1;
+        otc.allowed_takers.is_empty() || querier.as_ref().is_some_and(|(querier, canon)| 
+            &otc.seller == canon || otc.is_allowed_taker(querier)
+        )
+    })
     .take(limit)
     .collect();
 
+    let otcs = result?;
+    let metadata = resolve_metadata(deps, otcs.iter().flat_map(|(_, otc)| otc.assets()))?;
+
     //OTCS.load(deps.storage, )
-    Ok(GetOTCsResponse { otcs: result? })
+    Ok(GetOTCsResponse { otcs, metadata })
 }
 
 
@@ -843,10 +1867,7 @@ fn query_addr_otcs(
 
     let limit = limit.unwrap_or(DEFAULT_LIMIT).min(MAX_LIMIT) as usize;
     
-    let start = match start_after {
-        Some(start_after) => Some(Bound::exclusive(start_after)),
-        None => None
-    };
+    let start = start_after.map(Bound::exclusive);
 
     
     let canon = deps.api.addr_canonicalize(addr.as_str())?;
@@ -861,14 +1882,7 @@ fn query_addr_otcs(
     .filter(|otc| {
         if otc.is_ok() {
             let otc = &otc.as_ref().unwrap().1;
-            let mut ok = otc.seller == canon;
-            if ok {
-                if !include_expired {
-                    ok = !otc.expires.is_expired(&env.block);
-             
-                }
-            }
-            ok
+            otc.seller == canon && (include_expired || !otc.expires.is_expired(&env.block))
         } else {
             false
         }
@@ -877,7 +1891,10 @@ fn query_addr_otcs(
     .take(limit)
     .collect();
 
-    Ok(GetOTCsResponse { otcs: result? })
+    let otcs = result?;
+    let metadata = resolve_metadata(deps, otcs.iter().flat_map(|(_, otc)| otc.assets()))?;
+
+    Ok(GetOTCsResponse { otcs, metadata })
 }
 
 
@@ -885,20 +1902,56 @@ fn query_addr_otcs(
 fn query_otc(
     deps: Deps, 
     otc_id: u32
-) -> StdResult<OTCInfo> {
-    Ok(OTCS.load(deps.storage, otc_id)?)
+) -> StdResult<GetOTCResponse> {
+    let otc = OTCS.load(deps.storage, otc_id)?;
+    let metadata = resolve_metadata(deps, otc.assets())?;
+
+    Ok(GetOTCResponse { otc, metadata })
 }
 
 
 
 
+fn query_simulate_swap(
+    deps: Deps,
+    env: Env,
+    otc_id: u32,
+    token: String,
+    amount: Uint128
+) -> StdResult<SimulateSwapResponse> {
+
+    let config = STATE.load(deps.storage)?;
+    let mut otc = OTCS.load(deps.storage, otc_id)?;
+
+    let now = env.block.time.seconds();
+    let ask_for = otc.current_asks(now);
+
+    let ask_index = ask_for
+        .iter()
+        .position(|ask| ask.info.token() == token)
+        .ok_or_else(|| StdError::generic_err(ContractError::WrongDenom {}.to_string()))?;
+
+    let given_amount = otc.fill(ask_index, amount, now);
+
+    Ok(SimulateSwapResponse {
+        given_amount,
+        maker_fee: given_amount * Decimal::from_ratio(config.maker_fee, 10000u16),
+        ask_for,
+    })
+}
+
+
+
 fn query_config(deps: Deps) -> StdResult<GetConfigResponse> {
     let config = STATE.load(deps.storage)?;
     Ok(GetConfigResponse {
         active: config.active,
         maker_fee: config.maker_fee,
         taker_fee: config.taker_fee,
+        keeper_reward: config.keeper_reward,
         admin: deps.api.addr_humanize(&config.admin)?.to_string(),
+        arbitrator: config.arbitrator.map(|arbitrator| arbitrator.to_string()),
+        cw20_code_ids: config.cw20_code_ids,
     })
 }
 
@@ -0,0 +1,247 @@
+use cosmwasm_std::{
+    Deps, DepsMut, Env, Response, StdResult, Event, Addr, Order, Uint128, Empty, Storage,
+    to_binary
+};
+use cw20::Balance;
+use cw_storage_plus::Bound;
+
+use crate::asset::Asset;
+use crate::contract::{settle_fill, DEFAULT_LIMIT, MAX_LIMIT};
+use crate::error::ContractError;
+use crate::msg::{GetCountersResponse, NewCounterResponse};
+use crate::state::{STATE, OTCS, COUNTERS, COUNTER_INDEX, OTC_COUNTERS, PROPOSER_COUNTERS, Counter};
+
+
+
+/// Removes the counter along with its entries in the indexes
+fn remove_counter(storage: &mut dyn Storage, counter_id: u32, counter: &Counter) {
+    COUNTERS.remove(storage, counter_id);
+    OTC_COUNTERS.remove(storage, (counter.otc_id, counter_id));
+    PROPOSER_COUNTERS.remove(storage, (&counter.proposer, counter_id));
+}
+
+
+pub fn try_propose_counter(
+    deps: DepsMut,
+    env: Env,
+    proposer: &Addr,
+    otc_id: u32,
+    amount: Uint128,
+    balance: Balance,
+    ) -> Result<Response, ContractError> {
+
+    let config = STATE.load(deps.storage)?;
+
+    if !config.active {
+        return Err(ContractError::Stopped {});
+    }
+
+    let otc = OTCS.load(deps.storage, otc_id)
+        .map_err(|_| ContractError::NotFound {})?;
+
+    if otc.expires.is_expired(&env.block) {
+        return Err(ContractError::Expired {});
+    }
+
+    if otc.remote.is_some() {
+        return Err(ContractError::RemoteOnly {});
+    }
+
+    if otc.invalid {
+        return Err(ContractError::AllowanceRevoked {});
+    }
+
+    if otc.is_disputed() {
+        return Err(ContractError::WrongEscrowStatus {});
+    }
+
+    if !otc.is_allowed_taker(proposer) {
+        return Err(ContractError::NotAllowedTaker {});
+    }
+
+    if amount.is_zero() {
+        return Err(ContractError::TooSmall {});
+    }
+
+    if amount > otc.sell_amount {
+        return Err(ContractError::ExceedsOffer {});
+    }
+
+    let paid = Asset::from_balance(balance, None)
+        .map_err(|_| ContractError::WrongDenom {})?;
+
+    // only tokens the seller asks for can be proposed
+    if !otc.ask_for.iter().any(|ask| ask.info == paid.info) {
+        return Err(ContractError::WrongDenom {});
+    }
+
+    let counter = Counter {
+        otc_id,
+        proposer: proposer.clone(),
+        asset: paid.info,
+        paid: paid.amount,
+        amount,
+        sell: otc.sell.clone(),
+        initial_sell_amount: otc.initial_sell_amount,
+    };
+
+    let id = COUNTER_INDEX.may_load(deps.storage)?.unwrap_or_default();
+
+    COUNTERS.save(deps.storage, id, &counter)?;
+    OTC_COUNTERS.save(deps.storage, (otc_id, id), &Empty {})?;
+    PROPOSER_COUNTERS.save(deps.storage, (proposer, id), &Empty {})?;
+    COUNTER_INDEX.save(deps.storage, &(id + 1))?;
+
+    let data = NewCounterResponse {
+        id,
+        counter: counter.clone()
+    };
+
+    Ok(Response::new()
+        .set_data(to_binary(&data)?)
+        .add_event(
+            Event::new("peerswap_counter_proposed")
+            .add_attributes(vec![
+                ("counter_id", id.to_string()),
+                ("otc_id", otc_id.to_string()),
+                ("proposer", proposer.to_string()),
+                ("sent_amount", counter.paid.to_string()),
+                ("sent_token", counter.asset.token()),
+                ("amount", amount.to_string()),
+                ("token", otc.sell.token()),
+                ("method", "propose_counter".to_string())
+            ])
+        )
+    )
+}
+
+
+
+pub fn try_accept_counter(
+    deps: DepsMut,
+    env: Env,
+    sender: &Addr,
+    counter_id: u32,
+    ) -> Result<Response, ContractError> {
+
+    let counter = COUNTERS.load(deps.storage, counter_id)
+        .map_err(|_| ContractError::NotFound {})?;
+
+    let mut otc = OTCS.load(deps.storage, counter.otc_id)
+        .map_err(|_| ContractError::NotFound {})?;
+
+    if sender != &deps.api.addr_humanize(&otc.seller)? {
+        return Err(ContractError::Unauthorized {});
+    }
+
+    if otc.expires.is_expired(&env.block) {
+        return Err(ContractError::Expired {});
+    }
+
+    if otc.sell != counter.sell || otc.initial_sell_amount != counter.initial_sell_amount {
+        return Err(ContractError::StaleCounter {});
+    }
+
+    if !otc.ask_for.iter().any(|ask| ask.info == counter.asset) {
+        return Err(ContractError::StaleCounter {});
+    }
+
+    if counter.amount > otc.sell_amount {
+        return Err(ContractError::ExceedsOffer {});
+    }
+
+    let to_sell_amount = otc.release(counter.amount);
+
+    remove_counter(deps.storage, counter_id, &counter);
+
+    let res = settle_fill(
+        deps,
+        env,
+        &counter.proposer,
+        counter.otc_id,
+        otc,
+        Asset { info: counter.asset, amount: counter.paid },
+        to_sell_amount,
+        None,
+        "accept_counter"
+    )?;
+
+    Ok(res.add_attribute("counter_id", counter_id.to_string()))
+}
+
+
+
+pub fn try_withdraw_counter(
+    deps: DepsMut,
+    env: Env,
+    sender: &Addr,
+    counter_id: u32,
+    ) -> Result<Response, ContractError> {
+
+    let counter = COUNTERS.load(deps.storage, counter_id)
+        .map_err(|_| ContractError::NotFound {})?;
+
+    if sender != &counter.proposer {
+        return Err(ContractError::Unauthorized {});
+    }
+
+    remove_counter(deps.storage, counter_id, &counter);
+
+    Ok(Response::new()
+        .add_message(counter.asset.transfer(&env, counter.proposer.as_str(), counter.paid)?)
+        .add_event(
+            Event::new("peerswap_counter_withdrawn")
+            .add_attributes(vec![
+                ("counter_id", counter_id.to_string()),
+                ("otc_id", counter.otc_id.to_string()),
+                ("amount", counter.paid.to_string()),
+                ("token", counter.asset.token()),
+                ("method", "withdraw_counter".to_string())
+            ])
+        )
+    )
+}
+
+
+
+pub fn query_counters(
+    deps: Deps,
+    otc_id: u32,
+    start_after: Option<u32>,
+    limit: Option<u32>,
+) -> StdResult<GetCountersResponse> {
+
+    let limit = limit.unwrap_or(DEFAULT_LIMIT).min(MAX_LIMIT) as usize;
+    let start = start_after.map(Bound::exclusive);
+
+    let counters = OTC_COUNTERS
+        .prefix(otc_id)
+        .keys(deps.storage, start, None, Order::Ascending)
+        .take(limit)
+        .map(|id| id.and_then(|id| Ok((id, COUNTERS.load(deps.storage, id)?))))
+        .collect::<StdResult<Vec<_>>>()?;
+
+    Ok(GetCountersResponse { counters })
+}
+
+
+
+pub fn query_address_counters(
+    deps: Deps,
+    proposer: Addr,
+    start_after: Option<u32>,
+    limit: Option<u32>,
+) -> StdResult<GetCountersResponse> {
+
+    let limit = limit.unwrap_or(DEFAULT_LIMIT).min(MAX_LIMIT) as usize;
+    let start = start_after.map(Bound::exclusive);
+
+    let counters = PROPOSER_COUNTERS
+        .prefix(&proposer)
+        .keys(deps.storage, start, None, Order::Ascending)
+        .take(limit)
+        .map(|id| id.and_then(|id| Ok((id, COUNTERS.load(deps.storage, id)?))))
+        .collect::<StdResult<Vec<_>>>()?;
+
+    Ok(GetCountersResponse { counters })
+}
@@ -0,0 +1,77 @@
+use cosmwasm_std::{Env, StdResult, CosmosMsg, WasmMsg, Addr, Binary, Uint128, to_binary};
+use cw20::Cw20ReceiveMsg;
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+
+// Minimal subset of the cw1155 multi-token spec. Tokens arrive with `SendFrom` carrying
+// a `ReceiveMsg` for the contract and are sent out again with `SendFrom` from the contract
+
+
+
+/// Amount of a token id of a cw1155 contract
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+pub struct Cw1155Coin {
+    pub address: String,
+    pub token_id: String,
+    pub amount: Uint128,
+}
+
+
+/// Hook called by a cw1155 contract on `SendFrom` with a message
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+pub struct Cw1155ReceiveMsg {
+    // sender of the `SendFrom`
+    pub operator: String,
+    // owner of the tokens, none if they were minted
+    pub from: Option<String>,
+    pub token_id: String,
+    pub amount: Uint128,
+    pub msg: Binary,
+}
+
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum Cw1155ExecuteMsg {
+    SendFrom {
+        from: String,
+        to: String,
+        token_id: String,
+        value: Uint128,
+        msg: Option<Binary>,
+    },
+}
+
+
+/// cw20 and cw1155 contracts both call the `receive` variant
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+#[serde(untagged)]
+pub enum Receiver {
+    Cw20(Cw20ReceiveMsg),
+    Cw1155(Cw1155ReceiveMsg),
+}
+
+
+
+/// Message sending `value` of a token id held by the contract to the recipient
+pub fn send_from(
+    env: &Env,
+    contract: &Addr,
+    token_id: &str,
+    recipient: &str,
+    value: Uint128
+) -> StdResult<CosmosMsg> {
+
+    Ok(CosmosMsg::Wasm(WasmMsg::Execute {
+        contract_addr: contract.to_string(),
+        funds: vec![],
+        msg: to_binary(&Cw1155ExecuteMsg::SendFrom {
+            from: env.contract.address.to_string(),
+            to: recipient.to_string(),
+            token_id: token_id.to_string(),
+            value,
+            msg: None,
+        })?,
+    }))
+}
+
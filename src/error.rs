@@ -33,6 +33,147 @@ pub enum ContractError {
     #[error("Cannot set approval that is already expired")]
     Expired {},
 
+    #[error("The offer hasn't expired yet")]
+    NotExpired {},
+
+    #[error("The auction must start before it ends with a price not lower than the floor")]
+    InvalidAuction {},
+
+    #[error("The bid is lower than the minimum or doesn't outbid the highest one enough")]
+    BidTooLow {},
+
+    #[error("The auction already has bids")]
+    HasBids {},
+
+    #[error("The bid has already been committed")]
+    AlreadyCommitted {},
+
+    #[error("Not allowed in the current phase of the auction")]
+    WrongPhase {},
+
+    #[error("The soft cap must be positive and the hard cap can't be lower")]
+    InvalidCaps {},
+
+    #[error("The raise hasn't reached its soft cap")]
+    SoftCapNotReached {},
+
+    #[error("The raise has reached its soft cap")]
+    SoftCapReached {},
+
+    #[error("The raise has already been finalized")]
+    Finalized {},
+
+    #[error("The cliff of the vesting must not exceed its non-zero duration")]
+    InvalidVesting {},
+
+    #[error("Nothing to claim")]
+    NothingToClaim {},
+
+    #[error("Asking for more than the offer has left")]
+    ExceedsOffer {},
+
+    #[error("Fiat escrows need an arbitrator and can't ask for tokens")]
+    InvalidEscrow {},
+
+    #[error("The payment and dispute windows of a fiat escrow can't be zero")]
+    InvalidEscrowWindow {},
+
+    #[error("Only unordered channels with the peerswap-1 version are supported")]
+    InvalidIbcChannel {},
+
+    #[error("Unsupported or malformed packet")]
+    InvalidPacket {},
+
+    #[error("The offer isn't listed on this channel")]
+    WrongChannel {},
+
+    #[error("The offer can only be filled over its IBC channel")]
+    RemoteOnly {},
+
+    #[error("Remote offers need a seller address, ask for native tokens and can't use escrows, vesting or secrets")]
+    InvalidRemoteOffer {},
+
+    #[error("The allowance or balance of the seller doesn't cover the offer")]
+    InsufficientAllowance {},
+
+    #[error("The seller revoked the allowance or moved the tokens so the offer is invalid")]
+    AllowanceRevoked {},
+
+    #[error("Allowance offers can't send tokens or hold them in escrows, vesting or remote offers")]
+    InvalidAllowanceOffer {},
+
+    #[error("The order isn't signed by the registered key of its maker or is for another contract")]
+    InvalidSignature {},
+
+    #[error("Expected a 33 or 65 bytes secp256k1 public key")]
+    InvalidOrderKey {},
+
+    #[error("The order has already been filled or cancelled")]
+    NonceUsed {},
+
+    #[error("The offer changed since the counter was proposed")]
+    StaleCounter {},
+
+    #[error("Cw1155 tokens are only supported by offers without vesting, escrows, remote fills or matching")]
+    InvalidCw1155Offer {},
+
+    #[error("{asset} isn't allowed to be traded")]
+    AssetNotAllowed { asset: String },
+
+    #[error("{address} isn't a supported cw20 token")]
+    InvalidToken { address: String },
+
+    #[error("IBC recipients need a channel, an address, a timeout and native tokens")]
+    InvalidIbcRecipient {},
+
+    #[error("Milestones must be positive and add up to the escrow which expires and can't ask for tokens")]
+    InvalidMilestones {},
+
+    #[error("Not allowed in the current state of the escrow")]
+    WrongEscrowStatus {},
+
+    #[error("Arbitrated offers need a single allowed taker or milestones and must hold the sold tokens")]
+    InvalidArbitration {},
+
+    #[error("The address isn't a registered arbitrator")]
+    NotArbitrator {},
+
+    #[error("Private offers can only be disputed once the taker filled part of them")]
+    NotFilled {},
+
+    #[error("Shares and fees can't exceed the whole amount")]
+    InvalidSplit {},
+
+    #[error("A market needs a question and at least two outcomes")]
+    InvalidMarket {},
+
+    #[error("The market has no such outcome")]
+    WrongOutcome {},
+
+    #[error("The market has already been resolved")]
+    Resolved {},
+
+    #[error("The timelock must expire")]
+    InvalidTimelock {},
+
+    #[error("The offers can't be matched against each other")]
+    NotMatching {},
+
+    #[error("The offer is private and the sender isn't allowed to take it")]
+    NotAllowedTaker {},
+
+    #[error("Expected a hex encoded 32 bytes hash")]
+    InvalidHash {},
+
+    #[error("The whitelist proof is invalid")]
+    InvalidProof {},
+
+    #[error("The swap exceeds the whitelisted allocation")]
+    AllocationExceeded {},
+
+    #[error("Wrong or missing secret")]
+    WrongSecret {},
+
     #[error("The contract has been paused")]
     Stopped {},
 
@@ -0,0 +1,124 @@
+use cosmwasm_std::{Deps, DepsMut, Env, Response, Event, Addr, Decimal, CosmosMsg, Uint128};
+use cw_utils::Expiration;
+
+use crate::contract::remove_otc;
+use crate::error::ContractError;
+use crate::state::{STATE, OTCS, OTCInfo, FiatEscrow, EscrowStatus};
+
+
+
+/// Loads an offer along with its fiat escrow
+pub fn load_escrow(
+    deps: Deps,
+    otc_id: u32,
+) -> Result<(OTCInfo, FiatEscrow), ContractError> {
+    let otc = OTCS.load(deps.storage, otc_id)
+        .map_err(|_| ContractError::NotFound {})?;
+
+    let escrow = otc.fiat_escrow.clone()
+        .ok_or(ContractError::WrongEscrowStatus {})?;
+
+    Ok((otc, escrow))
+}
+
+
+
+/// Sends the amount of the locked tokens to the buyer minus the maker fee
+pub fn pay_buyer(
+    deps: Deps,
+    env: &Env,
+    otc: &OTCInfo,
+    buyer: &Addr,
+    amount: Uint128,
+) -> Result<Vec<CosmosMsg>, ContractError> {
+    let config = STATE.load(deps.storage)?;
+    let fee = amount * Decimal::from_ratio(config.maker_fee, 10000u16);
+
+    let mut payments = vec![
+        otc.sell.transfer(env, buyer.as_str(), amount - fee)?
+    ];
+
+    if !fee.is_zero() {
+        let admin = deps.api.addr_humanize(&config.admin)?;
+        payments.push(otc.sell.transfer(env, admin.as_str(), fee)?);
+    }
+
+    Ok(payments)
+}
+
+
+
+pub fn try_payment_sent(
+    deps: DepsMut,
+    env: Env,
+    sender: &Addr,
+    otc_id: u32,
+    ) -> Result<Response, ContractError> {
+
+    let (mut otc, mut escrow) = load_escrow(deps.as_ref(), otc_id)?;
+
+    if sender != &escrow.buyer {
+        return Err(ContractError::Unauthorized {});
+    }
+
+    if escrow.status != EscrowStatus::Locked {
+        return Err(ContractError::WrongEscrowStatus {});
+    }
+
+    if otc.expires.is_expired(&env.block) {
+        return Err(ContractError::Expired {});
+    }
+
+    // the arbitrator has until the end of the dispute window to step in
+    otc.expires = Expiration::AtTime(env.block.time.plus_seconds(escrow.dispute_window));
+    escrow.status = EscrowStatus::PaymentSent;
+    otc.fiat_escrow = Some(escrow);
+
+    OTCS.save(deps.storage, otc_id, &otc)?;
+
+    Ok(Response::new()
+        .add_event(
+            Event::new("peerswap_payment_sent")
+            .add_attributes(vec![
+                ("otc_id", otc_id.to_string()),
+                ("buyer", sender.to_string()),
+                ("dispute_until", otc.expires.to_string()),
+                ("method", "payment_sent".to_string())
+            ])
+        )
+    )
+}
+
+
+
+pub fn try_release(
+    deps: DepsMut,
+    env: Env,
+    sender: &Addr,
+    otc_id: u32,
+    ) -> Result<Response, ContractError> {
+
+    let (otc, escrow) = load_escrow(deps.as_ref(), otc_id)?;
+
+    if sender != &deps.api.addr_humanize(&otc.seller)? {
+        return Err(ContractError::Unauthorized {});
+    }
+
+    let payments = pay_buyer(deps.as_ref(), &env, &otc, &escrow.buyer, otc.sell_amount)?;
+
+    remove_otc(deps.storage, otc_id)?;
+
+    Ok(Response::new()
+        .add_messages(payments)
+        .add_event(
+            Event::new("peerswap_escrow_released")
+            .add_attributes(vec![
+                ("otc_id", otc_id.to_string()),
+                ("buyer", escrow.buyer.to_string()),
+                ("amount", otc.sell_amount.to_string()),
+                ("token", otc.sell.token()),
+                ("method", "release".to_string())
+            ])
+        )
+    )
+}
@@ -0,0 +1,174 @@
+use cosmwasm_std::{
+    Deps, DepsMut, Env, Response, StdResult, Event, Addr, Decimal,
+    to_binary
+};
+use cw20::Balance;
+use cw_utils::Expiration;
+
+use crate::contract::{parse_sell_balance, next_otc_id};
+use crate::error::ContractError;
+use crate::msg::{NewHTLC, NewHTLCResponse};
+use crate::registry::ensure_allowed;
+use crate::state::{STATE, HTLCS, HTLCInfo};
+use crate::utils::{decode_hash, sha256};
+
+
+
+pub fn try_create_htlc(
+    deps: DepsMut,
+    env: Env,
+    seller: &Addr,
+    sell_balance: Balance,
+    msg: NewHTLC,
+    ) -> Result<Response, ContractError> {
+
+    let mut config = STATE.load(deps.storage)?;
+
+    if !config.active {
+        return Err(ContractError::Stopped {});
+    }
+
+    if matches!(msg.timelock, Expiration::Never {}) {
+        return Err(ContractError::InvalidTimelock {});
+    }
+
+    if msg.timelock.is_expired(&env.block) {
+        return Err(ContractError::Expired {});
+    }
+
+    decode_hash(&msg.hashlock).ok_or(ContractError::InvalidHash {})?;
+
+    let sell = parse_sell_balance(sell_balance)?;
+    ensure_allowed(deps.storage, &sell.info)?;
+
+    let htlc = HTLCInfo {
+        seller: deps.api.addr_canonicalize(seller.as_str())?,
+        taker: deps.api.addr_validate(msg.taker.as_str())?,
+        sell: sell.info,
+        sell_amount: sell.amount,
+        hashlock: msg.hashlock,
+        timelock: msg.timelock,
+        description: msg.description,
+    };
+
+    next_otc_id(deps.storage, &mut config);
+
+    HTLCS.save(deps.storage, config.index, &htlc)?;
+    STATE.save(deps.storage, &config)?;
+
+    let data = NewHTLCResponse {
+        id: config.index,
+        htlc: htlc.clone()
+    };
+
+    Ok(Response::new()
+        .set_data(to_binary(&data)?)
+        .add_event(
+            Event::new("peerswap_htlc_created")
+            .add_attributes(vec![
+                ("otc_id", config.index.to_string()),
+                ("seller", seller.to_string()),
+                ("taker", htlc.taker.to_string()),
+                ("amount", htlc.sell_amount.to_string()),
+                ("token", htlc.sell.token()),
+                ("hashlock", htlc.hashlock),
+                ("timelock", htlc.timelock.to_string()),
+                ("method", "create_htlc".to_string())
+            ])
+        )
+    )
+}
+
+
+
+pub fn try_claim_htlc(
+    deps: DepsMut,
+    env: Env,
+    otc_id: u32,
+    preimage: String,
+    ) -> Result<Response, ContractError> {
+
+    let config = STATE.load(deps.storage)?;
+
+    let htlc = HTLCS.load(deps.storage, otc_id)
+        .map_err(|_| ContractError::NotFound {})?;
+
+    if htlc.timelock.is_expired(&env.block) {
+        return Err(ContractError::Expired {});
+    }
+
+    let secret = hex::decode(&preimage).map_err(|_| ContractError::WrongSecret {})?;
+
+    if Some(sha256(&secret)) != decode_hash(&htlc.hashlock) {
+        return Err(ContractError::WrongSecret {});
+    }
+
+    let admin = deps.api.addr_humanize(&config.admin)?.to_string();
+    let fee = htlc.sell_amount * Decimal::from_ratio(config.maker_fee, 10000u16);
+
+    let payments = vec![
+        htlc.sell.transfer(&env, htlc.taker.as_str(), htlc.sell_amount - fee)?,
+        htlc.sell.transfer(&env, &admin, fee)?,
+    ];
+
+    HTLCS.remove(deps.storage, otc_id);
+
+    Ok(Response::new()
+        .add_messages(payments)
+        .add_event(
+            Event::new("peerswap_htlc_claimed")
+            .add_attributes(vec![
+                ("otc_id", otc_id.to_string()),
+                ("taker", htlc.taker.to_string()),
+                ("amount", htlc.sell_amount.to_string()),
+                ("token", htlc.sell.token()),
+                // lets the counterparty claim the other leg of the swap
+                ("preimage", preimage),
+                ("method", "claim".to_string())
+            ])
+        )
+    )
+}
+
+
+
+pub fn try_refund_htlc(
+    deps: DepsMut,
+    env: Env,
+    otc_id: u32,
+    ) -> Result<Response, ContractError> {
+
+    let htlc = HTLCS.load(deps.storage, otc_id)
+        .map_err(|_| ContractError::NotFound {})?;
+
+    if !htlc.timelock.is_expired(&env.block) {
+        return Err(ContractError::NotExpired {});
+    }
+
+    let seller = deps.api.addr_humanize(&htlc.seller)?;
+
+    HTLCS.remove(deps.storage, otc_id);
+
+    Ok(Response::new()
+        .add_message(htlc.sell.transfer(&env, seller.as_str(), htlc.sell_amount)?)
+        .add_event(
+            Event::new("peerswap_htlc_refunded")
+            .add_attributes(vec![
+                ("otc_id", otc_id.to_string()),
+                ("seller", seller.to_string()),
+                ("amount", htlc.sell_amount.to_string()),
+                ("token", htlc.sell.token()),
+                ("method", "refund".to_string())
+            ])
+        )
+    )
+}
+
+
+
+pub fn query_htlc(
+    deps: Deps,
+    otc_id: u32
+) -> StdResult<HTLCInfo> {
+    HTLCS.load(deps.storage, otc_id)
+}
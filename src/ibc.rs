@@ -0,0 +1,381 @@
+#[cfg(not(feature = "library"))]
+use cosmwasm_std::entry_point;
+use cosmwasm_std::{
+    Deps, DepsMut, Env, Storage, Response, StdResult, StdError, CosmosMsg, SubMsg, SubMsgResponse, Event, Addr, CanonicalAddr,
+    IbcOrder, IbcBasicResponse, IbcReceiveResponse, IbcChannelOpenMsg,
+    IbcChannelConnectMsg, IbcChannelCloseMsg, IbcPacketReceiveMsg, IbcPacketAckMsg, IbcPacketTimeoutMsg,
+    Binary, Coin, Uint128, from_binary
+};
+use prost::Message;
+
+use crate::asset::{AssetInfo, transfer_tokens};
+use crate::error::ContractError;
+use crate::msg::{SudoMsg, IBCLifecycleComplete};
+use crate::remote::{receive_packet, settle_remote_fill, PeerSwapPacket};
+use crate::utils::sha256;
+use crate::state::{IbcRecipient, PendingTransfer, PENDING_TRANSFERS, IBC_TRANSFERS, IBC_CHANNELS};
+
+// Offers and swaps can also arrive through the ibc-hooks middleware: an ICS-20 transfer
+// with a memo of `{"wasm": {"contract": <peerswap>, "msg": {"create": ..} or {"swap": ..}}}`
+// calls `execute` with the transferred voucher as funds and an intermediary address
+// derived from the channel and the remote sender as the sender.
+// Such senders can't use tokens on this chain so they set `ibc_recipient` to the channel and
+// their remote address, which routes their outputs back and, when it derives the sender,
+// their refunds as well
+
+pub const IBC_TRANSFER_REPLY: u64 = 1;
+
+// version of PeerSwap channels between instances on different chains
+pub const IBC_VERSION: &str = "peerswap-1";
+
+pub const MSG_TRANSFER_TYPE_URL: &str = "/ibc.applications.transfer.v1.MsgTransfer";
+
+// prefix of the hash ibc-hooks derives its intermediary senders with
+pub const HOOK_INTERMEDIARY: &str = "ibc-wasm-hook-intermediary";
+
+// Acks and timeouts of ICS-20 transfers are delivered to the transfer module, so payouts are
+// sent with a `MsgTransfer` whose memo asks ibc-hooks to call `sudo` with their outcome
+
+
+#[derive(Clone, PartialEq, prost::Message)]
+pub struct ProtoCoin {
+    #[prost(string, tag = "1")]
+    pub denom: String,
+    #[prost(string, tag = "2")]
+    pub amount: String,
+}
+
+/// `MsgTransfer` of ibc-go without a timeout height
+#[derive(Clone, PartialEq, prost::Message)]
+pub struct MsgTransfer {
+    #[prost(string, tag = "1")]
+    pub source_port: String,
+    #[prost(string, tag = "2")]
+    pub source_channel: String,
+    #[prost(message, optional, tag = "3")]
+    pub token: Option<ProtoCoin>,
+    #[prost(string, tag = "4")]
+    pub sender: String,
+    #[prost(string, tag = "5")]
+    pub receiver: String,
+    #[prost(uint64, tag = "7")]
+    pub timeout_timestamp: u64,
+    #[prost(string, tag = "8")]
+    pub memo: String,
+}
+
+
+
+/// Checks that the remote recipient is complete and only receives native tokens
+/// since cw20s can't be sent over an ICS-20 channel
+pub fn check_ibc_recipient(
+    recipient: &IbcRecipient,
+    native: bool,
+) -> Result<(), ContractError> {
+    if !native || !recipient.channel.starts_with("channel-") ||
+        recipient.address.is_empty() || recipient.timeout == 0 {
+        return Err(ContractError::InvalidIbcRecipient {});
+    }
+
+    Ok(())
+}
+
+
+
+/// Address ibc-hooks executes contracts from for a sender on the other end of the channel,
+/// `sha256(sha256("ibc-wasm-hook-intermediary") || "<channel>/<sender>")`
+pub fn hook_intermediary(channel: &str, sender: &str) -> CanonicalAddr {
+    let key = [&sha256(HOOK_INTERMEDIARY.as_bytes())[..], format!("{channel}/{sender}").as_bytes()].concat();
+    CanonicalAddr::from(sha256(&key).as_slice())
+}
+
+
+
+/// Whether the sender is the intermediary of ibc-hooks for the remote recipient, 
+/// so it can only be paid back over the channel
+pub fn is_hook_sender(deps: Deps, sender: &Addr, recipient: &IbcRecipient) -> StdResult<bool> {
+    Ok(deps.api.addr_canonicalize(sender.as_str())? == hook_intermediary(&recipient.channel, &recipient.address))
+}
+
+
+
+fn ibc_transfer(env: &Env, recipient: &IbcRecipient, coin: Coin) -> CosmosMsg {
+    let msg = MsgTransfer {
+        source_port: "transfer".to_string(),
+        source_channel: recipient.channel.clone(),
+        token: Some(ProtoCoin {
+            denom: coin.denom,
+            amount: coin.amount.to_string(),
+        }),
+        sender: env.contract.address.to_string(),
+        receiver: recipient.address.clone(),
+        timeout_timestamp: env.block.time.plus_seconds(recipient.timeout).nanos(),
+        memo: format!(r#"{{"ibc_callback":"{}"}}"#, env.contract.address),
+    };
+
+    CosmosMsg::Stargate {
+        type_url: MSG_TRANSFER_TYPE_URL.to_string(),
+        value: Binary::from(msg.encode_to_vec()),
+    }
+}
+
+
+
+/// Sends the tokens over IBC if there is a remote recipient, refundable to `recipient` until
+/// the transfer is acknowledged, or locally otherwise
+pub fn send_tokens(
+    deps: DepsMut,
+    env: &Env,
+    asset: &AssetInfo,
+    recipient: &Addr,
+    ibc_recipient: Option<&IbcRecipient>,
+    amount: Uint128
+) -> StdResult<SubMsg> {
+
+    match (asset, ibc_recipient) {
+        (AssetInfo::Native(denom), Some(remote)) => {
+            // intermediaries of ibc-hooks get their refunds over IBC
+            let refund_recipient = match is_hook_sender(deps.as_ref(), recipient, remote)? {
+                true => Some(remote),
+                false => None
+            };
+
+            send_refundable(deps.storage, env, recipient, refund_recipient, remote, denom, amount)
+        },
+        _ => Ok(SubMsg::new(asset.transfer(env, recipient.as_str(), amount)?))
+    }
+}
+
+
+
+/// Sends a payout over IBC and keeps it refundable to `refund_address`, 
+/// or to `refund_recipient` over IBC, until the transfer is acknowledged
+fn send_refundable(
+    storage: &mut dyn Storage,
+    env: &Env,
+    refund_address: &Addr,
+    refund_recipient: Option<&IbcRecipient>,
+    recipient: &IbcRecipient,
+    denom: &str,
+    amount: Uint128
+) -> StdResult<SubMsg> {
+
+    let coin = Coin {
+        denom: denom.to_string(),
+        amount,
+    };
+
+    // replies come back in the order the transfers are sent
+    let mut pending = PENDING_TRANSFERS.may_load(storage)?.unwrap_or_default();
+    pending.push(PendingTransfer {
+        refund_address: refund_address.clone(),
+        coin: coin.clone(),
+        refund_recipient: refund_recipient.cloned(),
+    });
+    PENDING_TRANSFERS.save(storage, &pending)?;
+
+    Ok(SubMsg::reply_on_success(ibc_transfer(env, recipient, coin), IBC_TRANSFER_REPLY))
+}
+
+
+
+/// Stores the pending payout under the channel and sequence of the packet sent for it
+pub fn reply_ibc_transfer(
+    deps: DepsMut,
+    response: SubMsgResponse,
+) -> Result<Response, ContractError> {
+
+    let mut pending = PENDING_TRANSFERS.load(deps.storage)?;
+    let transfer = pending.remove(0);
+
+    match pending.is_empty() {
+        true => PENDING_TRANSFERS.remove(deps.storage),
+        false => PENDING_TRANSFERS.save(deps.storage, &pending)?
+    }
+
+    let packet = response.events
+        .iter()
+        .find(|event| event.ty == "send_packet")
+        .ok_or_else(|| StdError::generic_err("missing send_packet event"))?;
+
+    let attribute = |key: &str| packet.attributes
+        .iter()
+        .find(|attr| attr.key == key)
+        .map(|attr| attr.value.clone())
+        .ok_or_else(|| StdError::generic_err(format!("missing {key} attribute")));
+
+    let channel = attribute("packet_src_channel")?;
+    let sequence = attribute("packet_sequence")?
+        .parse::<u64>()
+        .map_err(|_| StdError::generic_err("invalid packet sequence"))?;
+
+    IBC_TRANSFERS.save(deps.storage, (&channel, sequence), &transfer)?;
+
+    Ok(Response::new()
+        .add_attribute("method", "ibc_transfer")
+        .add_attribute("channel", channel)
+        .add_attribute("sequence", sequence.to_string())
+    )
+}
+
+
+
+/// Forgets an acknowledged payout and refunds it if the transfer failed
+fn settle_ibc_transfer(
+    deps: DepsMut,
+    env: &Env,
+    channel: &str,
+    sequence: u64,
+    failed: bool,
+) -> Result<Response, ContractError> {
+
+    let pending = match IBC_TRANSFERS.may_load(deps.storage, (channel, sequence))? {
+        Some(pending) => pending,
+        None => return Ok(Response::new())
+    };
+
+    IBC_TRANSFERS.remove(deps.storage, (channel, sequence));
+
+    if !failed {
+        return Ok(Response::new()
+            .add_attribute("method", "ibc_transfer_acknowledged")
+            .add_attribute("sequence", sequence.to_string())
+        );
+    }
+
+    let (refund, recipient) = match pending.refund_recipient.as_ref() {
+        Some(remote) => (ibc_transfer(env, remote, pending.coin.clone()), remote.address.clone()),
+        None => (
+            transfer_tokens(true, &pending.coin.denom, pending.refund_address.as_str(), pending.coin.amount)?, 
+            pending.refund_address.to_string()
+        )
+    };
+
+    Ok(Response::new()
+        .add_message(refund)
+        .add_event(
+            Event::new("peerswap_ibc_refund")
+            .add_attributes(vec![
+                ("channel", channel.to_string()),
+                ("sequence", sequence.to_string()),
+                ("recipient", recipient),
+                ("amount", pending.coin.amount.to_string()),
+                ("token", pending.coin.denom),
+                ("method", "ibc_refund".to_string())
+            ])
+        )
+    )
+}
+
+
+
+/// Outcome of the payouts sent over IBC
+#[cfg_attr(not(feature = "library"), entry_point)]
+pub fn sudo(
+    deps: DepsMut,
+    env: Env,
+    msg: SudoMsg,
+) -> Result<Response, ContractError> {
+    match msg {
+        SudoMsg::IBCLifecycleComplete(IBCLifecycleComplete::IBCAck { channel, sequence, success, .. }) => 
+            settle_ibc_transfer(deps, &env, &channel, sequence, !success),
+
+        SudoMsg::IBCLifecycleComplete(IBCLifecycleComplete::IBCTimeout { channel, sequence }) => 
+            settle_ibc_transfer(deps, &env, &channel, sequence, true),
+    }
+}
+
+
+
+#[cfg_attr(not(feature = "library"), entry_point)]
+pub fn ibc_channel_open(
+    _deps: DepsMut,
+    _env: Env,
+    msg: IbcChannelOpenMsg,
+) -> Result<(), ContractError> {
+    let channel = msg.channel();
+
+    if channel.order != IbcOrder::Unordered || channel.version != IBC_VERSION ||
+        msg.counterparty_version().is_some_and(|version| version != IBC_VERSION) {
+        return Err(ContractError::InvalidIbcChannel {});
+    }
+
+    Ok(())
+}
+
+
+
+#[cfg_attr(not(feature = "library"), entry_point)]
+pub fn ibc_channel_connect(
+    deps: DepsMut,
+    _env: Env,
+    msg: IbcChannelConnectMsg,
+) -> Result<IbcBasicResponse, ContractError> {
+    let channel = msg.channel();
+
+    IBC_CHANNELS.save(deps.storage, &channel.endpoint.channel_id, channel)?;
+
+    Ok(IbcBasicResponse::new()
+        .add_attribute("method", "ibc_channel_connect")
+        .add_attribute("channel", &channel.endpoint.channel_id)
+        .add_attribute("counterparty_channel", &channel.counterparty_endpoint.channel_id)
+    )
+}
+
+
+
+/// Fills in flight over a closed channel time out and get refunded
+#[cfg_attr(not(feature = "library"), entry_point)]
+pub fn ibc_channel_close(
+    deps: DepsMut,
+    _env: Env,
+    msg: IbcChannelCloseMsg,
+) -> Result<IbcBasicResponse, ContractError> {
+    let channel = msg.channel();
+
+    IBC_CHANNELS.remove(deps.storage, &channel.endpoint.channel_id);
+
+    Ok(IbcBasicResponse::new()
+        .add_attribute("method", "ibc_channel_close")
+        .add_attribute("channel", &channel.endpoint.channel_id)
+    )
+}
+
+
+
+#[cfg_attr(not(feature = "library"), entry_point)]
+pub fn ibc_packet_receive(
+    deps: DepsMut,
+    env: Env,
+    msg: IbcPacketReceiveMsg,
+) -> Result<IbcReceiveResponse, ContractError> {
+    Ok(receive_packet(deps, env, msg.packet))
+}
+
+
+
+/// Acks of PeerSwap packets settle remote fills
+#[cfg_attr(not(feature = "library"), entry_point)]
+pub fn ibc_packet_ack(
+    deps: DepsMut,
+    _env: Env,
+    msg: IbcPacketAckMsg,
+) -> Result<IbcBasicResponse, ContractError> {
+    let data: PeerSwapPacket = from_binary(&msg.original_packet.data)?;
+
+    settle_remote_fill(deps, data, Some(msg.acknowledgement.data))
+}
+
+
+
+#[cfg_attr(not(feature = "library"), entry_point)]
+pub fn ibc_packet_timeout(
+    deps: DepsMut,
+    _env: Env,
+    msg: IbcPacketTimeoutMsg,
+) -> Result<IbcBasicResponse, ContractError> {
+    // also of channels closed since the packet was sent
+    let data: PeerSwapPacket = from_binary(&msg.packet.data)?;
+
+    settle_remote_fill(deps, data, None)
+}
@@ -0,0 +1,104 @@
+use cosmwasm_std::{CanonicalAddr, Addr, Uint128, Storage, StdResult, Order};
+use cw_storage_plus::Map;
+use cw_utils::Expiration;
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+
+use crate::asset::AssetInfo;
+use crate::state::{OTCS, OTCInfo, AskFor, UserInfo};
+
+// Layout of the offers stored by 0.3.3, which encoded assets as a native flag
+// with an optional denom or address. Only read by `migrate`
+
+
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+pub struct LegacyAskFor {
+    pub initial_amount: Uint128,
+    pub amount: Uint128,
+    pub denom: Option<String>,
+    pub address: Option<Addr>,
+    pub native: bool,
+}
+
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+pub struct LegacyOTCInfo {
+    pub seller: CanonicalAddr,
+    pub sell_native: bool,
+    pub sell_amount: Uint128,
+    pub initial_sell_amount: Uint128,
+    pub sell_denom: Option<String>,
+    pub sell_address: Option<Addr>,
+    pub ask_for: Vec<LegacyAskFor>,
+    pub expires: Expiration,
+    pub user_info: Option<UserInfo>,
+    pub description: Option<String>,
+}
+
+
+pub const LEGACY_OTCS: Map<u32, LegacyOTCInfo> = Map::new("otcs");
+
+
+
+fn legacy_asset(native: bool, denom: Option<String>, address: Option<Addr>) -> AssetInfo {
+    let token = if native {
+        denom.unwrap_or_default()
+    } else {
+        address.map(String::from).unwrap_or_default()
+    };
+
+    AssetInfo::from_parts(native, token, None)
+}
+
+
+impl From<LegacyOTCInfo> for OTCInfo {
+    fn from(otc: LegacyOTCInfo) -> Self {
+        OTCInfo {
+            seller: otc.seller,
+            sell: legacy_asset(otc.sell_native, otc.sell_denom, otc.sell_address),
+            sell_amount: otc.sell_amount,
+            initial_sell_amount: otc.initial_sell_amount,
+            ask_for: otc.ask_for
+                .into_iter()
+                .map(|ask| AskFor {
+                    initial_amount: ask.initial_amount,
+                    amount: ask.amount,
+                    info: legacy_asset(ask.native, ask.denom, ask.address),
+                })
+                .collect(),
+            expires: otc.expires,
+            user_info: otc.user_info,
+            description: otc.description,
+            allowed_takers: vec![],
+            whitelist_root: None,
+            secret_hash: None,
+            dutch_auction: None,
+            vesting: None,
+            fiat_escrow: None,
+            milestones: None,
+            ibc_recipient: None,
+            remote: None,
+            allowance: false,
+            invalid: false,
+            arbitration: None,
+        }
+    }
+}
+
+
+
+/// Rewrites the stored offers with typed assets and returns how many were migrated
+pub fn migrate_assets(storage: &mut dyn Storage) -> StdResult<usize> {
+    let legacy = LEGACY_OTCS
+        .range(storage, None, None, Order::Ascending)
+        .collect::<StdResult<Vec<_>>>()?;
+
+    let count = legacy.len();
+
+    for (id, otc) in legacy {
+        OTCS.save(storage, id, &otc.into())?;
+    }
+
+    Ok(count)
+}
@@ -1,5 +1,24 @@
 pub mod error;
 pub mod contract;
+pub mod asset;
+pub mod legacy;
+pub mod allowance;
+pub mod signed_order;
+pub mod cw1155;
+pub mod htlc;
+pub mod ibc;
+pub mod remote;
+pub mod auction;
+pub mod sealed_auction;
+pub mod raise;
+pub mod vesting;
+pub mod counter;
+pub mod escrow;
+pub mod arbitration;
+pub mod registry;
+pub mod metadata;
+pub mod market;
+pub mod milestone;
 pub mod msg;
 pub mod state;
 pub mod utils;
@@ -0,0 +1,290 @@
+use cosmwasm_std::{
+    Deps, DepsMut, Env, Response, StdResult, Event, Addr, Decimal, Uint128, CosmosMsg,
+    to_binary
+};
+use cw20::Balance;
+
+use crate::asset::Asset;
+use crate::error::ContractError;
+use crate::msg::{NewMarket, NewMarketResponse, GetStakesResponse};
+use crate::registry::ensure_allowed;
+use crate::state::{STATE, MARKETS, MARKET_INDEX, STAKES, MarketInfo};
+
+// stakes of markets not resolved this long after the deadline can be claimed back
+pub const RESOLUTION_PERIOD: u64 = 30 * 24 * 60 * 60;
+
+
+
+pub fn try_create_market(
+    deps: DepsMut,
+    env: Env,
+    creator: &Addr,
+    msg: NewMarket,
+    ) -> Result<Response, ContractError> {
+
+    let config = STATE.load(deps.storage)?;
+
+    if !config.active {
+        return Err(ContractError::Stopped {});
+    }
+
+    if msg.deadline <= env.block.time.seconds() {
+        return Err(ContractError::Expired {});
+    }
+
+    if msg.question.is_empty() || msg.outcomes.len() < 2 {
+        return Err(ContractError::InvalidMarket {});
+    }
+
+    let min_stake = Asset::from_balance(msg.min_stake, None)
+        .map_err(|_| ContractError::NoAskTokens {})?;
+
+    let stake = min_stake.info.validate(deps.api)?;
+    ensure_allowed(deps.storage, &stake)?;
+
+    let market = MarketInfo {
+        creator: deps.api.addr_canonicalize(creator.as_str())?,
+        question: msg.question,
+        pools: vec![Uint128::zero(); msg.outcomes.len()],
+        outcomes: msg.outcomes,
+        resolver: deps.api.addr_validate(msg.resolver.as_str())?,
+        deadline: msg.deadline,
+        stake,
+        min_stake: min_stake.amount,
+        result: None,
+    };
+
+    let id = MARKET_INDEX.may_load(deps.storage)?.unwrap_or_default();
+
+    MARKETS.save(deps.storage, id, &market)?;
+    MARKET_INDEX.save(deps.storage, &(id + 1))?;
+
+    let data = NewMarketResponse {
+        id,
+        market: market.clone()
+    };
+
+    Ok(Response::new()
+        .set_data(to_binary(&data)?)
+        .add_event(
+            Event::new("peerswap_market_created")
+            .add_attributes(vec![
+                ("market_id", id.to_string()),
+                ("creator", creator.to_string()),
+                ("question", market.question.clone()),
+                ("outcomes", market.outcomes.join(",")),
+                ("resolver", market.resolver.to_string()),
+                ("stake_token", market.stake.token()),
+                ("deadline", market.deadline.to_string()),
+                ("method", "create_market".to_string())
+            ])
+        )
+    )
+}
+
+
+
+pub fn try_stake(
+    deps: DepsMut,
+    env: Env,
+    staker: &Addr,
+    market_id: u32,
+    outcome: u32,
+    balance: Balance,
+    ) -> Result<Response, ContractError> {
+
+    let mut market = MARKETS.load(deps.storage, market_id)
+        .map_err(|_| ContractError::NotFound {})?;
+
+    if env.block.time.seconds() >= market.deadline {
+        return Err(ContractError::Expired {});
+    }
+
+    let index = outcome as usize;
+    if index >= market.outcomes.len() {
+        return Err(ContractError::WrongOutcome {});
+    }
+
+    let amount = Asset::from_balance(balance, None)
+        .ok()
+        .filter(|stake| stake.info == market.stake)
+        .ok_or(ContractError::WrongDenom {})?
+        .amount;
+
+    if amount < market.min_stake {
+        return Err(ContractError::TooSmall {});
+    }
+
+    market.pools[index] += amount;
+    MARKETS.save(deps.storage, market_id, &market)?;
+
+    let mut stakes = STAKES.may_load(deps.storage, (market_id, staker))?
+        .unwrap_or_else(|| vec![Uint128::zero(); market.outcomes.len()]);
+    stakes[index] += amount;
+    STAKES.save(deps.storage, (market_id, staker), &stakes)?;
+
+    Ok(Response::new()
+        .add_event(
+            Event::new("peerswap_stake")
+            .add_attributes(vec![
+                ("market_id", market_id.to_string()),
+                ("staker", staker.to_string()),
+                ("outcome", market.outcomes[index].clone()),
+                ("amount", amount.to_string()),
+                ("token", market.stake.token()),
+                ("pool", market.pools[index].to_string()),
+                ("method", "stake".to_string())
+            ])
+        )
+    )
+}
+
+
+
+pub fn try_resolve_market(
+    deps: DepsMut,
+    env: Env,
+    sender: &Addr,
+    market_id: u32,
+    outcome: u32,
+    ) -> Result<Response, ContractError> {
+
+    let mut market = MARKETS.load(deps.storage, market_id)
+        .map_err(|_| ContractError::NotFound {})?;
+
+    if sender != &market.resolver {
+        return Err(ContractError::Unauthorized {});
+    }
+
+    if market.result.is_some() {
+        return Err(ContractError::Resolved {});
+    }
+
+    let now = env.block.time.seconds();
+
+    if now < market.deadline {
+        return Err(ContractError::NotExpired {});
+    }
+
+    if now >= market.deadline + RESOLUTION_PERIOD {
+        return Err(ContractError::Expired {});
+    }
+
+    if outcome as usize >= market.outcomes.len() {
+        return Err(ContractError::WrongOutcome {});
+    }
+
+    market.result = Some(outcome);
+    MARKETS.save(deps.storage, market_id, &market)?;
+
+    Ok(Response::new()
+        .add_event(
+            Event::new("peerswap_market_resolved")
+            .add_attributes(vec![
+                ("market_id", market_id.to_string()),
+                ("outcome", market.outcomes[outcome as usize].clone()),
+                ("winning_pool", market.pools[outcome as usize].to_string()),
+                ("total_staked", market.total_staked().to_string()),
+                ("method", "resolve_market".to_string())
+            ])
+        )
+    )
+}
+
+
+
+pub fn try_claim_winnings(
+    deps: DepsMut,
+    env: Env,
+    staker: &Addr,
+    market_id: u32,
+    ) -> Result<Response, ContractError> {
+
+    let config = STATE.load(deps.storage)?;
+
+    let market = MARKETS.load(deps.storage, market_id)
+        .map_err(|_| ContractError::NotFound {})?;
+
+    let stakes = STAKES.load(deps.storage, (market_id, staker))
+        .map_err(|_| ContractError::NotFound {})?;
+
+    let staked : Uint128 = stakes.iter().sum();
+
+    // (payout, fee)
+    let (payout, fee) = match market.result {
+        Some(outcome) => {
+            let winning_pool = market.pools[outcome as usize];
+
+            if winning_pool.is_zero() {
+                // nobody to pay out so everyone gets their stakes back
+                (staked, Uint128::zero())
+            } else {
+                let stake = stakes[outcome as usize];
+
+                if stake.is_zero() {
+                    return Err(ContractError::NothingToClaim {});
+                }
+
+                let winnings = (market.total_staked() - winning_pool).multiply_ratio(stake, winning_pool);
+                let fee = winnings * Decimal::from_ratio(config.taker_fee, 10000u16);
+
+                (stake + winnings - fee, fee)
+            }
+        },
+        None => {
+            if env.block.time.seconds() < market.deadline + RESOLUTION_PERIOD {
+                return Err(ContractError::NotExpired {});
+            }
+            (staked, Uint128::zero())
+        }
+    };
+
+    STAKES.remove(deps.storage, (market_id, staker));
+
+    let mut payments : Vec<CosmosMsg> = vec![
+        market.stake.transfer(&env, staker.as_str(), payout)?
+    ];
+
+    if !fee.is_zero() {
+        let admin = deps.api.addr_humanize(&config.admin)?;
+        payments.push(market.stake.transfer(&env, admin.as_str(), fee)?);
+    }
+
+    Ok(Response::new()
+        .add_messages(payments)
+        .add_event(
+            Event::new("peerswap_winnings_claimed")
+            .add_attributes(vec![
+                ("market_id", market_id.to_string()),
+                ("staker", staker.to_string()),
+                ("amount", payout.to_string()),
+                ("token", market.stake.token()),
+                ("method", "claim_winnings".to_string())
+            ])
+        )
+    )
+}
+
+
+
+pub fn query_market(
+    deps: Deps,
+    market_id: u32
+) -> StdResult<MarketInfo> {
+    MARKETS.load(deps.storage, market_id)
+}
+
+
+
+pub fn query_stakes(
+    deps: Deps,
+    market_id: u32,
+    address: Addr
+) -> StdResult<GetStakesResponse> {
+    let market = MARKETS.load(deps.storage, market_id)?;
+
+    let stakes = STAKES.may_load(deps.storage, (market_id, &address))?
+        .unwrap_or_else(|| vec![Uint128::zero(); market.outcomes.len()]);
+
+    Ok(GetStakesResponse { stakes })
+}
@@ -0,0 +1,158 @@
+use cosmwasm_std::{Deps, DepsMut, Response, StdResult, Event, Addr, QuerierWrapper};
+use cw20::{Cw20QueryMsg, TokenInfoResponse};
+
+use crate::arbitration::ensure_admin;
+use crate::asset::AssetInfo;
+use crate::error::ContractError;
+use crate::state::{State, TOKEN_METADATA, TokenMetadata};
+
+// Metadata of cw20s is fetched from the token contract the first time an offer uses them,
+// the admin sets it for native and IBC denoms and can override it for any asset
+
+
+
+/// Unverified metadata of a cw20 from its `TokenInfo`
+pub fn fetch_cw20_metadata(querier: &QuerierWrapper, address: &Addr) -> StdResult<TokenMetadata> {
+    let info : TokenInfoResponse = querier.query_wasm_smart(address, &Cw20QueryMsg::TokenInfo {})?;
+
+    Ok(TokenMetadata {
+        name: info.name,
+        symbol: info.symbol,
+        decimals: info.decimals,
+        logo: None,
+        verified: false,
+    })
+}
+
+
+
+/// Stores the metadata of a cw20 used for the first time,
+/// contracts that don't answer `TokenInfo` are left without any
+pub fn cache_metadata(deps: DepsMut, asset: &AssetInfo) -> StdResult<()> {
+    if let AssetInfo::Cw20(address) = asset {
+        if !TOKEN_METADATA.has(deps.storage, asset.to_string()) {
+            if let Ok(metadata) = fetch_cw20_metadata(&deps.querier, address) {
+                TOKEN_METADATA.save(deps.storage, asset.to_string(), &metadata)?;
+            }
+        }
+    }
+
+    Ok(())
+}
+
+
+
+/// Fails unless the address is a cw20 answering `TokenInfo`, and an instance
+/// of the allowed code ids if the config has any. Caches the metadata of new cw20s
+pub fn validate_cw20(deps: DepsMut, config: &State, address: &Addr) -> Result<(), ContractError> {
+    let invalid = || ContractError::InvalidToken { address: address.to_string() };
+
+    if !config.cw20_code_ids.is_empty() {
+        let info = deps.querier.query_wasm_contract_info(address).map_err(|_| invalid())?;
+
+        if !config.cw20_code_ids.contains(&info.code_id) {
+            return Err(invalid());
+        }
+    }
+
+    let metadata = fetch_cw20_metadata(&deps.querier, address).map_err(|_| invalid())?;
+
+    let key = AssetInfo::Cw20(address.clone()).to_string();
+    if !TOKEN_METADATA.has(deps.storage, key.clone()) {
+        TOKEN_METADATA.save(deps.storage, key, &metadata)?;
+    }
+
+    Ok(())
+}
+
+
+
+/// Metadata of the assets that have any, cw20s used before the registry existed are fetched
+pub fn resolve_metadata<'a>(
+    deps: Deps,
+    assets: impl IntoIterator<Item = &'a AssetInfo>,
+) -> StdResult<Vec<(AssetInfo, TokenMetadata)>> {
+
+    let mut resolved : Vec<(AssetInfo, TokenMetadata)> = vec![];
+
+    for asset in assets {
+        if resolved.iter().any(|(known, _)| known == asset) {
+            continue;
+        }
+
+        let metadata = match TOKEN_METADATA.may_load(deps.storage, asset.to_string())? {
+            Some(metadata) => Some(metadata),
+            None => match asset {
+                AssetInfo::Cw20(address) => fetch_cw20_metadata(&deps.querier, address).ok(),
+                _ => None
+            }
+        };
+
+        if let Some(metadata) = metadata {
+            resolved.push((asset.clone(), metadata));
+        }
+    }
+
+    Ok(resolved)
+}
+
+
+
+pub fn try_set_token_metadata(
+    deps: DepsMut,
+    sender: &Addr,
+    asset: AssetInfo,
+    name: String,
+    symbol: String,
+    decimals: u8,
+    logo: Option<String>,
+) -> Result<Response, ContractError> {
+
+    ensure_admin(deps.as_ref(), sender)?;
+
+    let asset = asset.validate(deps.api)?;
+
+    TOKEN_METADATA.save(deps.storage, asset.to_string(), &TokenMetadata {
+        name,
+        symbol: symbol.clone(),
+        decimals,
+        logo,
+        verified: true,
+    })?;
+
+    Ok(Response::new()
+        .add_event(
+            Event::new("peerswap_token_metadata_set")
+            .add_attributes(vec![
+                ("asset", asset.to_string()),
+                ("symbol", symbol),
+                ("decimals", decimals.to_string()),
+                ("method", "set_token_metadata".to_string())
+            ])
+        )
+    )
+}
+
+
+
+pub fn try_remove_token_metadata(
+    deps: DepsMut,
+    sender: &Addr,
+    asset: AssetInfo,
+) -> Result<Response, ContractError> {
+
+    ensure_admin(deps.as_ref(), sender)?;
+
+    // cw20s get fetched again on their next use
+    TOKEN_METADATA.remove(deps.storage, asset.to_string());
+
+    Ok(Response::new()
+        .add_event(
+            Event::new("peerswap_token_metadata_removed")
+            .add_attributes(vec![
+                ("asset", asset.to_string()),
+                ("method", "remove_token_metadata".to_string())
+            ])
+        )
+    )
+}
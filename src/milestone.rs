@@ -0,0 +1,111 @@
+use cosmwasm_std::{Deps, DepsMut, Env, Response, StdResult, StdError, Event, Addr, Decimal, Uint128};
+
+use crate::contract::remove_otc;
+use crate::error::ContractError;
+use crate::msg::GetMilestonesResponse;
+use crate::state::{STATE, OTCS};
+
+
+
+pub fn try_approve_milestone(
+    deps: DepsMut,
+    env: Env,
+    sender: &Addr,
+    otc_id: u32,
+    milestone: u32,
+    ) -> Result<Response, ContractError> {
+
+    let config = STATE.load(deps.storage)?;
+
+    let mut otc = OTCS.load(deps.storage, otc_id)
+        .map_err(|_| ContractError::NotFound {})?;
+
+    let mut escrow = otc.milestones.clone()
+        .ok_or(ContractError::WrongEscrowStatus {})?;
+
+    // only an arbitrator chosen for the escrow can approve besides the seller
+    if sender != &deps.api.addr_humanize(&otc.seller)? && Some(sender) != escrow.arbitrator.as_ref() {
+        return Err(ContractError::Unauthorized {});
+    }
+
+    // the remainder goes back to the seller once expired
+    if otc.expires.is_expired(&env.block) {
+        return Err(ContractError::Expired {});
+    }
+
+    if otc.is_disputed() {
+        return Err(ContractError::WrongEscrowStatus {});
+    }
+
+    let index = milestone as usize;
+
+    let tranche = escrow.milestones.get_mut(index)
+        .ok_or(ContractError::NotFound {})?;
+
+    if tranche.released {
+        return Err(ContractError::WrongEscrowStatus {});
+    }
+
+    tranche.released = true;
+    let amount = tranche.amount;
+
+    let fee = amount * Decimal::from_ratio(config.maker_fee, 10000u16);
+
+    let mut res = Response::new()
+        .add_message(otc.sell.transfer(&env, escrow.payee.as_str(), amount - fee)?);
+
+    if !fee.is_zero() {
+        let admin = deps.api.addr_humanize(&config.admin)?;
+        res = res.add_message(otc.sell.transfer(&env, admin.as_str(), fee)?);
+    }
+
+    otc.sell_amount -= amount;
+
+    if escrow.milestones.iter().all(|milestone| milestone.released) {
+        remove_otc(deps.storage, otc_id)?;
+    } else {
+        otc.milestones = Some(escrow.clone());
+        OTCS.save(deps.storage, otc_id, &otc)?;
+    }
+
+    Ok(res
+        .add_event(
+            Event::new("peerswap_milestone_approved")
+            .add_attributes(vec![
+                ("otc_id", otc_id.to_string()),
+                ("milestone", milestone.to_string()),
+                ("approver", sender.to_string()),
+                ("payee", escrow.payee.to_string()),
+                ("amount", amount.to_string()),
+                ("token", otc.sell.token()),
+                ("remaining", otc.sell_amount.to_string()),
+                ("method", "approve_milestone".to_string())
+            ])
+        )
+    )
+}
+
+
+
+pub fn query_milestones(
+    deps: Deps,
+    otc_id: u32
+) -> StdResult<GetMilestonesResponse> {
+    let otc = OTCS.load(deps.storage, otc_id)?;
+
+    let escrow = otc.milestones
+        .ok_or_else(|| StdError::not_found("milestones"))?;
+
+    let released = escrow.milestones
+        .iter()
+        .filter(|milestone| milestone.released)
+        .map(|milestone| milestone.amount)
+        .sum::<Uint128>();
+
+    Ok(GetMilestonesResponse {
+        payee: escrow.payee,
+        milestones: escrow.milestones,
+        released,
+        remaining: otc.sell_amount,
+    })
+}
@@ -1,9 +1,11 @@
 use cw_utils::Expiration;
 use schemars::JsonSchema;
 use serde::{Deserialize, Serialize};
-use cw20::{Balance, Cw20ReceiveMsg};
-use cosmwasm_std::{Addr};
-use crate::state::{UserInfo, OTCInfo};
+use cw20::{Balance, Cw20Coin};
+use cosmwasm_std::{Addr, Binary, Decimal, Uint128};
+use crate::cw1155::{Cw1155Coin, Receiver};
+use crate::state::{UserInfo, OTCInfo, HTLCInfo, AuctionInfo, SealedAuctionInfo, RaiseInfo, AskFor, DutchAuction, Vesting, VestingPosition, Counter, ArbitratorInfo, MarketInfo, Milestone, IbcRecipient, RemoteOffer, OrderStatus, AssetListMode, TokenMetadata};
+use crate::asset::AssetInfo;
 
 
 
@@ -11,10 +13,12 @@ use crate::state::{UserInfo, OTCInfo};
 pub struct InstantiateMsg {
     pub taker_fee: Option<u16>, // basis points, e.g. 5 = 0.0005%
     pub maker_fee: Option<u16>,
+    pub keeper_reward: Option<u16>, // share of the fees paid to whoever matches offers
+    pub arbitrator: Option<String>, // resolves disputed fiat escrows
 }
 
 
-#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema, Default)]
 pub struct NewOTC {
     pub ask_balances: Vec<Balance>, 
 
@@ -27,6 +31,237 @@ pub struct NewOTC {
     // optional description
     pub description: Option<String>,
 
+    // makes the offer private to the listed takers
+    pub allowed_takers: Option<Vec<Addr>>,
+
+    // hex encoded merkle root of leaves hashing the JSON array [address, allocation]
+    pub whitelist_root: Option<String>,
+
+    // hex encoded sha256 of a passcode shared with takers off-chain
+    pub secret_hash: Option<String>,
+
+    // sell with a price decaying down to the ask balances
+    pub dutch_auction: Option<DutchAuction>,
+
+    // deliver the sold tokens to takers over a schedule
+    pub vesting: Option<Vesting>,
+
+    // lock the tokens for a buyer paying fiat, the asks must be empty
+    pub fiat_escrow: Option<NewFiatEscrow>,
+
+    // pay the tokens out in tranches, the asks must be empty
+    pub milestones: Option<NewMilestones>,
+
+    // remote address receiving the paid tokens and refunds, only for native tokens
+    pub ibc_recipient: Option<IbcRecipient>,
+
+    // list the offer for takers on the other end of a PeerSwap channel
+    pub remote: Option<RemoteOffer>,
+
+    // sell cw20s the contract is allowed to spend instead of sending them
+    pub allowance: Option<Cw20Coin>,
+
+    // token ids of cw1155 contracts asked for along with `ask_balances`
+    pub ask_cw1155: Option<Vec<Cw1155Coin>>,
+
+    // registered arbitrator of disputes with the only allowed taker,
+    // milestone escrows fall back to their approving arbitrator
+    pub arbitrator: Option<Addr>,
+
+}
+
+
+
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+pub struct NewFiatEscrow {
+    // the only address that can receive the tokens
+    pub buyer: Addr,
+
+    // seconds the buyer has to pay before the seller gets refunded
+    pub payment_window: u64,
+
+    // seconds the arbitrator has to resolve after the payment is marked as sent
+    pub dispute_window: u64,
+
+    // one of the registered arbitrators instead of the default one
+    pub arbitrator: Option<Addr>,
+}
+
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+pub struct NewMilestone {
+    pub amount: Uint128,
+    pub description: Option<String>,
+}
+
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+pub struct NewMilestones {
+    // receives the tranches
+    pub payee: Addr,
+
+    // the amounts must add up to the sent tokens
+    pub milestones: Vec<NewMilestone>,
+
+    // one of the registered arbitrators who can also approve milestones,
+    // only the seller approves them without one
+    pub arbitrator: Option<Addr>,
+}
+
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+pub struct NewHTLC {
+    // the only address that receives the tokens
+    pub taker: Addr,
+
+    // hex encoded sha256 of the preimage
+    pub hashlock: String,
+
+    // the tokens can be claimed before and refunded after
+    pub timelock: Expiration,
+
+    // optional description
+    pub description: Option<String>,
+}
+
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+pub struct NewAuction {
+    // token of the bids and the lowest amount accepted
+    pub min_bid: Balance,
+
+    // each bid must outbid the previous one at least by this amount
+    pub min_increment: Option<Uint128>,
+
+    // seconds since epoch
+    pub end_time: u64,
+
+    // anti-sniping: bids in the last seconds extend the auction
+    pub extension: Option<u64>,
+
+    // optional description
+    pub description: Option<String>,
+}
+
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+pub struct NewSealedAuction {
+    // token of the bids and the reserve price
+    pub min_bid: Balance,
+
+    // seconds since epoch when the bidding and then the revealing ends
+    pub commit_end: u64,
+    pub reveal_end: u64,
+
+    // the winner pays the second highest bid
+    pub second_price: bool,
+
+    // deposits of bids that weren't revealed go back to bidders instead of the seller
+    pub refund_unrevealed: bool,
+
+    // optional description
+    pub description: Option<String>,
+}
+
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+pub struct NewRaise {
+    // token of the contributions and the minimum to raise
+    pub soft_cap: Balance,
+
+    // the most the creator receives, the rest is refunded pro rata
+    pub hard_cap: Uint128,
+
+    // seconds since epoch
+    pub deadline: u64,
+
+    // optional description
+    pub description: Option<String>,
+}
+
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+pub struct NewMarket {
+    pub question: String,
+
+    // e.g. ["yes", "no"]
+    pub outcomes: Vec<String>,
+
+    // seconds since epoch when staking closes
+    pub deadline: u64,
+
+    // posts the result after the deadline
+    pub resolver: Addr,
+
+    // token of the stakes and the smallest stake accepted
+    pub min_stake: Balance,
+}
+
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+pub struct WhitelistProof {
+    // amount of the sold token the taker is allowed to receive
+    pub allocation: Uint128,
+
+    // hex encoded sibling hashes from the leaf to the root
+    pub proof: Vec<String>,
+}
+
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema, Default)]
+pub struct SwapMsg {
+    pub otc_id: u32,
+
+    // required for offers with a whitelist root
+    pub whitelist: Option<WhitelistProof>,
+
+    // passcode of offers with a secret hash
+    pub secret: Option<String>,
+
+    // remote address receiving the bought tokens, only for native tokens
+    pub ibc_recipient: Option<IbcRecipient>,
+}
+
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+pub struct RemoteSwapMsg {
+    // PeerSwap channel to the chain of the offer
+    pub channel: String,
+
+    pub otc_id: u32,
+
+    // address on the chain of the offer receiving the bought tokens
+    pub recipient: String,
+
+    // seconds until the fill times out and gets refunded
+    pub timeout: u64,
+}
+
+
+/// Maker order signed off-chain over the sha256 of its JSON encoding
+/// with the key registered by `SetOrderKey`
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+pub struct SignedOrder {
+    pub maker: String,
+
+    // pulled from the maker with the allowance given to the contract
+    pub sell: Cw20Coin,
+
+    // paid in full by the taker
+    pub ask: Balance,
+
+    // each nonce of a maker is filled or cancelled at most once
+    pub nonce: u64,
+
+    pub expires: Expiration,
+
+    // makes the order private to a single taker
+    pub taker: Option<String>,
+
+    // binds the signature to a single deployment
+    pub chain_id: String,
+    pub contract: String,
 }
 
 
@@ -34,35 +269,284 @@ pub struct NewOTC {
 
 #[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
 #[serde(rename_all = "snake_case")]
+#[allow(clippy::large_enum_variant)]
 pub enum ExecuteMsg {
     Create(NewOTC),
 
-    Swap {
-        otc_id: u32
+    Swap(SwapMsg),
+
+    /// Fill an offer listed on the other end of a PeerSwap channel
+    RemoteSwap(RemoteSwapMsg),
+
+    /// Settle two crossing offers against each other at the maker price
+    Match {
+        maker_otc: u32,
+        taker_otc: u32
     },
 
     Cancel {
         otc_id: u32
     },
 
+    /// Lock the sent tokens for a taker until the preimage of the hashlock is revealed
+    CreateHtlc(NewHTLC),
+
+    /// Send the locked tokens to the taker by revealing the hex encoded preimage
+    Claim {
+        otc_id: u32,
+        preimage: String
+    },
+
+    /// Return the locked tokens to the seller after the timelock
+    Refund {
+        otc_id: u32
+    },
+
+    /// Auction the sent tokens to the highest bidder
+    CreateAuction(NewAuction),
+
+    /// Escrow a bid, refunding the previous highest bidder
+    Bid {
+        auction_id: u32
+    },
+
+    /// Pay the seller and deliver the lot after the auction has ended
+    Settle {
+        auction_id: u32
+    },
+
+    /// Return the lot to the seller of an auction without bids
+    CancelAuction {
+        auction_id: u32
+    },
+
+    /// Auction the sent tokens with bids sealed until the reveal phase
+    CreateSealedAuction(NewSealedAuction),
+
+    /// Escrow a deposit covering a bid hidden behind the hex encoded
+    /// sha256 of the JSON array [bidder, amount, salt]
+    CommitBid {
+        auction_id: u32,
+        commitment: String
+    },
+
+    RevealBid {
+        auction_id: u32,
+        amount: Uint128,
+        salt: String
+    },
+
+    /// Return the deposit of a losing sealed bid after the reveal phase,
+    /// or pay it to the seller if the bid wasn't revealed
+    WithdrawSealedBid {
+        auction_id: u32,
+        bidder: String
+    },
+
+    /// Raise funds by distributing the sent tokens to the contributors
+    CreateRaise(NewRaise),
+
+    /// Escrow a contribution until the deadline of the raise
+    Contribute {
+        raise_id: u32
+    },
+
+    /// Pay out a successful raise to the creator or return the tokens of a failed one
+    Finalize {
+        raise_id: u32
+    },
+
+    /// Receive the share of tokens of a successful raise and the oversubscribed part of the contribution
+    ClaimTokens {
+        raise_id: u32
+    },
+
+    /// Get back the contribution to a raise that missed its soft cap
+    ClaimRefund {
+        raise_id: u32
+    },
+
+    /// Receive the vested tokens of all the positions of the sender
+    ClaimVested {},
+
+    /// Escrow the sent ask tokens as an offer for `amount` of the sell tokens
+    ProposeCounter {
+        otc_id: u32,
+        amount: Uint128
+    },
+
+    /// Settle a counter to an own offer
+    AcceptCounter {
+        counter_id: u32
+    },
+
+    /// Take back the tokens of an own counter
+    WithdrawCounter {
+        counter_id: u32
+    },
+
+    /// Buyer of a fiat escrow confirms paying off-chain
+    PaymentSent {
+        otc_id: u32
+    },
+
+    /// Seller of a fiat escrow sends the tokens to the buyer
+    Release {
+        otc_id: u32
+    },
+
+    /// Release the tranche of a milestone to the payee
+    ApproveMilestone {
+        otc_id: u32,
+        milestone: u32
+    },
+
+    /// Freeze an escrow or arbitrated offer until the arbitrator resolves it
+    /// or the dispute times out and the seller gets refunded. Private offers
+    /// can only be disputed once the taker filled part of them
+    Dispute {
+        otc_id: u32
+    },
+
+    /// Arbitrator gives `split` of a disputed offer or paid fiat escrow to the buyer,
+    /// taker or payee and the rest back to the seller after taking the arbitrator fee
+    Resolve {
+        otc_id: u32,
+        split: Decimal
+    },
+
+    /// Admin adds or updates an arbitrator sellers can choose
+    RegisterArbitrator {
+        address: Addr,
+        fee: u16,
+        description: Option<String>
+    },
+
+    RemoveArbitrator {
+        address: Addr
+    },
+
+    /// Admin chooses whether the registered assets are the only ones or the ones that can't be traded
+    SetAssetListMode {
+        mode: AssetListMode
+    },
+
+    RegisterAssets {
+        assets: Vec<AssetInfo>
+    },
+
+    RemoveAssets {
+        assets: Vec<AssetInfo>
+    },
+
+    /// Admin restricts asked cw20s to instances of the code ids, an empty list allows any
+    SetCw20CodeIds {
+        code_ids: Vec<u64>
+    },
+
+    /// Admin sets the verified metadata of an asset shown with the offers
+    SetTokenMetadata {
+        asset: AssetInfo,
+        name: String,
+        symbol: String,
+        decimals: u8,
+        logo: Option<String>
+    },
+
+    RemoveTokenMetadata {
+        asset: AssetInfo
+    },
+
+    /// Open a prediction market on a question
+    CreateMarket(NewMarket),
+
+    /// Stake the sent tokens on an outcome of a market
+    Stake {
+        market_id: u32,
+        outcome: u32
+    },
+
+    /// Resolver posts the winning outcome after the deadline
+    ResolveMarket {
+        market_id: u32,
+        outcome: u32
+    },
+
+    /// Receive the share of the losing pools of a resolved market,
+    /// or the stakes back if the market was never resolved
+    ClaimWinnings {
+        market_id: u32
+    },
+
+    /// Register the secp256k1 public key the sender signs off-chain orders with
+    SetOrderKey {
+        pubkey: Binary
+    },
+
+    /// Fill a signed maker order by paying its ask with the sent tokens
+    FillSignedOrder {
+        order: SignedOrder,
+        signature: Binary
+    },
+
+    /// Invalidate an own signed order before it gets filled
+    CancelSignedOrder {
+        nonce: u64
+    },
+
     SetActive {
         active: bool
     },
 
     RemoveExpired {},
 
-    /// This accepts a properly-encoded ReceiveMsg from a cw20 contract
-    Receive(Cw20ReceiveMsg),
+    /// This accepts a properly-encoded ReceiveMsg from a cw20 or a cw1155 contract
+    Receive(Receiver),
 }
 
 #[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
 #[serde(rename_all = "snake_case")]
+#[allow(clippy::large_enum_variant)]
 pub enum ReceiveMsg {
     
     Create(NewOTC),
 
-    Swap {
-        otc_id: u32
+    Swap(SwapMsg),
+
+    CreateHtlc(NewHTLC),
+
+    CreateAuction(NewAuction),
+
+    Bid {
+        auction_id: u32
+    },
+
+    CreateSealedAuction(NewSealedAuction),
+
+    CommitBid {
+        auction_id: u32,
+        commitment: String
+    },
+
+    CreateRaise(NewRaise),
+
+    Contribute {
+        raise_id: u32
+    },
+
+    ProposeCounter {
+        otc_id: u32,
+        amount: Uint128
+    },
+
+    Stake {
+        market_id: u32,
+        outcome: u32
+    },
+
+    FillSignedOrder {
+        order: SignedOrder,
+        signature: Binary
     }
 }
 
@@ -74,7 +558,9 @@ pub enum QueryMsg {
     GetOtcs { 
         include_expired: Option<bool>,
         start_after: Option<u32>,
-        limit: Option<u32>
+        limit: Option<u32>,
+        // private offers are only listed to their allowed takers
+        querier: Option<Addr>
     },
 
     GetAddressOtcs {
@@ -88,14 +574,145 @@ pub enum QueryMsg {
         otc_id: u32
     },
 
+    GetHtlc {
+        otc_id: u32
+    },
+
+    GetAuction {
+        auction_id: u32
+    },
+
+    GetAuctions {
+        start_after: Option<u32>,
+        limit: Option<u32>
+    },
+
+    GetSealedAuction {
+        auction_id: u32
+    },
+
+    GetSealedBid {
+        auction_id: u32,
+        bidder: Addr
+    },
+
+    GetRaise {
+        raise_id: u32
+    },
+
+    GetContribution {
+        raise_id: u32,
+        contributor: Addr
+    },
+
+    GetVestingPositions {
+        owner: Addr,
+        start_after: Option<u32>,
+        limit: Option<u32>
+    },
+
+    GetArbitrators {
+        start_after: Option<Addr>,
+        limit: Option<u32>
+    },
+
+    GetAssetList {
+        start_after: Option<String>,
+        limit: Option<u32>
+    },
+
+    GetMarket {
+        market_id: u32
+    },
+
+    GetMilestones {
+        otc_id: u32
+    },
+
+    GetRemoteFill {
+        fill_id: u32
+    },
+
+    GetOrderStatus {
+        maker: String,
+        nonce: u64
+    },
+
+    GetStakes {
+        market_id: u32,
+        address: Addr
+    },
+
+    GetCounters {
+        otc_id: u32,
+        start_after: Option<u32>,
+        limit: Option<u32>
+    },
+
+    GetAddressCounters {
+        proposer: Addr,
+        start_after: Option<u32>,
+        limit: Option<u32>
+    },
+
+    /// Quote a swap at the current price of the offer
+    SimulateSwap {
+        otc_id: u32,
+        // denom of a native token or address of a cw20 contract
+        token: String,
+        amount: Uint128
+    },
+
     Config {},
 }
 
 
+
+/// Called by the ibc-hooks middleware with the outcome of the ICS-20 transfers
+/// sent with an `ibc_callback` memo
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum SudoMsg {
+    #[serde(rename = "ibc_lifecycle_complete")]
+    IBCLifecycleComplete(IBCLifecycleComplete),
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum IBCLifecycleComplete {
+    #[serde(rename = "ibc_ack")]
+    IBCAck {
+        // source channel and sequence of the packet
+        channel: String,
+        sequence: u64,
+        ack: String,
+        success: bool,
+    },
+
+    #[serde(rename = "ibc_timeout")]
+    IBCTimeout {
+        channel: String,
+        sequence: u64,
+    },
+}
+
+
 // We define a custom struct for each query response
 #[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
 pub struct GetOTCsResponse {
-    pub otcs: Vec<(u32, OTCInfo)>
+    pub otcs: Vec<(u32, OTCInfo)>,
+    // of the assets of the listed offers
+    #[serde(default)]
+    pub metadata: Vec<(AssetInfo, TokenMetadata)>,
+}
+
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+pub struct GetOTCResponse {
+    #[serde(flatten)]
+    pub otc: OTCInfo,
+    #[serde(default)]
+    pub metadata: Vec<(AssetInfo, TokenMetadata)>,
 }
 
 
@@ -104,8 +721,11 @@ pub struct GetOTCsResponse {
 pub struct GetConfigResponse {
     pub taker_fee: u16,
     pub maker_fee: u16,
+    pub keeper_reward: u16,
     pub active: bool,
     pub admin: String,
+    pub arbitrator: Option<String>,
+    pub cw20_code_ids: Vec<u64>,
 }
 
 
@@ -114,4 +734,127 @@ pub struct GetConfigResponse {
 pub struct NewOTCResponse {
     pub id: u32,
     pub otc: OTCInfo,
+}
+
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+pub struct SimulateSwapResponse {
+    // released by the offer before the maker fee
+    pub given_amount: Uint128,
+    pub maker_fee: Uint128,
+    // asks at the current price
+    pub ask_for: Vec<AskFor>,
+}
+
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+pub struct GetAuctionsResponse {
+    pub auctions: Vec<(u32, AuctionInfo)>
+}
+
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+pub struct NewAuctionResponse {
+    pub id: u32,
+    pub auction: AuctionInfo,
+}
+
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+pub struct NewSealedAuctionResponse {
+    pub id: u32,
+    pub auction: SealedAuctionInfo,
+}
+
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+pub struct NewRaiseResponse {
+    pub id: u32,
+    pub raise: RaiseInfo,
+}
+
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+pub struct GetContributionResponse {
+    pub contribution: Uint128,
+    // tokens claimable if the raise succeeds with the current amount raised
+    pub allocation: Uint128,
+    pub excess: Uint128,
+}
+
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+pub struct VestingPositionResponse {
+    pub id: u32,
+    pub position: VestingPosition,
+    pub claimable: Uint128,
+}
+
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+pub struct GetVestingPositionsResponse {
+    pub positions: Vec<VestingPositionResponse>,
+}
+
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+pub struct GetOrderStatusResponse {
+    // none while the order can still be filled
+    pub status: Option<OrderStatus>,
+}
+
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+pub struct GetMilestonesResponse {
+    pub payee: Addr,
+    pub milestones: Vec<Milestone>,
+    pub released: Uint128,
+    pub remaining: Uint128,
+}
+
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+pub struct NewMarketResponse {
+    pub id: u32,
+    pub market: MarketInfo,
+}
+
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+pub struct GetStakesResponse {
+    // staked on each outcome
+    pub stakes: Vec<Uint128>,
+}
+
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+pub struct GetArbitratorsResponse {
+    pub arbitrators: Vec<(Addr, ArbitratorInfo)>
+}
+
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+pub struct GetAssetListResponse {
+    pub mode: AssetListMode,
+    pub assets: Vec<AssetInfo>,
+}
+
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+pub struct GetCountersResponse {
+    pub counters: Vec<(u32, Counter)>
+}
+
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+pub struct NewCounterResponse {
+    pub id: u32,
+    pub counter: Counter,
+}
+
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+pub struct NewHTLCResponse {
+    pub id: u32,
+    pub htlc: HTLCInfo,
 }
\ No newline at end of file
@@ -5,13 +5,19 @@ mod tests {
     use cosmwasm_std::{
         Addr, Empty, coin, Coin, Uint128, from_binary, 
         Api, Decimal, to_binary,
-        testing::mock_dependencies, 
+        testing::{mock_dependencies, MockApi, MockStorage}, 
+        Storage, CanonicalAddr, StdResult, RecoverPubkeyError, VerificationError, BlockInfo, Querier, Binary, BankMsg, CustomQuery, Event, IbcMsg, IbcQuery,
     };
+    use schemars::JsonSchema;
+    use serde::de::DeserializeOwned;
     use cw20::{Balance, Cw20Coin, Cw20ExecuteMsg, Cw20CoinVerified, };
-    use cw_multi_test::{App, ContractWrapper, Executor, AppResponse};
+    use cw_multi_test::{
+        App, AppBuilder, ContractWrapper, Executor, AppResponse, Module, CosmosRouter, Ibc, FailingModule, 
+        BankKeeper, WasmKeeper, StakeKeeper, DistributionKeeper
+    };
     use cw_utils::{NativeBalance, Expiration};
 
-    use crate::{contract::{*}, msg::{QueryMsg, GetOTCsResponse, ExecuteMsg, NewOTC, NewOTCResponse}, error::ContractError, state::{OTCInfo, AskFor}};
+    use crate::{contract::{*}, msg::{QueryMsg, InstantiateMsg, ReceiveMsg, NewMarket, NewMarketResponse, NewFiatEscrow, NewMilestones, NewMilestone, GetMilestonesResponse, GetOTCsResponse, ExecuteMsg, NewOTC, NewOTCResponse, SwapMsg, SignedOrder, GetOrderStatusResponse, NewHTLC, NewHTLCResponse, NewAuction, NewAuctionResponse, NewSealedAuction, NewSealedAuctionResponse, NewRaise, NewRaiseResponse, GetVestingPositionsResponse, GetCountersResponse, GetArbitratorsResponse, GetOTCResponse, GetConfigResponse}, error::ContractError, asset::AssetInfo, cw1155::{Cw1155Coin, Cw1155ReceiveMsg, Receiver}, state::{OTCInfo, AskFor, Vesting, IbcRecipient, OrderStatus, TokenMetadata, IBC_TRANSFERS}, utils::sha256, sealed_auction::bid_commitment, arbitration::DISPUTE_TIMEOUT, ibc::{MsgTransfer, MSG_TRANSFER_TYPE_URL, hook_intermediary}};
 
 
     fn mock_app() -> App {
@@ -83,12 +89,13 @@ mod tests {
             expires: None,
             user_info: None,
             description: None,
+            ..Default::default()
         }
     }
 
     pub fn query_otcs(app: &App, addr: Addr) -> Result<GetOTCsResponse, cosmwasm_std::StdError>   {
         let res = app.wrap()
-            .query_wasm_smart(addr, &QueryMsg::GetOtcs { include_expired: None, start_after: None, limit: None });
+            .query_wasm_smart(addr, &QueryMsg::GetOtcs { include_expired: None, start_after: None, limit: None, querier: None });
         res
     }
 
@@ -112,7 +119,7 @@ mod tests {
         app: &mut App, 
         contract_address: Addr, 
         otc_data: NewOTC,
-        send_funds: &Vec<Coin>
+        send_funds: &[Coin]
 
     ) -> Result<NewOTCResponse, cosmwasm_std::StdError> {
 
@@ -173,7 +180,7 @@ mod tests {
         for event in res.events.iter() {
             println!("{:?}", event);
         }
-        println!("");
+        println!();
 
         println!("Data:");
         println!("{:?}", res.data);
@@ -181,11 +188,263 @@ mod tests {
     }
     
 
+
+    /// Accepts ICS-20 transfers by burning the sent coins and logging them as events
+    pub struct AcceptingIbc;
+
+    impl Module for AcceptingIbc {
+        type ExecT = IbcMsg;
+        type QueryT = IbcQuery;
+        type SudoT = Empty;
+
+        fn execute<ExecC, QueryC>(
+            &self,
+            api: &dyn Api,
+            storage: &mut dyn Storage,
+            router: &dyn CosmosRouter<ExecC = ExecC, QueryC = QueryC>,
+            block: &BlockInfo,
+            sender: Addr,
+            msg: IbcMsg,
+        ) -> anyhow::Result<AppResponse>
+        where
+            ExecC: std::fmt::Debug + Clone + PartialEq + JsonSchema + DeserializeOwned + 'static,
+            QueryC: CustomQuery + DeserializeOwned + 'static,
+        {
+            match msg {
+                IbcMsg::Transfer { channel_id, to_address, amount, .. } => {
+                    let sequence = storage.get(b"ibc_sequence")
+                        .map_or(1, |seq| u64::from_be_bytes(seq.try_into().unwrap()) + 1);
+                    storage.set(b"ibc_sequence", &sequence.to_be_bytes());
+
+                    let events = vec![
+                        Event::new("ibc_transfer")
+                            .add_attribute("channel", channel_id.clone())
+                            .add_attribute("recipient", to_address)
+                            .add_attribute("amount", amount.to_string()),
+                        Event::new("send_packet")
+                            .add_attribute("packet_src_channel", channel_id)
+                            .add_attribute("packet_sequence", sequence.to_string()),
+                    ];
+
+                    router.execute(api, storage, block, sender, BankMsg::Burn { amount: vec![amount] }.into())?;
+
+                    Ok(AppResponse { events, data: None })
+                },
+                _ => anyhow::bail!("unsupported ibc message")
+            }
+        }
+
+        fn sudo<ExecC, QueryC>(
+            &self,
+            _api: &dyn Api,
+            _storage: &mut dyn Storage,
+            _router: &dyn CosmosRouter<ExecC = ExecC, QueryC = QueryC>,
+            _block: &BlockInfo,
+            _msg: Empty,
+        ) -> anyhow::Result<AppResponse>
+        where
+            ExecC: std::fmt::Debug + Clone + PartialEq + JsonSchema + DeserializeOwned + 'static,
+            QueryC: CustomQuery + DeserializeOwned + 'static,
+        {
+            anyhow::bail!("unsupported ibc sudo")
+        }
+
+        fn query(
+            &self,
+            _api: &dyn Api,
+            _storage: &dyn Storage,
+            _querier: &dyn Querier,
+            _block: &BlockInfo,
+            _request: IbcQuery,
+        ) -> anyhow::Result<Binary> {
+            anyhow::bail!("unsupported ibc query")
+        }
+    }
+
+    impl Ibc for AcceptingIbc {}
+
+    // 32 bytes accounts like the intermediaries of ibc-hooks are hex encoded
+    pub struct HookApi(MockApi);
+
+    impl Api for HookApi {
+        fn addr_validate(&self, human: &str) -> StdResult<Addr> {
+            self.addr_humanize(&self.addr_canonicalize(human)?)
+        }
+
+        fn addr_canonicalize(&self, human: &str) -> StdResult<CanonicalAddr> {
+            match hex::decode(human) {
+                Ok(bytes) if bytes.len() == 32 => Ok(CanonicalAddr::from(bytes)),
+                _ => self.0.addr_canonicalize(human)
+            }
+        }
+
+        fn addr_humanize(&self, canonical: &CanonicalAddr) -> StdResult<Addr> {
+            match canonical.len() {
+                32 => Ok(Addr::unchecked(hex::encode(canonical.as_slice()))),
+                _ => self.0.addr_humanize(canonical)
+            }
+        }
+
+        fn secp256k1_verify(&self, hash: &[u8], signature: &[u8], pubkey: &[u8]) -> Result<bool, VerificationError> {
+            self.0.secp256k1_verify(hash, signature, pubkey)
+        }
+
+        fn secp256k1_recover_pubkey(&self, hash: &[u8], signature: &[u8], param: u8) -> Result<Vec<u8>, RecoverPubkeyError> {
+            self.0.secp256k1_recover_pubkey(hash, signature, param)
+        }
+
+        fn ed25519_verify(&self, message: &[u8], signature: &[u8], pubkey: &[u8]) -> Result<bool, VerificationError> {
+            self.0.ed25519_verify(message, signature, pubkey)
+        }
+
+        fn ed25519_batch_verify(&self, messages: &[&[u8]], signatures: &[&[u8]], pubkeys: &[&[u8]]) -> Result<bool, VerificationError> {
+            self.0.ed25519_batch_verify(messages, signatures, pubkeys)
+        }
+
+        fn debug(&self, message: &str) {
+            self.0.debug(message)
+        }
+    }
+
+    pub type IbcApp = App<
+        BankKeeper, HookApi, MockStorage, FailingModule<Empty, Empty, Empty>, 
+        WasmKeeper<Empty, Empty>, StakeKeeper, DistributionKeeper, AcceptingIbc
+    >;
+
+    pub fn ibc_app() -> IbcApp {
+        AppBuilder::new()
+            .with_api(HookApi(MockApi::default()))
+            .with_ibc(AcceptingIbc)
+            .build(|_, _, _| {})
+    }
+
+    // the ibc module of multitest only takes IbcMsg so the transfers are handed to it as such
+    pub fn execute_with_ibc_transfers(
+        deps: cosmwasm_std::DepsMut, 
+        env: cosmwasm_std::Env, 
+        info: cosmwasm_std::MessageInfo, 
+        msg: ExecuteMsg
+    ) -> Result<cosmwasm_std::Response, ContractError> {
+        use prost::Message;
+
+        let mut res = execute(deps, env, info, msg)?;
+
+        for sub in res.messages.iter_mut() {
+            if let cosmwasm_std::CosmosMsg::Stargate { type_url, value } = &sub.msg {
+                if type_url == MSG_TRANSFER_TYPE_URL {
+                    let transfer = MsgTransfer::decode(value.as_slice()).unwrap();
+                    let token = transfer.token.unwrap();
+                    sub.msg = IbcMsg::Transfer {
+                        channel_id: transfer.source_channel,
+                        to_address: transfer.receiver,
+                        amount: coin(token.amount.parse().unwrap(), token.denom),
+                        timeout: cosmwasm_std::IbcTimeout::with_timestamp(cosmwasm_std::Timestamp::from_nanos(transfer.timeout_timestamp)),
+                    }.into();
+                }
+            }
+        }
+
+        Ok(res)
+    }
+
+    pub fn ibc_transfers(res: &AppResponse) -> Vec<(String, String, String)> {
+        res.events
+            .iter()
+            .filter(|event| event.ty == "ibc_transfer")
+            .map(|event| (
+                event.attributes[0].value.clone(),
+                event.attributes[1].value.clone(),
+                event.attributes[2].value.clone(),
+            ))
+            .collect()
+    }
+
+
+    // bare cw1155 with balances per (owner, token id) and no approvals
+    const CW1155_BALANCES: cw_storage_plus::Map<(&str, &str), Uint128> = cw_storage_plus::Map::new("balances");
+
+    #[derive(serde::Serialize, serde::Deserialize, Clone, Debug)]
+    #[serde(rename_all = "snake_case")]
+    pub enum MockCw1155Msg {
+        Mint { to: String, token_id: String, value: Uint128 },
+        SendFrom { from: String, to: String, token_id: String, value: Uint128, msg: Option<Binary> },
+    }
+
+    #[derive(serde::Serialize, serde::Deserialize, Clone, Debug)]
+    #[serde(rename_all = "snake_case")]
+    pub enum MockCw1155Query {
+        Balance { owner: String, token_id: String },
+    }
+
+    fn cw1155_execute(deps: cosmwasm_std::DepsMut, _env: cosmwasm_std::Env, info: cosmwasm_std::MessageInfo, msg: MockCw1155Msg) -> cosmwasm_std::StdResult<cosmwasm_std::Response> {
+        let mut add = |owner: &str, token_id: &str, value: Uint128| CW1155_BALANCES.update(
+            deps.storage, 
+            (owner, token_id), 
+            |balance| -> cosmwasm_std::StdResult<_> { Ok(balance.unwrap_or_default().checked_add(value)?) }
+        );
+
+        match msg {
+            MockCw1155Msg::Mint { to, token_id, value } => {
+                add(&to, &token_id, value)?;
+                Ok(cosmwasm_std::Response::new())
+            },
+            MockCw1155Msg::SendFrom { from, to, token_id, value, msg } => {
+                assert_eq!(from, info.sender.as_str());
+                CW1155_BALANCES.update(deps.storage, (&from, &token_id), |balance| -> cosmwasm_std::StdResult<_> { 
+                    Ok(balance.unwrap_or_default().checked_sub(value)?) 
+                })?;
+                CW1155_BALANCES.update(deps.storage, (&to, &token_id), |balance| -> cosmwasm_std::StdResult<_> { 
+                    Ok(balance.unwrap_or_default().checked_add(value)?) 
+                })?;
+
+                let mut res = cosmwasm_std::Response::new();
+                if let Some(msg) = msg {
+                    res = res.add_message(cosmwasm_std::WasmMsg::Execute {
+                        contract_addr: to,
+                        funds: vec![],
+                        msg: to_binary(&ExecuteMsg::Receive(Receiver::Cw1155(Cw1155ReceiveMsg {
+                            operator: info.sender.to_string(),
+                            from: Some(from),
+                            token_id,
+                            amount: value,
+                            msg
+                        })))?,
+                    });
+                }
+                Ok(res)
+            }
+        }
+    }
+
+    fn cw1155_query(deps: cosmwasm_std::Deps, _env: cosmwasm_std::Env, msg: MockCw1155Query) -> cosmwasm_std::StdResult<Binary> {
+        let MockCw1155Query::Balance { owner, token_id } = msg;
+        to_binary(&CW1155_BALANCES.may_load(deps.storage, (&owner, &token_id))?.unwrap_or_default())
+    }
+
+    pub fn init_cw1155(app: &mut App) -> Addr {
+        let code = ContractWrapper::new(
+            cw1155_execute, 
+            |_: cosmwasm_std::DepsMut, _: cosmwasm_std::Env, _: cosmwasm_std::MessageInfo, _: Empty| -> cosmwasm_std::StdResult<_> { 
+                Ok(cosmwasm_std::Response::new()) 
+            }, 
+            cw1155_query
+        );
+        let code_id = app.store_code(Box::new(code));
+        app.instantiate_contract(code_id, Addr::unchecked("owner"), &Empty {}, &[], "cw1155", None).unwrap()
+    }
+
+    pub fn query_cw1155_balance(app: &App, owner: &Addr, contract: &Addr, token_id: &str) -> Uint128 {
+        app.wrap()
+            .query_wasm_smart(contract, &MockCw1155Query::Balance { owner: owner.to_string(), token_id: token_id.to_string() })
+            .unwrap()
+    }
+
+
     #[test]
     fn init_contract() {
         let mut app = mock_app();
         let addr = init_main(&mut app);
-        assert!(addr.into_string().len() > 0)
+        assert!(!addr.into_string().is_empty())
     }
 
     #[test]
@@ -199,7 +458,7 @@ mod tests {
         assert_eq!(balance.amount, Uint128::zero());
 
         let amount : u128 = 1_000_000;
-        mint_native(&mut app, alice.clone().to_string(), token.clone(), amount.clone());
+        mint_native(&mut app, alice.clone().to_string(), token.clone(), amount);
         
         let balance = query_native_balance(&app, alice.clone(), token.clone()).unwrap();
         assert_eq!(balance.amount, Uint128::from(amount));
@@ -220,7 +479,7 @@ mod tests {
         let to_ask : u128 = 5_000_000;
 
 
-        mint_native(&mut app, alice.clone().to_string(), token.clone(), amount.clone());
+        mint_native(&mut app, alice.clone().to_string(), token.clone(), amount);
 
 
         let no_balances = new_otc_with_nones(vec![]);
@@ -238,7 +497,7 @@ mod tests {
                 NativeBalance(
                     vec![
                         coin(
-                            to_ask.clone(), 
+                            to_ask, 
                             token.clone()
                         )
                     ]
@@ -252,7 +511,7 @@ mod tests {
                 NativeBalance(
                     vec![
                         coin(
-                            to_ask.clone(), 
+                            to_ask, 
                             token2.clone()
                         )
                     ]
@@ -264,7 +523,7 @@ mod tests {
             alice.clone(),
             contract_address.clone(),
             &ExecuteMsg::Create( no_balances ),
-            &vec![coin(amount, token.clone())],
+            &[coin(amount, token.clone())],
         ).unwrap_err();
         
         
@@ -278,7 +537,7 @@ mod tests {
             alice.clone(),
             contract_address.clone(),
             &ExecuteMsg::Create( no_coins ),
-            &vec![coin(amount, token.clone())],
+            &[coin(amount, token.clone())],
         ).unwrap_err();
 
         assert_eq!(
@@ -291,7 +550,7 @@ mod tests {
             alice.clone(),
             contract_address.clone(),
             &ExecuteMsg::Create( same_token ),
-            &vec![coin(amount, token.clone())],
+            &[coin(amount, token.clone())],
         ).unwrap_err();
 
 
@@ -305,7 +564,7 @@ mod tests {
             alice.clone(),
             contract_address.clone(),
             &ExecuteMsg::Create( normal.clone() ),
-            &vec![coin(amount, token.clone())],
+            &[coin(amount, token.clone())],
         ).unwrap();
 
 
@@ -317,24 +576,31 @@ mod tests {
                 id: 0,
                 otc: OTCInfo { 
                     seller: mock_dependencies().api.addr_canonicalize(alice.as_str()).unwrap(), 
-                    sell_native: true, 
-                    sell_amount: amount.clone().into(), 
+                    sell: AssetInfo::Native(token), 
+                    sell_amount: amount.into(), 
                     initial_sell_amount: amount.into(), 
-                    sell_denom: Some(token), 
-                    sell_address: None, 
                     ask_for: vec![
                         AskFor {
-                            address: None,
-                            denom: Some(token2),
-                            amount: to_ask.clone().into(),
-                            initial_amount: to_ask.into(),
-                            native: true
+                            info: AssetInfo::Native(token2),
+                            amount: to_ask.into(),
+                            initial_amount: to_ask.into()
                         }
                     ], 
                     expires: Expiration::Never {}, 
                     user_info: normal.user_info, 
-                    description: normal.description
-
+                    description: normal.description,
+                    allowed_takers: vec![],
+                    whitelist_root: None,
+                    secret_hash: None,
+                    dutch_auction: None,
+                    vesting: None,
+                    fiat_escrow: None,
+                    milestones: None,
+                    ibc_recipient: None,
+                    remote: None,
+                    allowance: false,
+                    invalid: false,
+                    arbitration: None,
                 }
             }
         );
@@ -355,7 +621,7 @@ mod tests {
         let token2 = String::from("token2");
         let amount : u128 = 10_000_000;
 
-        mint_native(&mut app, alice.clone().to_string(), token.clone(), amount.clone());
+        mint_native(&mut app, alice.clone().to_string(), token.clone(), amount);
 
         let to_sell : u128 = 4_000_000;
         let to_ask : u128 = 5_000_000;
@@ -365,10 +631,10 @@ mod tests {
             contract_address.clone(),
             &ExecuteMsg::Create(
                 new_otc_with_nones(
-                    native_wrapper(to_ask.clone(), token2.clone())
+                    native_wrapper(to_ask, token2.clone())
                 )
             ),
-            &vec![coin(to_sell, token.clone())],
+            &[coin(to_sell, token.clone())],
         )
 
         .unwrap();
@@ -383,7 +649,7 @@ mod tests {
         let (_, otc) = otcs.otcs[0].clone();
 
         assert_eq!(otc.sell_amount, Uint128::from(to_sell));
-        assert_eq!(otc.sell_denom, Some(token));
+        assert_eq!(otc.sell, AssetInfo::Native(token));
 
         assert_eq!(otc.ask_for[0].amount, Uint128::from(to_ask));
         
@@ -410,14 +676,14 @@ mod tests {
             &mut app, 
             alice.clone().to_string(), 
             token.clone(), 
-            amount.clone()
+            amount
         );
 
         mint_native(
             &mut app, 
             bob.clone().to_string(), 
             token2.clone(), 
-            amount2.clone()
+            amount2
         );
 
 
@@ -426,11 +692,11 @@ mod tests {
             contract_address.clone(), 
             new_otc_with_nones(
                 native_wrapper(
-                    amount2.clone(), 
+                    amount2, 
                     token2.clone()
                 )
             ),
-            &vec![coin(amount.clone(), token.clone())],
+            &[coin(amount, token.clone())],
             
         ).unwrap();
     
@@ -444,12 +710,11 @@ mod tests {
         let res = app.execute_contract(
             bob.clone(), 
             contract_address.clone(), 
-            &ExecuteMsg::Swap {
+            &ExecuteMsg::Swap(SwapMsg {
                 otc_id: id,
-            },
-            &vec![
-                coin(amount2.clone(), token2.clone())
-            ]
+                ..Default::default()
+            }),
+            &[coin(amount2, token2.clone())]
         ).unwrap();
 
 
@@ -540,12 +805,12 @@ mod tests {
             contract_address.clone(),
             new_otc_with_nones(
                 cw20_wrapper(
-                    amount2.clone(), 
+                    amount2, 
                     token2.clone()
                 )
             ),
             token.clone(), 
-            amount.clone()
+            amount
         );
         
 
@@ -562,10 +827,10 @@ mod tests {
             token2.clone(), 
             &Cw20ExecuteMsg::Send { 
                 contract: contract_address.to_string(), 
-                amount: amount2.clone().into(),
-                msg: to_binary(&ExecuteMsg::Swap { otc_id: id, }).unwrap()
+                amount: amount2.into(),
+                msg: to_binary(&ExecuteMsg::Swap(SwapMsg { otc_id: id, ..Default::default() })).unwrap()
             },
-            &vec![]
+            &[]
         ).unwrap();
 
         print_response(&res);
@@ -589,8 +854,8 @@ mod tests {
         assert_eq!(otcs.otcs.len(), 0);
 
         let owner = Addr::unchecked("owner");
-        let maker_fee_rate = Decimal::from_ratio(2 as u8, 10000u16);
-        let taker_fee_rate = Decimal::from_ratio(1 as u8, 10000u16);
+        let maker_fee_rate = Decimal::from_ratio(2_u8, 10000u16);
+        let taker_fee_rate = Decimal::from_ratio(1_u8, 10000u16);
 
         
         let maker_fee = Uint128::from(amount2) * maker_fee_rate;
@@ -614,4 +879,1857 @@ mod tests {
 
 
 
-}
\ No newline at end of file
+    #[test]
+    fn htlc_claim_and_refund() {
+        let mut app = mock_app();
+        let contract_address = init_main(&mut app);
+
+        let alice = Addr::unchecked("alice");
+        let bob = Addr::unchecked("bob");
+        let token = String::from("token1");
+        let amount : u128 = 10_000_000;
+
+        mint_native(&mut app, alice.to_string(), token.clone(), amount * 2);
+
+        let preimage = [7u8; 32];
+        let timelock = Expiration::AtHeight(app.block_info().height + 100);
+
+        let new_htlc = NewHTLC {
+            taker: bob.clone(),
+            hashlock: hex::encode(sha256(&preimage)),
+            timelock,
+            description: None,
+        };
+
+        let mut ids = vec![];
+        for _ in 0..2 {
+            let res = app.execute_contract(
+                alice.clone(),
+                contract_address.clone(),
+                &ExecuteMsg::CreateHtlc(new_htlc.clone()),
+                &[coin(amount, token.clone())],
+            ).unwrap();
+            let data : NewHTLCResponse = from_binary(&res.data.unwrap()).unwrap();
+            ids.push(data.id);
+        }
+        assert_eq!(ids, vec![0, 1]);
+
+        let err = app.execute_contract(
+            bob.clone(), 
+            contract_address.clone(), 
+            &ExecuteMsg::Claim { otc_id: ids[0], preimage: hex::encode([8u8; 32]) }, 
+            &[]
+        ).unwrap_err();
+        assert_eq!(err.root_cause().to_string(), ContractError::WrongSecret {}.to_string());
+
+        let err = app.execute_contract(
+            alice.clone(), 
+            contract_address.clone(), 
+            &ExecuteMsg::Refund { otc_id: ids[0] }, 
+            &[]
+        ).unwrap_err();
+        assert_eq!(err.root_cause().to_string(), ContractError::NotExpired {}.to_string());
+
+        let res = app.execute_contract(
+            bob.clone(), 
+            contract_address.clone(), 
+            &ExecuteMsg::Claim { otc_id: ids[0], preimage: hex::encode(preimage) }, 
+            &[]
+        ).unwrap();
+
+        let wasm_event = res.events.iter().find(|e| e.ty == "wasm-peerswap_htlc_claimed").unwrap();
+        assert!(wasm_event.attributes.iter().any(|a| a.key == "preimage" && a.value == hex::encode(preimage)));
+
+        let maker_fee = Uint128::from(amount) * Decimal::from_ratio(1u8, 10000u16);
+        let balance = query_native_balance(&app, bob, token.clone()).unwrap();
+        assert_eq!(balance.amount, Uint128::from(amount) - maker_fee);
+
+        // the second one can only be refunded after the timelock
+        app.update_block(|block| block.height += 101);
+
+        let err = app.execute_contract(
+            alice.clone(), 
+            contract_address.clone(), 
+            &ExecuteMsg::Claim { otc_id: ids[1], preimage: hex::encode(preimage) }, 
+            &[]
+        ).unwrap_err();
+        assert_eq!(err.root_cause().to_string(), ContractError::Expired {}.to_string());
+
+        app.execute_contract(
+            alice.clone(), 
+            contract_address, 
+            &ExecuteMsg::Refund { otc_id: ids[1] }, 
+            &[]
+        ).unwrap();
+
+        let balance = query_native_balance(&app, alice, token).unwrap();
+        assert_eq!(balance.amount, Uint128::from(amount));
+    }
+
+
+
+    #[test]
+    fn auction_bids_and_settlement() {
+        let mut app = mock_app();
+        let contract_address = init_main(&mut app);
+
+        let alice = Addr::unchecked("alice");
+        let bob = Addr::unchecked("bob");
+        let carol = Addr::unchecked("carol");
+        let token = String::from("token1");
+        let token2 = String::from("token2");
+
+        let amount : u128 = 10_000_000;
+
+        mint_native(&mut app, alice.to_string(), token.clone(), amount);
+        mint_native(&mut app, bob.to_string(), token2.clone(), amount);
+        mint_native(&mut app, carol.to_string(), token2.clone(), amount);
+
+        let end_time = app.block_info().time.seconds() + 100;
+
+        let res = app.execute_contract(
+            alice.clone(),
+            contract_address.clone(),
+            &ExecuteMsg::CreateAuction(NewAuction {
+                min_bid: native_wrapper(1_000_000, token2.clone()).pop().unwrap(),
+                min_increment: None,
+                end_time,
+                extension: Some(50),
+                description: None,
+            }),
+            &[coin(amount, token.clone())],
+        ).unwrap();
+        let auction_id = from_binary::<NewAuctionResponse>(&res.data.unwrap()).unwrap().id;
+
+        let bid = |app: &mut App, bidder: &Addr, amount: u128| app.execute_contract(
+            bidder.clone(),
+            contract_address.clone(),
+            &ExecuteMsg::Bid { auction_id },
+            &[coin(amount, token2.clone())],
+        );
+
+        let err = bid(&mut app, &bob, 999_999).unwrap_err();
+        assert_eq!(err.root_cause().to_string(), ContractError::BidTooLow {}.to_string());
+
+        bid(&mut app, &bob, 1_000_000).unwrap();
+        bid(&mut app, &carol, 1_500_000).unwrap();
+
+        // outbid bob got refunded
+        let balance = query_native_balance(&app, bob.clone(), token2.clone()).unwrap();
+        assert_eq!(balance.amount, Uint128::from(amount));
+
+        let err = bid(&mut app, &bob, 1_500_000).unwrap_err();
+        assert_eq!(err.root_cause().to_string(), ContractError::BidTooLow {}.to_string());
+
+        // a late bid extends the auction
+        app.update_block(|block| block.time = block.time.plus_seconds(90));
+        bid(&mut app, &bob, 2_000_000).unwrap();
+
+        app.update_block(|block| block.time = block.time.plus_seconds(20));
+
+        let err = app.execute_contract(
+            carol.clone(),
+            contract_address.clone(),
+            &ExecuteMsg::Settle { auction_id },
+            &[],
+        ).unwrap_err();
+        assert_eq!(err.root_cause().to_string(), ContractError::NotExpired {}.to_string());
+
+        app.update_block(|block| block.time = block.time.plus_seconds(30));
+
+        app.execute_contract(
+            carol.clone(),
+            contract_address.clone(),
+            &ExecuteMsg::Settle { auction_id },
+            &[],
+        ).unwrap();
+
+        let maker_fee = Uint128::from(amount) * Decimal::from_ratio(1u8, 10000u16);
+        let taker_fee = Uint128::from(2_000_000u128) * Decimal::from_ratio(2u8, 10000u16);
+
+        let balance = query_native_balance(&app, bob, token.clone()).unwrap();
+        assert_eq!(balance.amount, Uint128::from(amount) - maker_fee);
+
+        let balance = query_native_balance(&app, alice.clone(), token2.clone()).unwrap();
+        assert_eq!(balance.amount, Uint128::from(2_000_000u128) - taker_fee);
+
+        let balance = query_native_balance(&app, carol.clone(), token2.clone()).unwrap();
+        assert_eq!(balance.amount, Uint128::from(amount));
+
+        // a bid too small to pay a taker fee still settles
+        mint_native(&mut app, alice.to_string(), token.clone(), amount);
+
+        let res = app.execute_contract(
+            alice.clone(),
+            contract_address.clone(),
+            &ExecuteMsg::CreateAuction(NewAuction {
+                min_bid: native_wrapper(1_000, token2.clone()).pop().unwrap(),
+                min_increment: None,
+                end_time: app.block_info().time.seconds() + 100,
+                extension: None,
+                description: None,
+            }),
+            &[coin(amount, token.clone())],
+        ).unwrap();
+        let auction_id = from_binary::<NewAuctionResponse>(&res.data.unwrap()).unwrap().id;
+
+        app.execute_contract(carol.clone(), contract_address.clone(), &ExecuteMsg::Bid { auction_id }, &[coin(1_000, token2.clone())]).unwrap();
+        app.update_block(|block| block.time = block.time.plus_seconds(100));
+        app.execute_contract(carol.clone(), contract_address.clone(), &ExecuteMsg::Settle { auction_id }, &[]).unwrap();
+
+        let balance = query_native_balance(&app, alice, token2).unwrap();
+        assert_eq!(balance.amount, Uint128::from(2_000_000u128) - taker_fee + Uint128::from(1_000u128));
+
+        let balance = query_native_balance(&app, carol, token).unwrap();
+        assert_eq!(balance.amount, Uint128::from(amount) - maker_fee);
+    }
+
+
+    #[test]
+    fn sealed_auction_second_price() {
+        let mut app = mock_app();
+        let contract_address = init_main(&mut app);
+
+        let alice = Addr::unchecked("alice");
+        let bob = Addr::unchecked("bob");
+        let carol = Addr::unchecked("carol");
+        let dave = Addr::unchecked("dave");
+        let token = String::from("token1");
+        let token2 = String::from("token2");
+
+        let amount : u128 = 10_000_000;
+
+        mint_native(&mut app, alice.to_string(), token.clone(), amount);
+        mint_native(&mut app, bob.to_string(), token2.clone(), amount);
+        mint_native(&mut app, carol.to_string(), token2.clone(), amount);
+        mint_native(&mut app, dave.to_string(), token2.clone(), amount);
+
+        let now = app.block_info().time.seconds();
+
+        let res = app.execute_contract(
+            alice.clone(),
+            contract_address.clone(),
+            &ExecuteMsg::CreateSealedAuction(NewSealedAuction {
+                min_bid: native_wrapper(1_000_000, token2.clone()).pop().unwrap(),
+                commit_end: now + 100,
+                reveal_end: now + 200,
+                second_price: true,
+                refund_unrevealed: false,
+                description: None,
+            }),
+            &[coin(amount, token.clone())],
+        ).unwrap();
+        let auction_id = from_binary::<NewSealedAuctionResponse>(&res.data.unwrap()).unwrap().id;
+
+        let commit = |app: &mut App, bidder: &Addr, bid: u128, deposit: u128| app.execute_contract(
+            bidder.clone(),
+            contract_address.clone(),
+            &ExecuteMsg::CommitBid { 
+                auction_id, 
+                commitment: bid_commitment(bidder, Uint128::from(bid), "salt").unwrap() 
+            },
+            &[coin(deposit, token2.clone())],
+        );
+
+        let reveal = |app: &mut App, bidder: &Addr, bid: u128| app.execute_contract(
+            bidder.clone(),
+            contract_address.clone(),
+            &ExecuteMsg::RevealBid { auction_id, amount: Uint128::from(bid), salt: "salt".to_string() },
+            &[],
+        );
+
+        // the amount can't be moved into the salt
+        assert_ne!(
+            bid_commitment(&bob, Uint128::from(12u128), "3salt").unwrap(),
+            bid_commitment(&bob, Uint128::from(123u128), "salt").unwrap()
+        );
+
+        // deposits hide the real bids
+        commit(&mut app, &bob, 2_000_000, 3_000_000).unwrap();
+        commit(&mut app, &carol, 1_500_000, 1_500_000).unwrap();
+        commit(&mut app, &dave, 5_000_000, 1_000_000).unwrap();
+
+        let err = commit(&mut app, &bob, 2_500_000, 3_000_000).unwrap_err();
+        assert_eq!(err.root_cause().to_string(), ContractError::AlreadyCommitted {}.to_string());
+
+        let err = reveal(&mut app, &bob, 2_000_000).unwrap_err();
+        assert_eq!(err.root_cause().to_string(), ContractError::WrongPhase {}.to_string());
+
+        app.update_block(|block| block.time = block.time.plus_seconds(100));
+
+        let err = commit(&mut app, &carol, 1_500_000, 1_500_000).unwrap_err();
+        assert_eq!(err.root_cause().to_string(), ContractError::WrongPhase {}.to_string());
+
+        let err = reveal(&mut app, &bob, 2_500_000).unwrap_err();
+        assert_eq!(err.root_cause().to_string(), ContractError::WrongSecret {}.to_string());
+
+        reveal(&mut app, &bob, 2_000_000).unwrap();
+        reveal(&mut app, &carol, 1_500_000).unwrap();
+        // dave never reveals
+
+        app.update_block(|block| block.time = block.time.plus_seconds(100));
+
+        let withdraw = |app: &mut App, bidder: &Addr| app.execute_contract(
+            carol.clone(),
+            contract_address.clone(),
+            &ExecuteMsg::WithdrawSealedBid { auction_id, bidder: bidder.to_string() },
+            &[],
+        );
+
+        // the winner is only paid out by the settlement
+        let err = withdraw(&mut app, &bob).unwrap_err();
+        assert_eq!(err.root_cause().to_string(), ContractError::WrongPhase {}.to_string());
+
+        withdraw(&mut app, &carol).unwrap();
+
+        app.execute_contract(
+            carol.clone(),
+            contract_address.clone(),
+            &ExecuteMsg::Settle { auction_id },
+            &[],
+        ).unwrap();
+
+        let err = app.execute_contract(
+            carol.clone(),
+            contract_address.clone(),
+            &ExecuteMsg::Settle { auction_id },
+            &[],
+        ).unwrap_err();
+        assert_eq!(err.root_cause().to_string(), ContractError::WrongPhase {}.to_string());
+
+        // anyone can release the forfeited deposit, which removes the auction
+        withdraw(&mut app, &dave).unwrap();
+
+        let err = withdraw(&mut app, &dave).unwrap_err();
+        assert_eq!(err.root_cause().to_string(), ContractError::NotFound {}.to_string());
+
+        let price = Uint128::from(1_500_000u128);
+        let maker_fee = Uint128::from(amount) * Decimal::from_ratio(1u8, 10000u16);
+        let taker_fee = price * Decimal::from_ratio(2u8, 10000u16);
+
+        let balance = query_native_balance(&app, bob.clone(), token).unwrap();
+        assert_eq!(balance.amount, Uint128::from(amount) - maker_fee);
+
+        // the winner pays the second highest bid
+        let balance = query_native_balance(&app, bob, token2.clone()).unwrap();
+        assert_eq!(balance.amount, Uint128::from(amount) - price);
+
+        let balance = query_native_balance(&app, carol, token2.clone()).unwrap();
+        assert_eq!(balance.amount, Uint128::from(amount));
+
+        // unrevealed deposit goes to the seller
+        let balance = query_native_balance(&app, dave, token2.clone()).unwrap();
+        assert_eq!(balance.amount, Uint128::from(amount - 1_000_000));
+
+        let balance = query_native_balance(&app, alice, token2).unwrap();
+        assert_eq!(balance.amount, price - taker_fee + Uint128::from(1_000_000u128));
+    }
+
+
+    #[test]
+    fn sealed_auction_low_price_settles() {
+        let mut app = mock_app();
+        let contract_address = init_main(&mut app);
+
+        let alice = Addr::unchecked("alice");
+        let bob = Addr::unchecked("bob");
+        let token = String::from("token1");
+        let token2 = String::from("token2");
+
+        mint_native(&mut app, alice.to_string(), token.clone(), 10_000);
+        mint_native(&mut app, bob.to_string(), token2.clone(), 10_000);
+
+        let now = app.block_info().time.seconds();
+
+        let res = app.execute_contract(
+            alice.clone(),
+            contract_address.clone(),
+            &ExecuteMsg::CreateSealedAuction(NewSealedAuction {
+                min_bid: native_wrapper(1_000, token2.clone()).pop().unwrap(),
+                commit_end: now + 100,
+                reveal_end: now + 200,
+                second_price: true,
+                refund_unrevealed: false,
+                description: None,
+            }),
+            &[coin(10_000, token.clone())],
+        ).unwrap();
+        let auction_id = from_binary::<NewSealedAuctionResponse>(&res.data.unwrap()).unwrap().id;
+
+        app.execute_contract(
+            bob.clone(),
+            contract_address.clone(),
+            &ExecuteMsg::CommitBid { auction_id, commitment: bid_commitment(&bob, Uint128::from(2_000u128), "salt").unwrap() },
+            &[coin(2_000, token2.clone())],
+        ).unwrap();
+
+        app.update_block(|block| block.time = block.time.plus_seconds(100));
+        app.execute_contract(
+            bob.clone(),
+            contract_address.clone(),
+            &ExecuteMsg::RevealBid { auction_id, amount: Uint128::from(2_000u128), salt: "salt".to_string() },
+            &[],
+        ).unwrap();
+
+        // the minimum bid clears with fees rounding down to nothing
+        app.update_block(|block| block.time = block.time.plus_seconds(100));
+        app.execute_contract(bob.clone(), contract_address.clone(), &ExecuteMsg::Settle { auction_id }, &[]).unwrap();
+
+        let balance = query_native_balance(&app, bob.clone(), token).unwrap();
+        assert_eq!(balance.amount, Uint128::from(9_999u128));
+        let balance = query_native_balance(&app, bob, token2.clone()).unwrap();
+        assert_eq!(balance.amount, Uint128::from(9_000u128));
+        let balance = query_native_balance(&app, alice, token2).unwrap();
+        assert_eq!(balance.amount, Uint128::from(1_000u128));
+    }
+
+
+    #[test]
+    fn raise_oversubscribed_and_failed() {
+        let mut app = mock_app();
+        let contract_address = init_main(&mut app);
+
+        let alice = Addr::unchecked("alice");
+        let bob = Addr::unchecked("bob");
+        let carol = Addr::unchecked("carol");
+        let token = String::from("token1");
+        let token2 = String::from("token2");
+
+        let amount : u128 = 10_000_000;
+
+        mint_native(&mut app, alice.to_string(), token.clone(), 2 * amount);
+        mint_native(&mut app, bob.to_string(), token2.clone(), amount);
+        mint_native(&mut app, carol.to_string(), token2.clone(), amount);
+
+        let deadline = app.block_info().time.seconds() + 100;
+
+        let create = |app: &mut App| {
+            let res = app.execute_contract(
+                alice.clone(),
+                contract_address.clone(),
+                &ExecuteMsg::CreateRaise(NewRaise {
+                    soft_cap: native_wrapper(2_000_000, token2.clone()).pop().unwrap(),
+                    hard_cap: Uint128::from(6_000_000u128),
+                    deadline,
+                    description: None,
+                }),
+                &[coin(amount, token.clone())],
+            ).unwrap();
+            from_binary::<NewRaiseResponse>(&res.data.unwrap()).unwrap().id
+        };
+
+        let err = app.execute_contract(
+            alice.clone(),
+            contract_address.clone(),
+            &ExecuteMsg::CreateRaise(NewRaise {
+                soft_cap: native_wrapper(0, token2.clone()).pop().unwrap(),
+                hard_cap: Uint128::from(6_000_000u128),
+                deadline,
+                description: None,
+            }),
+            &[coin(amount, token.clone())],
+        ).unwrap_err();
+        assert_eq!(err.root_cause().to_string(), ContractError::InvalidCaps {}.to_string());
+
+        let raised = create(&mut app);
+        let failed = create(&mut app);
+
+        let execute = |app: &mut App, sender: &Addr, msg: ExecuteMsg, funds: &[Coin]| app.execute_contract(
+            sender.clone(),
+            contract_address.clone(),
+            &msg,
+            funds,
+        );
+
+        execute(&mut app, &bob, ExecuteMsg::Contribute { raise_id: raised }, &[coin(6_000_000, token2.clone())]).unwrap();
+        execute(&mut app, &carol, ExecuteMsg::Contribute { raise_id: raised }, &[coin(3_000_000, token2.clone())]).unwrap();
+        execute(&mut app, &carol, ExecuteMsg::Contribute { raise_id: failed }, &[coin(1_000_000, token2.clone())]).unwrap();
+
+        let err = execute(&mut app, &bob, ExecuteMsg::ClaimTokens { raise_id: raised }, &[]).unwrap_err();
+        assert_eq!(err.root_cause().to_string(), ContractError::NotExpired {}.to_string());
+
+        app.update_block(|block| block.time = block.time.plus_seconds(100));
+
+        let err = execute(&mut app, &bob, ExecuteMsg::Contribute { raise_id: raised }, &[coin(1_000_000, token2.clone())]).unwrap_err();
+        assert_eq!(err.root_cause().to_string(), ContractError::Expired {}.to_string());
+
+        let err = execute(&mut app, &bob, ExecuteMsg::Finalize { raise_id: raised }, &[]).unwrap_err();
+        assert_eq!(err.root_cause().to_string(), ContractError::Unauthorized {}.to_string());
+
+        execute(&mut app, &alice, ExecuteMsg::Finalize { raise_id: raised }, &[]).unwrap();
+        execute(&mut app, &alice, ExecuteMsg::Finalize { raise_id: failed }, &[]).unwrap();
+
+        let err = execute(&mut app, &alice, ExecuteMsg::Finalize { raise_id: raised }, &[]).unwrap_err();
+        assert_eq!(err.root_cause().to_string(), ContractError::Finalized {}.to_string());
+
+        // only the hard cap goes to the creator
+        let taker_fee = Uint128::from(6_000_000u128) * Decimal::from_ratio(2u8, 10000u16);
+        let balance = query_native_balance(&app, alice.clone(), token2.clone()).unwrap();
+        assert_eq!(balance.amount, Uint128::from(6_000_000u128) - taker_fee);
+
+        // tokens of the failed raise went back
+        let balance = query_native_balance(&app, alice, token.clone()).unwrap();
+        assert_eq!(balance.amount, Uint128::from(amount));
+
+        let err = execute(&mut app, &carol, ExecuteMsg::ClaimRefund { raise_id: raised }, &[]).unwrap_err();
+        assert_eq!(err.root_cause().to_string(), ContractError::SoftCapReached {}.to_string());
+
+        let err = execute(&mut app, &carol, ExecuteMsg::ClaimTokens { raise_id: failed }, &[]).unwrap_err();
+        assert_eq!(err.root_cause().to_string(), ContractError::SoftCapNotReached {}.to_string());
+
+        execute(&mut app, &carol, ExecuteMsg::ClaimRefund { raise_id: failed }, &[]).unwrap();
+        execute(&mut app, &bob, ExecuteMsg::ClaimTokens { raise_id: raised }, &[]).unwrap();
+        execute(&mut app, &carol, ExecuteMsg::ClaimTokens { raise_id: raised }, &[]).unwrap();
+
+        let err = execute(&mut app, &bob, ExecuteMsg::ClaimTokens { raise_id: raised }, &[]).unwrap_err();
+        assert_eq!(err.root_cause().to_string(), ContractError::NotFound {}.to_string());
+
+        // 9M raised for a 6M hard cap: a third of each contribution is refunded
+        let bob_tokens = Uint128::from(6_666_666u128);
+        let balance = query_native_balance(&app, bob.clone(), token.clone()).unwrap();
+        assert_eq!(balance.amount, bob_tokens - bob_tokens * Decimal::from_ratio(1u8, 10000u16));
+        let balance = query_native_balance(&app, bob, token2.clone()).unwrap();
+        assert_eq!(balance.amount, Uint128::from(amount - 4_000_000));
+
+        let carol_tokens = Uint128::from(3_333_333u128);
+        let balance = query_native_balance(&app, carol.clone(), token).unwrap();
+        assert_eq!(balance.amount, carol_tokens - carol_tokens * Decimal::from_ratio(1u8, 10000u16));
+        let balance = query_native_balance(&app, carol, token2).unwrap();
+        assert_eq!(balance.amount, Uint128::from(amount - 2_000_000));
+    }
+
+
+    #[test]
+    fn vested_swap_and_claims() {
+        let mut app = mock_app();
+        let contract_address = init_main(&mut app);
+
+        let alice = Addr::unchecked("alice");
+        let bob = Addr::unchecked("bob");
+        let token = String::from("token1");
+        let token2 = String::from("token2");
+
+        let amount : u128 = 10_000_000;
+
+        mint_native(&mut app, alice.to_string(), token.clone(), amount);
+        mint_native(&mut app, bob.to_string(), token2.clone(), amount);
+
+        let invalid = NewOTC {
+            vesting: Some(Vesting { cliff: 100, duration: 0 }),
+            ..new_otc_with_nones(native_wrapper(5_000_000, token2.clone()))
+        };
+
+        let err = app.execute_contract(
+            alice.clone(),
+            contract_address.clone(),
+            &ExecuteMsg::Create(invalid),
+            &[coin(amount, token.clone())],
+        ).unwrap_err();
+        assert_eq!(err.root_cause().to_string(), ContractError::InvalidVesting {}.to_string());
+
+        let otc_id = create_new_otc_with_funds(
+            &mut app, 
+            contract_address.clone(), 
+            NewOTC {
+                vesting: Some(Vesting { cliff: 100, duration: 1000 }),
+                ..new_otc_with_nones(native_wrapper(5_000_000, token2.clone()))
+            }, 
+            &[coin(amount, token.clone())]
+        ).unwrap().id;
+
+        app.execute_contract(
+            bob.clone(),
+            contract_address.clone(),
+            &ExecuteMsg::Swap(SwapMsg { otc_id, ..Default::default() }),
+            &[coin(5_000_000, token2)],
+        ).unwrap();
+
+        // nothing delivered at the swap
+        let balance = query_native_balance(&app, bob.clone(), token.clone()).unwrap();
+        assert_eq!(balance.amount, Uint128::zero());
+
+        let claim = |app: &mut App| app.execute_contract(
+            bob.clone(),
+            contract_address.clone(),
+            &ExecuteMsg::ClaimVested {},
+            &[],
+        );
+
+        app.update_block(|block| block.time = block.time.plus_seconds(50));
+        let err = claim(&mut app).unwrap_err();
+        assert_eq!(err.root_cause().to_string(), ContractError::NothingToClaim {}.to_string());
+
+        let vested = Uint128::from(amount) - Uint128::from(amount) * Decimal::from_ratio(1u8, 10000u16);
+
+        app.update_block(|block| block.time = block.time.plus_seconds(450));
+
+        let res : GetVestingPositionsResponse = app.wrap().query_wasm_smart(
+            contract_address.clone(), 
+            &QueryMsg::GetVestingPositions { owner: bob.clone(), start_after: None, limit: None }
+        ).unwrap();
+        assert_eq!(res.positions.len(), 1);
+        assert_eq!(res.positions[0].position.amount, vested);
+        assert_eq!(res.positions[0].claimable, vested.multiply_ratio(1u8, 2u8));
+
+        // paginated by position id
+        let res : GetVestingPositionsResponse = app.wrap().query_wasm_smart(
+            contract_address.clone(), 
+            &QueryMsg::GetVestingPositions { owner: bob.clone(), start_after: Some(res.positions[0].id), limit: Some(1) }
+        ).unwrap();
+        assert!(res.positions.is_empty());
+
+        claim(&mut app).unwrap();
+        let balance = query_native_balance(&app, bob.clone(), token.clone()).unwrap();
+        assert_eq!(balance.amount, vested.multiply_ratio(1u8, 2u8));
+
+        app.update_block(|block| block.time = block.time.plus_seconds(1000));
+        claim(&mut app).unwrap();
+
+        let balance = query_native_balance(&app, bob.clone(), token).unwrap();
+        assert_eq!(balance.amount, vested);
+
+        let res : GetVestingPositionsResponse = app.wrap().query_wasm_smart(
+            contract_address, 
+            &QueryMsg::GetVestingPositions { owner: bob, start_after: None, limit: None }
+        ).unwrap();
+        assert!(res.positions.is_empty());
+    }
+
+
+    #[test]
+    fn counter_offers() {
+        let mut app = mock_app();
+        let contract_address = init_main(&mut app);
+
+        let alice = Addr::unchecked("alice");
+        let bob = Addr::unchecked("bob");
+        let carol = Addr::unchecked("carol");
+        let token = String::from("token1");
+        let token2 = String::from("token2");
+
+        let amount : u128 = 10_000_000;
+
+        mint_native(&mut app, alice.to_string(), token.clone(), amount);
+        mint_native(&mut app, bob.to_string(), token.clone(), amount);
+        mint_native(&mut app, bob.to_string(), token2.clone(), amount);
+        mint_native(&mut app, carol.to_string(), token2.clone(), amount);
+
+        let otc_id = create_new_otc_with_funds(
+            &mut app, 
+            contract_address.clone(), 
+            new_otc_with_nones(native_wrapper(5_000_000, token2.clone())), 
+            &[coin(amount, token.clone())]
+        ).unwrap().id;
+
+        let execute = |app: &mut App, sender: &Addr, msg: ExecuteMsg, funds: &[Coin]| app.execute_contract(
+            sender.clone(),
+            contract_address.clone(),
+            &msg,
+            funds,
+        );
+
+        let propose = |amount: u128| ExecuteMsg::ProposeCounter { otc_id, amount: Uint128::from(amount) };
+
+        let err = execute(&mut app, &bob, propose(5_000_000), &[coin(2_000_000, token.clone())]).unwrap_err();
+        assert_eq!(err.root_cause().to_string(), ContractError::WrongDenom {}.to_string());
+
+        let err = execute(&mut app, &bob, propose(amount + 1), &[coin(2_000_000, token2.clone())]).unwrap_err();
+        assert_eq!(err.root_cause().to_string(), ContractError::ExceedsOffer {}.to_string());
+
+        execute(&mut app, &bob, propose(5_000_000), &[coin(2_000_000, token2.clone())]).unwrap();
+        execute(&mut app, &carol, propose(1_000_000), &[coin(100_000, token2.clone())]).unwrap();
+
+        let res : GetCountersResponse = app.wrap().query_wasm_smart(
+            contract_address.clone(), 
+            &QueryMsg::GetCounters { otc_id, start_after: None, limit: None }
+        ).unwrap();
+        assert_eq!(res.counters.len(), 2);
+
+        // paginated by counter id
+        let res : GetCountersResponse = app.wrap().query_wasm_smart(
+            contract_address.clone(), 
+            &QueryMsg::GetCounters { otc_id, start_after: Some(0), limit: Some(1) }
+        ).unwrap();
+        assert_eq!(res.counters.iter().map(|(id, _)| *id).collect::<Vec<_>>(), vec![1]);
+
+        let res : GetCountersResponse = app.wrap().query_wasm_smart(
+            contract_address.clone(), 
+            &QueryMsg::GetAddressCounters { proposer: bob.clone(), start_after: Some(0), limit: None }
+        ).unwrap();
+        assert!(res.counters.is_empty());
+
+        let res : GetCountersResponse = app.wrap().query_wasm_smart(
+            contract_address.clone(), 
+            &QueryMsg::GetAddressCounters { proposer: carol.clone(), start_after: None, limit: None }
+        ).unwrap();
+        assert_eq!(res.counters.len(), 1);
+        let carol_counter = res.counters[0].0;
+
+        let err = execute(&mut app, &bob, ExecuteMsg::WithdrawCounter { counter_id: carol_counter }, &[]).unwrap_err();
+        assert_eq!(err.root_cause().to_string(), ContractError::Unauthorized {}.to_string());
+
+        execute(&mut app, &carol, ExecuteMsg::WithdrawCounter { counter_id: carol_counter }, &[]).unwrap();
+        let res : GetCountersResponse = app.wrap().query_wasm_smart(
+            contract_address.clone(), 
+            &QueryMsg::GetAddressCounters { proposer: carol.clone(), start_after: None, limit: None }
+        ).unwrap();
+        assert!(res.counters.is_empty());
+
+        let balance = query_native_balance(&app, carol.clone(), token2.clone()).unwrap();
+        assert_eq!(balance.amount, Uint128::from(amount));
+
+        let bob_counter = 0;
+
+        let err = execute(&mut app, &bob, ExecuteMsg::AcceptCounter { counter_id: bob_counter }, &[]).unwrap_err();
+        assert_eq!(err.root_cause().to_string(), ContractError::Unauthorized {}.to_string());
+
+        execute(&mut app, &alice, ExecuteMsg::AcceptCounter { counter_id: bob_counter }, &[]).unwrap();
+
+        let paid = Uint128::from(2_000_000u128);
+        let bought = Uint128::from(5_000_000u128);
+
+        let balance = query_native_balance(&app, alice.clone(), token2.clone()).unwrap();
+        assert_eq!(balance.amount, paid - paid * Decimal::from_ratio(2u8, 10000u16));
+
+        let balance = query_native_balance(&app, bob.clone(), token.clone()).unwrap();
+        assert_eq!(balance.amount, Uint128::from(amount) + bought - bought * Decimal::from_ratio(1u8, 10000u16));
+
+        // the rest of the offer keeps its price
+        let otc : OTCInfo = app.wrap().query_wasm_smart(
+            contract_address.clone(), 
+            &QueryMsg::GetOtc { otc_id }
+        ).unwrap();
+        assert_eq!(otc.sell_amount, bought);
+        assert_eq!(otc.ask_for[0].amount, Uint128::from(2_500_000u128));
+
+        let res : GetCountersResponse = app.wrap().query_wasm_smart(
+            contract_address.clone(), 
+            &QueryMsg::GetCounters { otc_id, start_after: None, limit: None }
+        ).unwrap();
+        assert!(res.counters.is_empty());
+
+        // counters can't be accepted on a new offer reusing the id
+        execute(&mut app, &carol, propose(1_000_000), &[coin(100_000, token2.clone())]).unwrap();
+        execute(&mut app, &alice, ExecuteMsg::Cancel { otc_id }, &[]).unwrap();
+
+        let new_id = create_new_otc_with_funds(
+            &mut app, 
+            contract_address.clone(), 
+            new_otc_with_nones(native_wrapper(5_000_000, token2.clone())), 
+            &[coin(5_000_000, token.clone())]
+        ).unwrap().id;
+        assert_eq!(new_id, otc_id);
+
+        let err = execute(&mut app, &alice, ExecuteMsg::AcceptCounter { counter_id: 2 }, &[]).unwrap_err();
+        assert_eq!(err.root_cause().to_string(), ContractError::StaleCounter {}.to_string());
+    }
+
+
+    #[test]
+    fn fiat_escrow_release_resolve_and_timeout() {
+        let mut app = mock_app();
+
+        let code_id = app.store_code(Box::new(ContractWrapper::new(execute, instantiate, query)));
+        let contract_address = app.instantiate_contract(
+            code_id,
+            Addr::unchecked("owner"),
+            &InstantiateMsg { taker_fee: None, maker_fee: None, keeper_reward: None, arbitrator: Some("arbiter".to_string()) },
+            &[],
+            "Contract",
+            None,
+        ).unwrap();
+
+        let alice = Addr::unchecked("alice");
+        let bob = Addr::unchecked("bob");
+        let arbiter = Addr::unchecked("arbiter");
+        let token = String::from("token1");
+
+        let amount : u128 = 10_000_000;
+        mint_native(&mut app, alice.to_string(), token.clone(), 3 * amount);
+
+        let escrow = |asks: Vec<Balance>| NewOTC {
+            fiat_escrow: Some(NewFiatEscrow { buyer: bob.clone(), payment_window: 100, dispute_window: 200, arbitrator: None }),
+            ..new_otc_with_nones(asks)
+        };
+
+        let err = app.execute_contract(
+            alice.clone(),
+            contract_address.clone(),
+            &ExecuteMsg::Create(escrow(native_wrapper(1, "token2".to_string()))),
+            &[coin(amount, token.clone())],
+        ).unwrap_err();
+        assert_eq!(err.root_cause().to_string(), ContractError::InvalidEscrow {}.to_string());
+
+        // the buyer needs time to pay and the arbitrator to resolve
+        for (payment_window, dispute_window) in [(0, 200), (100, 0)] {
+            let err = app.execute_contract(
+                alice.clone(),
+                contract_address.clone(),
+                &ExecuteMsg::Create(NewOTC {
+                    fiat_escrow: Some(NewFiatEscrow { buyer: bob.clone(), payment_window, dispute_window, arbitrator: None }),
+                    ..new_otc_with_nones(vec![])
+                }),
+                &[coin(amount, token.clone())],
+            ).unwrap_err();
+            assert_eq!(err.root_cause().to_string(), ContractError::InvalidEscrowWindow {}.to_string());
+        }
+
+        let mut ids = vec![];
+        for _ in 0..3 {
+            ids.push(create_new_otc_with_funds(
+                &mut app, contract_address.clone(), escrow(vec![]), &[coin(amount, token.clone())]
+            ).unwrap().id);
+        }
+
+        let execute = |app: &mut App, sender: &Addr, msg: ExecuteMsg| app.execute_contract(
+            sender.clone(),
+            contract_address.clone(),
+            &msg,
+            &[],
+        );
+
+        // paid and released by the seller
+        let err = execute(&mut app, &alice, ExecuteMsg::PaymentSent { otc_id: ids[0] }).unwrap_err();
+        assert_eq!(err.root_cause().to_string(), ContractError::Unauthorized {}.to_string());
+
+        let err = execute(&mut app, &arbiter, ExecuteMsg::Resolve { otc_id: ids[0], split: Decimal::one() }).unwrap_err();
+        assert_eq!(err.root_cause().to_string(), ContractError::WrongEscrowStatus {}.to_string());
+
+        execute(&mut app, &bob, ExecuteMsg::PaymentSent { otc_id: ids[0] }).unwrap();
+        execute(&mut app, &alice, ExecuteMsg::Release { otc_id: ids[0] }).unwrap();
+
+        let fee = Uint128::from(amount) * Decimal::from_ratio(1u8, 10000u16);
+        let balance = query_native_balance(&app, bob.clone(), token.clone()).unwrap();
+        assert_eq!(balance.amount, Uint128::from(amount) - fee);
+
+        // disputed and resolved for the seller
+        execute(&mut app, &bob, ExecuteMsg::PaymentSent { otc_id: ids[1] }).unwrap();
+
+        let err = execute(&mut app, &alice, ExecuteMsg::Cancel { otc_id: ids[1] }).unwrap_err();
+        assert_eq!(err.root_cause().to_string(), ContractError::NotExpired {}.to_string());
+
+        let err = execute(&mut app, &bob, ExecuteMsg::Resolve { otc_id: ids[1], split: Decimal::one() }).unwrap_err();
+        assert_eq!(err.root_cause().to_string(), ContractError::Unauthorized {}.to_string());
+
+        execute(&mut app, &arbiter, ExecuteMsg::Resolve { otc_id: ids[1], split: Decimal::zero() }).unwrap();
+
+        let balance = query_native_balance(&app, alice.clone(), token.clone()).unwrap();
+        assert_eq!(balance.amount, Uint128::from(amount));
+
+        // never paid and refunded after the payment window
+        app.update_block(|block| block.time = block.time.plus_seconds(100));
+
+        let err = execute(&mut app, &bob, ExecuteMsg::PaymentSent { otc_id: ids[2] }).unwrap_err();
+        assert_eq!(err.root_cause().to_string(), ContractError::Expired {}.to_string());
+
+        execute(&mut app, &bob, ExecuteMsg::RemoveExpired {}).unwrap();
+
+        let balance = query_native_balance(&app, alice, token).unwrap();
+        assert_eq!(balance.amount, Uint128::from(2 * amount));
+    }
+
+
+    #[test]
+    fn disputes_resolved_by_chosen_arbitrator() {
+        let mut app = mock_app();
+        let contract_address = init_main(&mut app);
+
+        let owner = Addr::unchecked("owner");
+        let alice = Addr::unchecked("alice");
+        let bob = Addr::unchecked("bob");
+        let judge = Addr::unchecked("judge");
+        let token = String::from("token1");
+
+        let amount : u128 = 10_000_000;
+        mint_native(&mut app, alice.to_string(), token.clone(), amount);
+
+        let execute = |app: &mut App, sender: &Addr, msg: ExecuteMsg, funds: &[Coin]| app.execute_contract(
+            sender.clone(),
+            contract_address.clone(),
+            &msg,
+            funds,
+        );
+
+        let escrow = NewOTC {
+            fiat_escrow: Some(NewFiatEscrow { 
+                buyer: bob.clone(), 
+                payment_window: 100, 
+                dispute_window: 200, 
+                arbitrator: Some(judge.clone()) 
+            }),
+            ..new_otc_with_nones(vec![])
+        };
+
+        let err = execute(&mut app, &alice, ExecuteMsg::Create(escrow.clone()), &[coin(amount, token.clone())]).unwrap_err();
+        assert_eq!(err.root_cause().to_string(), ContractError::NotArbitrator {}.to_string());
+
+        let register = ExecuteMsg::RegisterArbitrator { address: judge.clone(), fee: 100, description: None };
+
+        let err = execute(&mut app, &alice, register.clone(), &[]).unwrap_err();
+        assert_eq!(err.root_cause().to_string(), ContractError::Unauthorized {}.to_string());
+
+        execute(&mut app, &owner, register, &[]).unwrap();
+
+        let res : GetArbitratorsResponse = app.wrap().query_wasm_smart(
+            contract_address.clone(), 
+            &QueryMsg::GetArbitrators { start_after: None, limit: None }
+        ).unwrap();
+        assert_eq!(res.arbitrators.len(), 1);
+        assert_eq!(res.arbitrators[0].0, judge);
+
+        let otc_id = from_binary::<NewOTCResponse>(
+            &execute(&mut app, &alice, ExecuteMsg::Create(escrow), &[coin(amount, token.clone())]).unwrap().data.unwrap()
+        ).unwrap().id;
+
+        execute(&mut app, &bob, ExecuteMsg::Dispute { otc_id }, &[]).unwrap();
+
+        let err = execute(&mut app, &alice, ExecuteMsg::Dispute { otc_id }, &[]).unwrap_err();
+        assert_eq!(err.root_cause().to_string(), ContractError::WrongEscrowStatus {}.to_string());
+
+        // frozen past the payment window
+        app.update_block(|block| block.time = block.time.plus_seconds(1000));
+        execute(&mut app, &bob, ExecuteMsg::RemoveExpired {}, &[]).unwrap();
+
+        let err = execute(&mut app, &alice, ExecuteMsg::Cancel { otc_id }, &[]).unwrap_err();
+        assert_eq!(err.root_cause().to_string(), ContractError::NotExpired {}.to_string());
+
+        let err = execute(&mut app, &judge, ExecuteMsg::Resolve { otc_id, split: Decimal::percent(101) }, &[]).unwrap_err();
+        assert_eq!(err.root_cause().to_string(), ContractError::InvalidSplit {}.to_string());
+
+        execute(&mut app, &judge, ExecuteMsg::Resolve { otc_id, split: Decimal::percent(50) }, &[]).unwrap();
+
+        let fee = Uint128::from(amount / 100);
+        let half = (Uint128::from(amount) - fee) * Decimal::percent(50);
+
+        let balance = query_native_balance(&app, judge, token.clone()).unwrap();
+        assert_eq!(balance.amount, fee);
+
+        let balance = query_native_balance(&app, bob, token.clone()).unwrap();
+        assert_eq!(balance.amount, half - half * Decimal::from_ratio(1u8, 10000u16));
+
+        let balance = query_native_balance(&app, alice, token).unwrap();
+        assert_eq!(balance.amount, half);
+    }
+
+
+
+    #[test]
+    fn disputes_over_private_offers_and_milestones() {
+        let mut app = mock_app();
+        let contract_address = init_main(&mut app);
+
+        let owner = Addr::unchecked("owner");
+        let alice = Addr::unchecked("alice");
+        let bob = Addr::unchecked("bob");
+        let carol = Addr::unchecked("carol");
+        let judge = Addr::unchecked("judge");
+        let token = String::from("token1");
+        let token2 = String::from("token2");
+
+        let amount : u128 = 10_000_000;
+        mint_native(&mut app, alice.to_string(), token.clone(), 2 * amount);
+        mint_native(&mut app, bob.to_string(), token2.clone(), amount);
+
+        let execute = |app: &mut App, sender: &Addr, msg: ExecuteMsg, funds: &[Coin]| app.execute_contract(
+            sender.clone(),
+            contract_address.clone(),
+            &msg,
+            funds,
+        );
+
+        execute(&mut app, &owner, ExecuteMsg::RegisterArbitrator { address: judge.clone(), fee: 100, description: None }, &[]).unwrap();
+
+        let private = |takers: Vec<Addr>| ExecuteMsg::Create(NewOTC {
+            allowed_takers: Some(takers),
+            arbitrator: Some(judge.clone()),
+            ..new_otc_with_nones(native_wrapper(amount, token2.clone()))
+        });
+
+        // the arbitrator needs a single counterparty
+        let err = execute(&mut app, &alice, private(vec![bob.clone(), carol.clone()]), &[coin(amount, token.clone())]).unwrap_err();
+        assert_eq!(err.root_cause().to_string(), ContractError::InvalidArbitration {}.to_string());
+
+        let otc_id = from_binary::<NewOTCResponse>(
+            &execute(&mut app, &alice, private(vec![bob.clone()]), &[coin(amount, token.clone())]).unwrap().data.unwrap()
+        ).unwrap().id;
+
+        let err = execute(&mut app, &judge, ExecuteMsg::Resolve { otc_id, split: Decimal::one() }, &[]).unwrap_err();
+        assert_eq!(err.root_cause().to_string(), ContractError::WrongEscrowStatus {}.to_string());
+
+        let err = execute(&mut app, &carol, ExecuteMsg::Dispute { otc_id }, &[]).unwrap_err();
+        assert_eq!(err.root_cause().to_string(), ContractError::Unauthorized {}.to_string());
+
+        // nothing to dispute before bob filled part of the offer
+        let err = execute(&mut app, &bob, ExecuteMsg::Dispute { otc_id }, &[]).unwrap_err();
+        assert_eq!(err.root_cause().to_string(), ContractError::NotFilled {}.to_string());
+
+        execute(&mut app, &bob, ExecuteMsg::Swap(SwapMsg { otc_id, ..Default::default() }), &[coin(amount / 2, token2.clone())]).unwrap();
+        execute(&mut app, &bob, ExecuteMsg::Dispute { otc_id }, &[]).unwrap();
+
+        // frozen until resolved
+        let err = execute(&mut app, &bob, ExecuteMsg::Swap(SwapMsg { otc_id, ..Default::default() }), &[coin(amount / 2, token2.clone())]).unwrap_err();
+        assert_eq!(err.root_cause().to_string(), ContractError::WrongEscrowStatus {}.to_string());
+
+        let err = execute(&mut app, &alice, ExecuteMsg::Cancel { otc_id }, &[]).unwrap_err();
+        assert_eq!(err.root_cause().to_string(), ContractError::NotExpired {}.to_string());
+
+        // or until the dispute times out and the seller gets refunded
+        app.update_block(|block| block.time = block.time.plus_seconds(DISPUTE_TIMEOUT));
+
+        let err = execute(&mut app, &judge, ExecuteMsg::Resolve { otc_id, split: Decimal::one() }, &[]).unwrap_err();
+        assert_eq!(err.root_cause().to_string(), ContractError::Expired {}.to_string());
+
+        execute(&mut app, &bob, ExecuteMsg::RemoveExpired {}, &[]).unwrap();
+
+        let balance = query_native_balance(&app, alice.clone(), token.clone()).unwrap();
+        assert_eq!(balance.amount, Uint128::from(amount + amount / 2));
+
+        // milestone escrows are disputed by the payee and resolved by their arbitrator
+        let filled = query_native_balance(&app, bob.clone(), token.clone()).unwrap().amount;
+        let expires = Expiration::AtTime(app.block_info().time.plus_seconds(1000));
+        let otc_id = from_binary::<NewOTCResponse>(&execute(&mut app, &alice, ExecuteMsg::Create(NewOTC {
+            expires: Some(expires),
+            milestones: Some(NewMilestones {
+                payee: bob.clone(),
+                milestones: vec![
+                    NewMilestone { amount: Uint128::from(amount / 2), description: None },
+                    NewMilestone { amount: Uint128::from(amount / 2), description: None },
+                ],
+                arbitrator: Some(judge.clone())
+            }),
+            ..new_otc_with_nones(vec![])
+        }), &[coin(amount, token.clone())]).unwrap().data.unwrap()).unwrap().id;
+
+        // the chosen arbitrator can approve milestones as well
+        execute(&mut app, &judge, ExecuteMsg::ApproveMilestone { otc_id, milestone: 0 }, &[]).unwrap();
+        execute(&mut app, &bob, ExecuteMsg::Dispute { otc_id }, &[]).unwrap();
+
+        let err = execute(&mut app, &alice, ExecuteMsg::ApproveMilestone { otc_id, milestone: 1 }, &[]).unwrap_err();
+        assert_eq!(err.root_cause().to_string(), ContractError::WrongEscrowStatus {}.to_string());
+
+        execute(&mut app, &judge, ExecuteMsg::Resolve { otc_id, split: Decimal::one() }, &[]).unwrap();
+
+        // the remainder minus the arbitrator fee
+        let remainder = Uint128::from(amount / 2);
+        let fee = remainder * Decimal::percent(1);
+        assert_eq!(query_native_balance(&app, judge, token.clone()).unwrap().amount, fee);
+
+        let maker_fee = Decimal::from_ratio(1u8, 10000u16);
+        let paid = remainder - remainder * maker_fee + (remainder - fee) - (remainder - fee) * maker_fee;
+        assert_eq!(query_native_balance(&app, bob, token).unwrap().amount, filled + paid);
+    }
+
+
+    #[test]
+    fn prediction_market_with_cw20_stakes() {
+        let mut app = mock_app();
+        let contract_address = init_main(&mut app);
+
+        let alice = Addr::unchecked("alice");
+        let bob = Addr::unchecked("bob");
+        let carol = Addr::unchecked("carol");
+        let dave = Addr::unchecked("dave");
+        let oracle = Addr::unchecked("oracle");
+
+        let amount : u128 = 5_000_000;
+
+        let token = init_cw20(
+            &mut app,
+            String::from("token1"), 
+            String::from("TKN"), 
+            [&bob, &carol, &dave].iter().map(|addr| Cw20Coin {
+                address: addr.to_string(),
+                amount: Uint128::from(amount),
+            }).collect(),
+            String::from("Contract 1"),
+        );
+
+        let deadline = app.block_info().time.seconds() + 100;
+
+        let res = app.execute_contract(
+            alice,
+            contract_address.clone(),
+            &ExecuteMsg::CreateMarket(NewMarket {
+                question: "Will it rain tomorrow?".to_string(),
+                outcomes: vec!["yes".to_string(), "no".to_string()],
+                deadline,
+                resolver: oracle.clone(),
+                min_stake: cw20_wrapper(100, token.clone()).pop().unwrap(),
+            }),
+            &[],
+        ).unwrap();
+        let market_id = from_binary::<NewMarketResponse>(&res.data.unwrap()).unwrap().id;
+
+        let stake = |app: &mut App, staker: &Addr, outcome: u32, amount: u128| app.execute_contract(
+            staker.clone(),
+            token.clone(),
+            &Cw20ExecuteMsg::Send { 
+                contract: contract_address.to_string(), 
+                amount: Uint128::from(amount), 
+                msg: to_binary(&ReceiveMsg::Stake { market_id, outcome }).unwrap()
+            },
+            &[],
+        );
+
+        let err = stake(&mut app, &bob, 2, 1_000).unwrap_err();
+        assert_eq!(err.root_cause().to_string(), ContractError::WrongOutcome {}.to_string());
+
+        stake(&mut app, &bob, 0, 3_000_000).unwrap();
+        stake(&mut app, &carol, 0, 1_000_000).unwrap();
+        stake(&mut app, &dave, 1, 2_000_000).unwrap();
+
+        let execute = |app: &mut App, sender: &Addr, msg: ExecuteMsg| app.execute_contract(
+            sender.clone(),
+            contract_address.clone(),
+            &msg,
+            &[],
+        );
+
+        let err = execute(&mut app, &oracle, ExecuteMsg::ResolveMarket { market_id, outcome: 0 }).unwrap_err();
+        assert_eq!(err.root_cause().to_string(), ContractError::NotExpired {}.to_string());
+
+        app.update_block(|block| block.time = block.time.plus_seconds(100));
+
+        let err = stake(&mut app, &bob, 0, 1_000).unwrap_err();
+        assert_eq!(err.root_cause().to_string(), ContractError::Expired {}.to_string());
+
+        let err = execute(&mut app, &bob, ExecuteMsg::ResolveMarket { market_id, outcome: 0 }).unwrap_err();
+        assert_eq!(err.root_cause().to_string(), ContractError::Unauthorized {}.to_string());
+
+        execute(&mut app, &oracle, ExecuteMsg::ResolveMarket { market_id, outcome: 0 }).unwrap();
+
+        let err = execute(&mut app, &oracle, ExecuteMsg::ResolveMarket { market_id, outcome: 1 }).unwrap_err();
+        assert_eq!(err.root_cause().to_string(), ContractError::Resolved {}.to_string());
+
+        let err = execute(&mut app, &dave, ExecuteMsg::ClaimWinnings { market_id }).unwrap_err();
+        assert_eq!(err.root_cause().to_string(), ContractError::NothingToClaim {}.to_string());
+
+        execute(&mut app, &bob, ExecuteMsg::ClaimWinnings { market_id }).unwrap();
+        execute(&mut app, &carol, ExecuteMsg::ClaimWinnings { market_id }).unwrap();
+
+        // the losing pool is shared 3:1 minus the protocol fee on the winnings
+        let balance = query_wasm_balance(&app, bob, token.clone()).unwrap();
+        assert_eq!(balance.balance, Uint128::from(amount + 1_500_000 - 300));
+
+        let balance = query_wasm_balance(&app, carol, token.clone()).unwrap();
+        assert_eq!(balance.balance, Uint128::from(amount + 500_000 - 100));
+
+        let balance = query_wasm_balance(&app, Addr::unchecked("owner"), token).unwrap();
+        assert_eq!(balance.balance, Uint128::from(400u128));
+    }
+
+
+    #[test]
+    fn milestones_released_and_remainder_refunded() {
+        let mut app = mock_app();
+
+        let code_id = app.store_code(Box::new(ContractWrapper::new(execute, instantiate, query)));
+        let contract_address = app.instantiate_contract(
+            code_id,
+            Addr::unchecked("owner"),
+            &InstantiateMsg { taker_fee: None, maker_fee: None, keeper_reward: None, arbitrator: Some("arbiter".to_string()) },
+            &[],
+            "Contract",
+            None,
+        ).unwrap();
+
+        let alice = Addr::unchecked("alice");
+        let bob = Addr::unchecked("bob");
+        let token = String::from("token1");
+
+        mint_native(&mut app, alice.to_string(), token.clone(), 100_000);
+
+        let expires = Expiration::AtTime(app.block_info().time.plus_seconds(100));
+
+        let milestones = |amounts: Vec<u128>| NewOTC {
+            expires: Some(expires),
+            milestones: Some(NewMilestones {
+                payee: bob.clone(),
+                milestones: amounts
+                    .into_iter()
+                    .map(|amount| NewMilestone { amount: Uint128::from(amount), description: None })
+                    .collect(),
+                arbitrator: None
+            }),
+            ..new_otc_with_nones(vec![])
+        };
+
+        let err = app.execute_contract(
+            alice.clone(),
+            contract_address.clone(),
+            &ExecuteMsg::Create(milestones(vec![30_000, 30_000])),
+            &[coin(100_000, token.clone())],
+        ).unwrap_err();
+        assert_eq!(err.root_cause().to_string(), ContractError::InvalidMilestones {}.to_string());
+
+        // tranches of nothing can't be paid out
+        let err = app.execute_contract(
+            alice.clone(),
+            contract_address.clone(),
+            &ExecuteMsg::Create(milestones(vec![60_000, 0, 40_000])),
+            &[coin(100_000, token.clone())],
+        ).unwrap_err();
+        assert_eq!(err.root_cause().to_string(), ContractError::InvalidMilestones {}.to_string());
+
+        let id = create_new_otc_with_funds(
+            &mut app, contract_address.clone(), milestones(vec![30_000, 30_000, 40_000]), &[coin(100_000, token.clone())]
+        ).unwrap().id;
+
+        let execute = |app: &mut App, sender: &Addr, msg: ExecuteMsg| app.execute_contract(
+            sender.clone(),
+            contract_address.clone(),
+            &msg,
+            &[],
+        );
+
+        let err = execute(&mut app, &bob, ExecuteMsg::ApproveMilestone { otc_id: id, milestone: 0 }).unwrap_err();
+        assert_eq!(err.root_cause().to_string(), ContractError::Unauthorized {}.to_string());
+
+        // the default arbitrator only approves escrows that chose it
+        let err = execute(&mut app, &Addr::unchecked("arbiter"), ExecuteMsg::ApproveMilestone { otc_id: id, milestone: 0 }).unwrap_err();
+        assert_eq!(err.root_cause().to_string(), ContractError::Unauthorized {}.to_string());
+
+        execute(&mut app, &alice, ExecuteMsg::ApproveMilestone { otc_id: id, milestone: 1 }).unwrap();
+
+        let err = execute(&mut app, &alice, ExecuteMsg::ApproveMilestone { otc_id: id, milestone: 1 }).unwrap_err();
+        assert_eq!(err.root_cause().to_string(), ContractError::WrongEscrowStatus {}.to_string());
+
+        let err = execute(&mut app, &alice, ExecuteMsg::Cancel { otc_id: id }).unwrap_err();
+        assert_eq!(err.root_cause().to_string(), ContractError::NotExpired {}.to_string());
+
+        let res : GetMilestonesResponse = app.wrap()
+            .query_wasm_smart(contract_address.clone(), &QueryMsg::GetMilestones { otc_id: id })
+            .unwrap();
+        assert_eq!(res.released, Uint128::from(30_000u128));
+        assert_eq!(res.remaining, Uint128::from(70_000u128));
+        assert!(res.milestones[1].released);
+
+        // minus the maker fee
+        let balance = query_native_balance(&app, bob.clone(), token.clone()).unwrap();
+        assert_eq!(balance.amount, Uint128::from(29_997u128));
+
+        // the unapproved milestones go back to the seller after expiry
+        app.update_block(|block| block.time = block.time.plus_seconds(100));
+
+        let err = execute(&mut app, &alice, ExecuteMsg::ApproveMilestone { otc_id: id, milestone: 0 }).unwrap_err();
+        assert_eq!(err.root_cause().to_string(), ContractError::Expired {}.to_string());
+
+        execute(&mut app, &alice, ExecuteMsg::Cancel { otc_id: id }).unwrap();
+
+        let balance = query_native_balance(&app, alice, token).unwrap();
+        assert_eq!(balance.amount, Uint128::from(70_000u128));
+    }
+
+
+    #[test]
+    fn ibc_hook_create_and_swap_route_outputs_back() {
+        let mut app = ibc_app();
+
+        let code_id = app.store_code(Box::new(ContractWrapper::new(execute_with_ibc_transfers, instantiate, query).with_reply(reply)));
+        let contract_address = app.instantiate_contract(
+            code_id,
+            Addr::unchecked("owner"),
+            &InstantiateMsg { taker_fee: None, maker_fee: None, keeper_reward: None, arbitrator: None },
+            &[],
+            "Contract",
+            None,
+        ).unwrap();
+
+        // ibc-hooks calls the contract from intermediary addresses of the remote senders
+        let alice = Addr::unchecked(hex::encode(hook_intermediary("channel-0", "cosmos1alice").as_slice()));
+        let bob = Addr::unchecked(hex::encode(hook_intermediary("channel-1", "juno1bob").as_slice()));
+        // other 32 bytes accounts like interchain accounts aren't intermediaries
+        let carol = Addr::unchecked(hex::encode(sha256(b"interchain account")));
+        let voucher = String::from("ibc/27394FB092D2ECCD56123C74F36E4C1F926001CEADA9CA97EA622B25F41E5EB2");
+        let token = String::from("uconst");
+
+        app.sudo(cw_multi_test::SudoMsg::Bank(cw_multi_test::BankSudo::Mint {
+            to_address: alice.to_string(),
+            amount: vec![coin(30_000_000, voucher.clone())],
+        })).unwrap();
+        app.sudo(cw_multi_test::SudoMsg::Bank(cw_multi_test::BankSudo::Mint {
+            to_address: bob.to_string(),
+            amount: vec![coin(5_000_000, token.clone())],
+        })).unwrap();
+        app.sudo(cw_multi_test::SudoMsg::Bank(cw_multi_test::BankSudo::Mint {
+            to_address: carol.to_string(),
+            amount: vec![coin(5_000_000, token.clone())],
+        })).unwrap();
+
+        let remote = |channel: &str, address: &str| Some(IbcRecipient { 
+            channel: channel.to_string(), 
+            address: address.to_string(), 
+            timeout: 600 
+        });
+
+        let err = app.execute_contract(
+            alice.clone(),
+            contract_address.clone(),
+            &ExecuteMsg::Create(NewOTC { 
+                ibc_recipient: remote("channel-0", "cosmos1alice"), 
+                ..new_otc_with_nones(cw20_wrapper(5_000_000, Addr::unchecked("cw20"))) 
+            }),
+            &[coin(10_000_000, voucher.clone())],
+        ).unwrap_err();
+        assert_eq!(err.root_cause().to_string(), ContractError::InvalidIbcRecipient {}.to_string());
+
+        let res = app.execute_contract(
+            alice.clone(),
+            contract_address.clone(),
+            &ExecuteMsg::Create(NewOTC { 
+                ibc_recipient: remote("channel-0", "cosmos1alice"), 
+                ..new_otc_with_nones(native_wrapper(5_000_000, token.clone())) 
+            }),
+            &[coin(10_000_000, voucher.clone())],
+        ).unwrap();
+        let otc : NewOTCResponse = from_binary(&res.data.unwrap()).unwrap();
+
+        let res = app.execute_contract(
+            bob.clone(),
+            contract_address.clone(),
+            &ExecuteMsg::Swap(SwapMsg { 
+                otc_id: otc.id, 
+                ibc_recipient: remote("channel-1", "juno1bob"), 
+                ..Default::default() 
+            }),
+            &[coin(5_000_000, token.clone())],
+        ).unwrap();
+
+        // minus the taker and maker fees
+        assert_eq!(ibc_transfers(&res), vec![
+            ("channel-0".to_string(), "cosmos1alice".to_string(), format!("4999000{}", token)),
+            ("channel-1".to_string(), "juno1bob".to_string(), format!("9999000{}", voucher)),
+        ]);
+        assert_eq!(app.wrap().query_balance(&bob, voucher.clone()).unwrap().amount, Uint128::zero());
+
+        // both are refunded over IBC if their transfers fail
+        let pending = IBC_TRANSFERS.query(&app.wrap(), contract_address.clone(), ("channel-0", 1)).unwrap().unwrap();
+        assert_eq!(pending.refund_recipient, remote("channel-0", "cosmos1alice"));
+        let pending = IBC_TRANSFERS.query(&app.wrap(), contract_address.clone(), ("channel-1", 2)).unwrap().unwrap();
+        assert_eq!(pending.refund_recipient, remote("channel-1", "juno1bob"));
+
+        // while accounts the recipient doesn't derive are refunded on this chain
+        let res = app.execute_contract(
+            alice.clone(),
+            contract_address.clone(),
+            &ExecuteMsg::Create(NewOTC { 
+                ibc_recipient: remote("channel-0", "cosmos1alice"), 
+                ..new_otc_with_nones(native_wrapper(5_000_000, token.clone())) 
+            }),
+            &[coin(10_000_000, voucher.clone())],
+        ).unwrap();
+        let otc : NewOTCResponse = from_binary(&res.data.unwrap()).unwrap();
+
+        app.execute_contract(
+            carol.clone(),
+            contract_address.clone(),
+            &ExecuteMsg::Swap(SwapMsg { 
+                otc_id: otc.id, 
+                ibc_recipient: remote("channel-1", "juno1carol"), 
+                ..Default::default() 
+            }),
+            &[coin(5_000_000, token.clone())],
+        ).unwrap();
+
+        let pending = IBC_TRANSFERS.query(&app.wrap(), contract_address.clone(), ("channel-1", 4)).unwrap().unwrap();
+        assert_eq!((pending.refund_address, pending.refund_recipient), (carol, None));
+
+        // refunds of cancelled offers go back over IBC as well
+        let res = app.execute_contract(
+            alice.clone(),
+            contract_address.clone(),
+            &ExecuteMsg::Create(NewOTC { 
+                ibc_recipient: remote("channel-0", "cosmos1alice"), 
+                ..new_otc_with_nones(native_wrapper(5_000_000, token.clone())) 
+            }),
+            &[coin(10_000_000, voucher.clone())],
+        ).unwrap();
+        let otc : NewOTCResponse = from_binary(&res.data.unwrap()).unwrap();
+
+        let res = app.execute_contract(
+            alice.clone(),
+            contract_address.clone(),
+            &ExecuteMsg::Cancel { otc_id: otc.id },
+            &[],
+        ).unwrap();
+
+        assert_eq!(ibc_transfers(&res), vec![
+            ("channel-0".to_string(), "cosmos1alice".to_string(), format!("10000000{}", voucher)),
+        ]);
+        assert_eq!(app.wrap().query_balance(&contract_address, voucher).unwrap().amount, Uint128::zero());
+    }
+
+
+    #[test]
+    fn allowance_offers_pull_tokens_until_revoked() {
+        let mut app = mock_app();
+        let contract_address = init_main(&mut app);
+
+        let alice = Addr::unchecked("alice");
+        let bob = Addr::unchecked("bob");
+        let token = String::from("token1");
+
+        let cw20 = init_cw20(
+            &mut app, 
+            "Treasury".to_string(), 
+            "TRSY".to_string(), 
+            vec![Cw20Coin { address: alice.to_string(), amount: Uint128::from(1_000_000u128) }], 
+            "cw20".to_string()
+        );
+        mint_native(&mut app, bob.to_string(), token.clone(), 1_500_000);
+
+        app.execute_contract(alice.clone(), cw20.clone(), &Cw20ExecuteMsg::IncreaseAllowance { 
+            spender: contract_address.to_string(), 
+            amount: Uint128::from(500_000u128), 
+            expires: None 
+        }, &[]).unwrap();
+
+        let offer = |amount: u128| ExecuteMsg::Create(NewOTC {
+            allowance: Some(Cw20Coin { address: cw20.to_string(), amount: Uint128::from(amount) }),
+            ..new_otc_with_nones(native_wrapper(1_000_000, token.clone()))
+        });
+
+        let err = app.execute_contract(alice.clone(), contract_address.clone(), &offer(600_000), &[]).unwrap_err();
+        assert_eq!(err.root_cause().to_string(), ContractError::InsufficientAllowance {}.to_string());
+
+        let res = app.execute_contract(alice.clone(), contract_address.clone(), &offer(500_000), &[]).unwrap();
+        let otc : NewOTCResponse = from_binary(&res.data.unwrap()).unwrap();
+
+        // the tokens stay with the seller until filled
+        let balance = query_wasm_balance(&app, contract_address.clone(), cw20.clone()).unwrap();
+        assert_eq!(balance.balance, Uint128::zero());
+
+        let swap = |app: &mut App| app.execute_contract(
+            bob.clone(), 
+            contract_address.clone(), 
+            &ExecuteMsg::Swap(SwapMsg { otc_id: otc.id, ..Default::default() }), 
+            &[coin(500_000, token.clone())]
+        );
+
+        swap(&mut app).unwrap();
+
+        // half of the offer minus the maker fee
+        let balance = query_wasm_balance(&app, bob.clone(), cw20.clone()).unwrap();
+        assert_eq!(balance.balance, Uint128::from(249_975u128));
+        let balance = query_wasm_balance(&app, alice.clone(), cw20.clone()).unwrap();
+        assert_eq!(balance.balance, Uint128::from(750_000u128));
+
+        // revoking invalidates the offer on the next fill and refunds the taker
+        app.execute_contract(alice.clone(), cw20.clone(), &Cw20ExecuteMsg::DecreaseAllowance { 
+            spender: contract_address.to_string(), 
+            amount: Uint128::from(250_000u128), 
+            expires: None 
+        }, &[]).unwrap();
+
+        let res = swap(&mut app).unwrap();
+        let event = res.events.iter().find(|event| event.ty == "wasm-peerswap_offer_invalidated").unwrap();
+        assert_eq!(event.attributes[2].value, ContractError::AllowanceRevoked {}.to_string());
+        assert_eq!(query_native_balance(&app, bob.clone(), token.clone()).unwrap().amount, Uint128::from(1_000_000u128));
+
+        let err = swap(&mut app).unwrap_err();
+        assert_eq!(err.root_cause().to_string(), ContractError::AllowanceRevoked {}.to_string());
+
+        // nothing to refund on cancel
+        app.execute_contract(alice.clone(), contract_address.clone(), &ExecuteMsg::Cancel { otc_id: otc.id }, &[]).unwrap();
+        let balance = query_wasm_balance(&app, alice, cw20).unwrap();
+        assert_eq!(balance.balance, Uint128::from(750_000u128));
+    }
+
+
+    #[test]
+    fn signed_orders_filled_once_from_allowance() {
+        use k256::ecdsa::{SigningKey, Signature, signature::Signer};
+
+        let mut app = mock_app();
+        let contract_address = init_main(&mut app);
+
+        let alice = Addr::unchecked("alice");
+        let bob = Addr::unchecked("bob");
+        let token = String::from("token1");
+
+        let cw20 = init_cw20(
+            &mut app, 
+            "Treasury".to_string(), 
+            "TRSY".to_string(), 
+            vec![Cw20Coin { address: alice.to_string(), amount: Uint128::from(1_000_000u128) }], 
+            "cw20".to_string()
+        );
+        mint_native(&mut app, bob.to_string(), token.clone(), 100_000);
+
+        app.execute_contract(alice.clone(), cw20.clone(), &Cw20ExecuteMsg::IncreaseAllowance { 
+            spender: contract_address.to_string(), 
+            amount: Uint128::from(1_000_000u128), 
+            expires: None 
+        }, &[]).unwrap();
+
+        let key = SigningKey::from_bytes(&[7u8; 32]).unwrap();
+        let pubkey = Binary::from(key.verifying_key().to_bytes().as_slice());
+
+        app.execute_contract(alice.clone(), contract_address.clone(), &ExecuteMsg::SetOrderKey { pubkey }, &[]).unwrap();
+
+        let chain_id = app.block_info().chain_id;
+        let order = |nonce: u64| SignedOrder {
+            maker: alice.to_string(),
+            sell: Cw20Coin { address: cw20.to_string(), amount: Uint128::from(100_000u128) },
+            ask: Balance::from(vec![coin(50_000, token.clone())]),
+            nonce,
+            expires: Expiration::Never {},
+            taker: None,
+            chain_id: chain_id.clone(),
+            contract: contract_address.to_string(),
+        };
+        let sign = |key: &SigningKey, order: &SignedOrder| {
+            let signature : Signature = key.sign(&to_binary(order).unwrap());
+            Binary::from(signature.as_ref())
+        };
+        let fill = |app: &mut App, order: &SignedOrder, signature: Binary| app.execute_contract(
+            bob.clone(), 
+            contract_address.clone(), 
+            &ExecuteMsg::FillSignedOrder { order: order.clone(), signature }, 
+            &[coin(50_000, token.clone())]
+        );
+
+        let first = order(1);
+
+        let forged = sign(&SigningKey::from_bytes(&[8u8; 32]).unwrap(), &first);
+        let err = fill(&mut app, &first, forged).unwrap_err();
+        assert_eq!(err.root_cause().to_string(), ContractError::InvalidSignature {}.to_string());
+
+        let tampered = SignedOrder { sell: Cw20Coin { amount: Uint128::from(200_000u128), ..first.sell.clone() }, ..first.clone() };
+        let err = fill(&mut app, &tampered, sign(&key, &first)).unwrap_err();
+        assert_eq!(err.root_cause().to_string(), ContractError::InvalidSignature {}.to_string());
+
+        fill(&mut app, &first, sign(&key, &first)).unwrap();
+
+        // minus the maker and taker fees
+        let balance = query_wasm_balance(&app, bob.clone(), cw20.clone()).unwrap();
+        assert_eq!(balance.balance, Uint128::from(99_990u128));
+        assert_eq!(query_native_balance(&app, alice.clone(), token.clone()).unwrap().amount, Uint128::from(49_990u128));
+
+        mint_native(&mut app, bob.to_string(), token.clone(), 100_000);
+
+        let err = fill(&mut app, &first, sign(&key, &first)).unwrap_err();
+        assert_eq!(err.root_cause().to_string(), ContractError::NonceUsed {}.to_string());
+
+        // cancelled before being filled
+        let second = order(2);
+        app.execute_contract(alice.clone(), contract_address.clone(), &ExecuteMsg::CancelSignedOrder { nonce: 2 }, &[]).unwrap();
+
+        let err = fill(&mut app, &second, sign(&key, &second)).unwrap_err();
+        assert_eq!(err.root_cause().to_string(), ContractError::NonceUsed {}.to_string());
+
+        let status : GetOrderStatusResponse = app.wrap().query_wasm_smart(
+            contract_address.clone(), 
+            &QueryMsg::GetOrderStatus { maker: alice.to_string(), nonce: 2 }
+        ).unwrap();
+        assert_eq!(status.status, Some(OrderStatus::Cancelled));
+
+        // the allowance no longer covers the order
+        app.execute_contract(alice.clone(), cw20.clone(), &Cw20ExecuteMsg::DecreaseAllowance { 
+            spender: contract_address.to_string(), 
+            amount: Uint128::from(950_000u128), 
+            expires: None 
+        }, &[]).unwrap();
+
+        let third = order(3);
+        let err = fill(&mut app, &third, sign(&key, &third)).unwrap_err();
+        assert_eq!(err.root_cause().to_string(), ContractError::InsufficientAllowance {}.to_string());
+    }
+
+
+    #[test]
+    fn cw1155_tokens_sold_and_asked_for() {
+        let mut app = mock_app();
+        let contract_address = init_main(&mut app);
+        let tickets = init_cw1155(&mut app);
+
+        let alice = Addr::unchecked("alice");
+        let bob = Addr::unchecked("bob");
+        let carol = Addr::unchecked("carol");
+        let token = String::from("token1");
+
+        app.execute_contract(Addr::unchecked("owner"), tickets.clone(), &MockCw1155Msg::Mint { 
+            to: alice.to_string(), 
+            token_id: "vip".to_string(), 
+            value: Uint128::from(10u128) 
+        }, &[]).unwrap();
+        mint_native(&mut app, bob.to_string(), token.clone(), 500_000);
+        mint_native(&mut app, carol.to_string(), token.clone(), 1_000_000);
+
+        let send = |app: &mut App, sender: &Addr, value: u128, msg: &ReceiveMsg| app.execute_contract(
+            sender.clone(), 
+            tickets.clone(), 
+            &MockCw1155Msg::SendFrom { 
+                from: sender.to_string(), 
+                to: contract_address.to_string(), 
+                token_id: "vip".to_string(), 
+                value: Uint128::from(value), 
+                msg: Some(to_binary(msg).unwrap()) 
+            }, 
+            &[]
+        );
+
+        // only plain offers
+        let vested = ReceiveMsg::Create(NewOTC {
+            vesting: Some(Vesting { cliff: 0, duration: 100 }),
+            ..new_otc_with_nones(native_wrapper(1_000_000, token.clone()))
+        });
+        let err = send(&mut app, &alice, 10, &vested).unwrap_err();
+        assert_eq!(err.root_cause().to_string(), ContractError::InvalidCw1155Offer {}.to_string());
+
+        let res = send(&mut app, &alice, 10, &ReceiveMsg::Create(new_otc_with_nones(native_wrapper(1_000_000, token.clone())))).unwrap();
+        let event = res.events.iter().find(|event| event.ty == "wasm-peerswap_otc_created").unwrap();
+        assert_eq!(event.attributes[4].value, format!("cw1155:{}:vip", tickets));
+
+        let otcs = query_otcs(&app, contract_address.clone()).unwrap();
+        let otc_id = otcs.otcs[0].0;
+        assert_eq!(otcs.otcs[0].1.sell, AssetInfo::Cw1155 { address: tickets.clone(), token_id: "vip".to_string() });
+
+        app.execute_contract(
+            bob.clone(), 
+            contract_address.clone(), 
+            &ExecuteMsg::Swap(SwapMsg { otc_id, ..Default::default() }), 
+            &[coin(500_000, token.clone())]
+        ).unwrap();
+        assert_eq!(query_cw1155_balance(&app, &bob, &tickets, "vip"), Uint128::from(5u128));
+
+        // tickets as the ask of a native offer
+        let res = app.execute_contract(
+            carol.clone(), 
+            contract_address.clone(), 
+            &ExecuteMsg::Create(NewOTC {
+                ask_cw1155: Some(vec![Cw1155Coin { address: tickets.to_string(), token_id: "vip".to_string(), amount: Uint128::from(4u128) }]),
+                ..new_otc_with_nones(vec![])
+            }), 
+            &[coin(1_000_000, token.clone())]
+        ).unwrap();
+        let carol_otc : NewOTCResponse = from_binary(&res.data.unwrap()).unwrap();
+
+        send(&mut app, &bob, 4, &ReceiveMsg::Swap(SwapMsg { otc_id: carol_otc.id, ..Default::default() })).unwrap();
+        assert_eq!(query_cw1155_balance(&app, &carol, &tickets, "vip"), Uint128::from(4u128));
+        assert_eq!(query_native_balance(&app, bob.clone(), token.clone()).unwrap().amount, Uint128::from(999_900u128));
+
+        app.execute_contract(alice.clone(), contract_address.clone(), &ExecuteMsg::Cancel { otc_id }, &[]).unwrap();
+        assert_eq!(query_cw1155_balance(&app, &alice, &tickets, "vip"), Uint128::from(5u128));
+        assert_eq!(query_cw1155_balance(&app, &contract_address, &tickets, "vip"), Uint128::zero());
+    }
+
+
+
+    #[test]
+    fn cw1155_partial_fills_round_down() {
+        let mut app = mock_app();
+        let contract_address = init_main(&mut app);
+        let tickets = init_cw1155(&mut app);
+
+        let alice = Addr::unchecked("alice");
+        let bob = Addr::unchecked("bob");
+        let token = String::from("token1");
+
+        app.execute_contract(Addr::unchecked("owner"), tickets.clone(), &MockCw1155Msg::Mint { 
+            to: alice.to_string(), 
+            token_id: "vip".to_string(), 
+            value: Uint128::from(10u128) 
+        }, &[]).unwrap();
+        mint_native(&mut app, bob.to_string(), token.clone(), 1_000_000);
+
+        // 100_000 per ticket
+        app.execute_contract(
+            alice.clone(), 
+            tickets.clone(), 
+            &MockCw1155Msg::SendFrom { 
+                from: alice.to_string(), 
+                to: contract_address.to_string(), 
+                token_id: "vip".to_string(), 
+                value: Uint128::from(10u128), 
+                msg: Some(to_binary(&ReceiveMsg::Create(new_otc_with_nones(native_wrapper(1_000_000, token.clone())))).unwrap()) 
+            }, 
+            &[]
+        ).unwrap();
+        let otc_id = query_otcs(&app, contract_address.clone()).unwrap().otcs[0].0;
+
+        let swap = |app: &mut App, paid: u128| app.execute_contract(
+            bob.clone(), 
+            contract_address.clone(), 
+            &ExecuteMsg::Swap(SwapMsg { otc_id, ..Default::default() }), 
+            &[coin(paid, token.clone())]
+        );
+
+        // less than a whole ticket releases nothing
+        let err = swap(&mut app, 99_999).unwrap_err();
+        assert_eq!(err.root_cause().to_string(), ContractError::TooSmall {}.to_string());
+
+        // a ticket and a half only buys one
+        for _ in 0..3 {
+            swap(&mut app, 150_000).unwrap();
+        }
+        assert_eq!(query_cw1155_balance(&app, &bob, &tickets, "vip"), Uint128::from(3u128));
+
+        // the remaining tickets keep their price
+        let otc : OTCInfo = app.wrap().query_wasm_smart(contract_address.clone(), &QueryMsg::GetOtc { otc_id }).unwrap();
+        assert_eq!(otc.sell_amount, Uint128::from(7u128));
+        assert_eq!(otc.ask_for[0].amount, Uint128::from(700_000u128));
+
+        // a single ticket buys too little for the fees to round above zero
+        mint_native(&mut app, alice.to_string(), token.clone(), 10_000);
+        let res = app.execute_contract(
+            alice.clone(), 
+            contract_address.clone(), 
+            &ExecuteMsg::Create(NewOTC {
+                ask_cw1155: Some(vec![Cw1155Coin { address: tickets.to_string(), token_id: "vip".to_string(), amount: Uint128::from(10u128) }]),
+                ..new_otc_with_nones(vec![])
+            }), 
+            &[coin(10_000, token.clone())]
+        ).unwrap();
+        let otc : NewOTCResponse = from_binary(&res.data.unwrap()).unwrap();
+
+        app.execute_contract(
+            bob.clone(), 
+            tickets.clone(), 
+            &MockCw1155Msg::SendFrom { 
+                from: bob.to_string(), 
+                to: contract_address.to_string(), 
+                token_id: "vip".to_string(), 
+                value: Uint128::one(), 
+                msg: Some(to_binary(&ReceiveMsg::Swap(SwapMsg { otc_id: otc.id, ..Default::default() })).unwrap()) 
+            }, 
+            &[]
+        ).unwrap();
+        assert_eq!(query_cw1155_balance(&app, &alice, &tickets, "vip"), Uint128::one());
+        assert_eq!(query_native_balance(&app, bob.clone(), token.clone()).unwrap().amount, Uint128::from(1_000u128 + 550_000));
+    }
+
+
+
+    #[test]
+    fn offers_resolve_token_metadata() {
+        let mut app = mock_app();
+        let contract_address = init_main(&mut app);
+
+        let alice = Addr::unchecked("alice");
+        let token = String::from("ibc/27394FB092D2ECCD56123C74F36E4C1F926001CEADA9CA97EA622B25F41E5EB2");
+
+        let cw20 = init_cw20(&mut app, "Peer Token".to_string(), "PEER".to_string(), vec![], "peer".to_string());
+        mint_native(&mut app, alice.to_string(), token.clone(), 1_010_000);
+
+        let atom = TokenMetadata {
+            name: "Cosmos Hub Atom".to_string(),
+            symbol: "ATOM".to_string(),
+            decimals: 6,
+            logo: Some("https://example.com/atom.svg".to_string()),
+            verified: true,
+        };
+
+        let set_atom = ExecuteMsg::SetTokenMetadata { 
+            asset: AssetInfo::Native(token.clone()), 
+            name: atom.name.clone(), 
+            symbol: atom.symbol.clone(), 
+            decimals: atom.decimals, 
+            logo: atom.logo.clone() 
+        };
+        let err = app.execute_contract(alice.clone(), contract_address.clone(), &set_atom, &[]).unwrap_err();
+        assert_eq!(err.root_cause().to_string(), ContractError::Unauthorized {}.to_string());
+        app.execute_contract(Addr::unchecked("owner"), contract_address.clone(), &set_atom, &[]).unwrap();
+
+        let res = create_new_otc_with_funds(
+            &mut app, 
+            contract_address.clone(), 
+            new_otc_with_nones(cw20_wrapper(2_000_000, cw20.clone())), 
+            &[coin(1_000_000, token.clone())]
+        ).unwrap();
+
+        let peer = TokenMetadata {
+            name: "Peer Token".to_string(),
+            symbol: "PEER".to_string(),
+            decimals: 6,
+            logo: None,
+            verified: false,
+        };
+
+        let otc : GetOTCResponse = app.wrap().query_wasm_smart(
+            contract_address.clone(), 
+            &QueryMsg::GetOtc { otc_id: res.id }
+        ).unwrap();
+        assert_eq!(otc.otc.sell, AssetInfo::Native(token.clone()));
+        assert_eq!(otc.metadata, vec![
+            (AssetInfo::Native(token.clone()), atom.clone()),
+            (AssetInfo::Cw20(cw20.clone()), peer.clone()),
+        ]);
+
+        // shared by the listed offers
+        create_new_otc_with_funds(
+            &mut app, 
+            contract_address.clone(), 
+            new_otc_with_nones(cw20_wrapper(500_000, cw20.clone())), 
+            &[coin(10_000, token.clone())]
+        ).unwrap();
+
+        let otcs = query_otcs(&app, contract_address.clone()).unwrap();
+        assert_eq!(otcs.otcs.len(), 2);
+        assert_eq!(otcs.metadata, vec![
+            (AssetInfo::Native(token.clone()), atom),
+            (AssetInfo::Cw20(cw20.clone()), peer),
+        ]);
+
+        app.execute_contract(
+            Addr::unchecked("owner"), 
+            contract_address.clone(), 
+            &ExecuteMsg::RemoveTokenMetadata { asset: AssetInfo::Native(token.clone()) }, 
+            &[]
+        ).unwrap();
+
+        let otc : GetOTCResponse = app.wrap().query_wasm_smart(
+            contract_address, 
+            &QueryMsg::GetOtc { otc_id: res.id }
+        ).unwrap();
+        assert_eq!(otc.metadata.len(), 1);
+        assert_eq!(otc.metadata[0].0, AssetInfo::Cw20(cw20));
+    }
+
+
+
+    #[test]
+    fn cw20_asks_validated_at_creation() {
+        let mut app = mock_app();
+        let contract_address = init_main(&mut app);
+        let tickets = init_cw1155(&mut app);
+        let cw20 = init_cw20(&mut app, "Peer Token".to_string(), "PEER".to_string(), vec![], "peer".to_string());
+
+        let alice = Addr::unchecked("alice");
+        let token = String::from("token1");
+        mint_native(&mut app, alice.to_string(), token.clone(), 1_000_000);
+
+        let create = |app: &mut App, ask: Addr| app.execute_contract(
+            alice.clone(), 
+            contract_address.clone(), 
+            &ExecuteMsg::Create(new_otc_with_nones(cw20_wrapper(1_000_000, ask))), 
+            &[coin(1_000_000, token.clone())]
+        );
+
+        // neither a valid address, a contract nor a cw20
+        for ask in [Addr::unchecked("NOT A CW20"), Addr::unchecked("carol"), tickets] {
+            let err = create(&mut app, ask.clone()).unwrap_err();
+            assert_eq!(err.root_cause().to_string(), ContractError::InvalidToken { address: ask.to_string() }.to_string());
+        }
+
+        let code_id = app.wrap().query_wasm_contract_info(cw20.clone()).unwrap().code_id;
+
+        let set_code_ids = |app: &mut App, code_ids: Vec<u64>| app.execute_contract(
+            Addr::unchecked("owner"), 
+            contract_address.clone(), 
+            &ExecuteMsg::SetCw20CodeIds { code_ids }, 
+            &[]
+        ).unwrap();
+
+        set_code_ids(&mut app, vec![code_id + 100]);
+        let err = create(&mut app, cw20.clone()).unwrap_err();
+        assert_eq!(err.root_cause().to_string(), ContractError::InvalidToken { address: cw20.to_string() }.to_string());
+
+        set_code_ids(&mut app, vec![code_id]);
+        create(&mut app, cw20.clone()).unwrap();
+
+        let config : GetConfigResponse = app.wrap().query_wasm_smart(contract_address.clone(), &QueryMsg::Config {}).unwrap();
+        assert_eq!(config.cw20_code_ids, vec![code_id]);
+        assert_eq!(query_otcs(&app, contract_address).unwrap().otcs[0].1.ask_for[0].info, AssetInfo::Cw20(cw20));
+    }
+}
@@ -0,0 +1,336 @@
+use cosmwasm_std::{
+    Deps, DepsMut, Env, Response, StdResult, Event, Addr, Decimal, CosmosMsg,
+    to_binary
+};
+use cw20::Balance;
+
+use crate::asset::Asset;
+use crate::contract::parse_sell_balance;
+use crate::error::ContractError;
+use crate::msg::{NewRaise, NewRaiseResponse, GetContributionResponse};
+use crate::registry::ensure_allowed;
+use crate::state::{STATE, RAISES, RAISE_INDEX, CONTRIBUTIONS, RaiseInfo};
+
+
+
+pub fn try_create_raise(
+    deps: DepsMut,
+    env: Env,
+    creator: &Addr,
+    sell_balance: Balance,
+    msg: NewRaise,
+    ) -> Result<Response, ContractError> {
+
+    let config = STATE.load(deps.storage)?;
+
+    if !config.active {
+        return Err(ContractError::Stopped {});
+    }
+
+    if msg.deadline <= env.block.time.seconds() {
+        return Err(ContractError::Expired {});
+    }
+
+    let sell = parse_sell_balance(sell_balance)?;
+    let soft_cap = Asset::from_balance(msg.soft_cap, None)
+        .map_err(|_| ContractError::NoAskTokens {})?;
+
+    if sell.info == soft_cap.info {
+        return Err(ContractError::SameToken {});
+    }
+
+    if soft_cap.amount.is_zero() || msg.hard_cap < soft_cap.amount {
+        return Err(ContractError::InvalidCaps {});
+    }
+
+    let raised = soft_cap.info.validate(deps.api)?;
+
+    ensure_allowed(deps.storage, &sell.info)?;
+    ensure_allowed(deps.storage, &raised)?;
+
+    let raise = RaiseInfo {
+        creator: deps.api.addr_canonicalize(creator.as_str())?,
+        sell: sell.info,
+        sell_amount: sell.amount,
+        raise: raised,
+        soft_cap: soft_cap.amount,
+        hard_cap: msg.hard_cap,
+        raised: Default::default(),
+        deadline: msg.deadline,
+        finalized: false,
+        description: msg.description,
+    };
+
+    let id = RAISE_INDEX.may_load(deps.storage)?.unwrap_or_default();
+
+    RAISES.save(deps.storage, id, &raise)?;
+    RAISE_INDEX.save(deps.storage, &(id + 1))?;
+
+    let data = NewRaiseResponse {
+        id,
+        raise: raise.clone()
+    };
+
+    Ok(Response::new()
+        .set_data(to_binary(&data)?)
+        .add_event(
+            Event::new("peerswap_raise_created")
+            .add_attributes(vec![
+                ("raise_id", id.to_string()),
+                ("creator", creator.to_string()),
+                ("amount", raise.sell_amount.to_string()),
+                ("token", raise.sell.token()),
+                ("raise_token", raise.raise.token()),
+                ("soft_cap", raise.soft_cap.to_string()),
+                ("hard_cap", raise.hard_cap.to_string()),
+                ("deadline", raise.deadline.to_string()),
+                ("method", "create_raise".to_string())
+            ])
+        )
+    )
+}
+
+
+
+pub fn try_contribute(
+    deps: DepsMut,
+    env: Env,
+    contributor: &Addr,
+    raise_id: u32,
+    balance: Balance,
+    ) -> Result<Response, ContractError> {
+
+    let mut raise = RAISES.load(deps.storage, raise_id)
+        .map_err(|_| ContractError::NotFound {})?;
+
+    if env.block.time.seconds() >= raise.deadline {
+        return Err(ContractError::Expired {});
+    }
+
+    let amount = Asset::from_balance(balance, None)
+        .ok()
+        .filter(|contribution| contribution.info == raise.raise)
+        .ok_or(ContractError::WrongDenom {})?
+        .amount;
+
+    // contributions keep being accepted above the hard cap and get scaled down on claim
+    raise.raised += amount;
+    RAISES.save(deps.storage, raise_id, &raise)?;
+
+    let contribution = CONTRIBUTIONS.update(
+        deps.storage,
+        (raise_id, contributor),
+        |contributed| -> StdResult<_> { Ok(contributed.unwrap_or_default() + amount) }
+    )?;
+
+    Ok(Response::new()
+        .add_event(
+            Event::new("peerswap_contribution")
+            .add_attributes(vec![
+                ("raise_id", raise_id.to_string()),
+                ("contributor", contributor.to_string()),
+                ("amount", amount.to_string()),
+                ("token", raise.raise.token()),
+                ("contribution", contribution.to_string()),
+                ("raised", raise.raised.to_string()),
+                ("method", "contribute".to_string())
+            ])
+        )
+    )
+}
+
+
+
+pub fn try_finalize_raise(
+    deps: DepsMut,
+    env: Env,
+    sender: &Addr,
+    raise_id: u32,
+    ) -> Result<Response, ContractError> {
+
+    let config = STATE.load(deps.storage)?;
+
+    let mut raise = RAISES.load(deps.storage, raise_id)
+        .map_err(|_| ContractError::NotFound {})?;
+
+    let creator = deps.api.addr_humanize(&raise.creator)?;
+    if sender != &creator {
+        return Err(ContractError::Unauthorized {});
+    }
+
+    if env.block.time.seconds() < raise.deadline {
+        return Err(ContractError::NotExpired {});
+    }
+
+    if raise.finalized {
+        return Err(ContractError::Finalized {});
+    }
+
+    let mut payments : Vec<CosmosMsg> = vec![];
+
+    let paid = if raise.succeeded() {
+        let accepted = raise.accepted();
+        let fee = accepted * Decimal::from_ratio(config.taker_fee, 10000u16);
+        let admin = deps.api.addr_humanize(&config.admin)?.to_string();
+
+        if accepted > fee {
+            payments.push(raise.raise.transfer(&env, creator.as_str(), accepted - fee)?);
+        }
+        if !fee.is_zero() {
+            payments.push(raise.raise.transfer(&env, &admin, fee)?);
+        }
+        accepted
+    } else {
+        // nobody gets the tokens of a failed raise
+        payments.push(raise.sell.transfer(&env, creator.as_str(), raise.sell_amount)?);
+        Default::default()
+    };
+
+    raise.finalized = true;
+    RAISES.save(deps.storage, raise_id, &raise)?;
+
+    Ok(Response::new()
+        .add_messages(payments)
+        .add_event(
+            Event::new("peerswap_raise_finalized")
+            .add_attributes(vec![
+                ("raise_id", raise_id.to_string()),
+                ("creator", creator.to_string()),
+                ("succeeded", raise.succeeded().to_string()),
+                ("raised", raise.raised.to_string()),
+                ("paid", paid.to_string()),
+                ("raise_token", raise.raise.token()),
+                ("method", "finalize".to_string())
+            ])
+        )
+    )
+}
+
+
+
+pub fn try_claim_tokens(
+    deps: DepsMut,
+    env: Env,
+    contributor: &Addr,
+    raise_id: u32,
+    ) -> Result<Response, ContractError> {
+
+    let config = STATE.load(deps.storage)?;
+
+    let raise = RAISES.load(deps.storage, raise_id)
+        .map_err(|_| ContractError::NotFound {})?;
+
+    if env.block.time.seconds() < raise.deadline {
+        return Err(ContractError::NotExpired {});
+    }
+
+    if !raise.succeeded() {
+        return Err(ContractError::SoftCapNotReached {});
+    }
+
+    let contribution = CONTRIBUTIONS.load(deps.storage, (raise_id, contributor))
+        .map_err(|_| ContractError::NotFound {})?;
+
+    let allocation = raise.allocation(contribution);
+    let excess = raise.excess(contribution);
+    let fee = allocation * Decimal::from_ratio(config.maker_fee, 10000u16);
+    let admin = deps.api.addr_humanize(&config.admin)?.to_string();
+
+    let mut payments : Vec<CosmosMsg> = vec![];
+
+    // small contributions may round down to no allocation or no fee
+    if allocation > fee {
+        payments.push(raise.sell.transfer(&env, contributor.as_str(), allocation - fee)?);
+    }
+
+    if !fee.is_zero() {
+        payments.push(raise.sell.transfer(&env, &admin, fee)?);
+    }
+
+    if !excess.is_zero() {
+        payments.push(raise.raise.transfer(&env, contributor.as_str(), excess)?);
+    }
+
+    CONTRIBUTIONS.remove(deps.storage, (raise_id, contributor));
+
+    Ok(Response::new()
+        .add_messages(payments)
+        .add_event(
+            Event::new("peerswap_raise_claimed")
+            .add_attributes(vec![
+                ("raise_id", raise_id.to_string()),
+                ("contributor", contributor.to_string()),
+                ("amount", allocation.to_string()),
+                ("token", raise.sell.token()),
+                ("refunded", excess.to_string()),
+                ("method", "claim_tokens".to_string())
+            ])
+        )
+    )
+}
+
+
+
+pub fn try_claim_refund(
+    deps: DepsMut,
+    env: Env,
+    contributor: &Addr,
+    raise_id: u32,
+    ) -> Result<Response, ContractError> {
+
+    let raise = RAISES.load(deps.storage, raise_id)
+        .map_err(|_| ContractError::NotFound {})?;
+
+    if env.block.time.seconds() < raise.deadline {
+        return Err(ContractError::NotExpired {});
+    }
+
+    if raise.succeeded() {
+        return Err(ContractError::SoftCapReached {});
+    }
+
+    let contribution = CONTRIBUTIONS.load(deps.storage, (raise_id, contributor))
+        .map_err(|_| ContractError::NotFound {})?;
+
+    CONTRIBUTIONS.remove(deps.storage, (raise_id, contributor));
+
+    Ok(Response::new()
+        .add_message(raise.raise.transfer(&env, contributor.as_str(), contribution)?)
+        .add_event(
+            Event::new("peerswap_raise_refunded")
+            .add_attributes(vec![
+                ("raise_id", raise_id.to_string()),
+                ("contributor", contributor.to_string()),
+                ("amount", contribution.to_string()),
+                ("token", raise.raise.token()),
+                ("method", "claim_refund".to_string())
+            ])
+        )
+    )
+}
+
+
+
+pub fn query_raise(
+    deps: Deps,
+    raise_id: u32
+) -> StdResult<RaiseInfo> {
+    RAISES.load(deps.storage, raise_id)
+}
+
+
+
+pub fn query_contribution(
+    deps: Deps,
+    raise_id: u32,
+    contributor: Addr
+) -> StdResult<GetContributionResponse> {
+    let raise = RAISES.load(deps.storage, raise_id)?;
+    let contribution = CONTRIBUTIONS.may_load(deps.storage, (raise_id, &contributor))?.unwrap_or_default();
+
+    Ok(GetContributionResponse {
+        contribution,
+        allocation: raise.allocation(contribution),
+        excess: raise.excess(contribution),
+    })
+}
@@ -0,0 +1,160 @@
+use cosmwasm_std::{Deps, DepsMut, Response, StdResult, Event, Addr, Order, Storage};
+use cw_storage_plus::Bound;
+
+use crate::arbitration::ensure_admin;
+use crate::asset::AssetInfo;
+use crate::contract::{DEFAULT_LIMIT, MAX_LIMIT};
+use crate::error::ContractError;
+use crate::msg::GetAssetListResponse;
+use crate::state::{STATE, ASSET_LIST, ASSET_LIST_MODE, AssetListMode};
+
+// The admin can register assets to either only allow them or block them from being
+// sold and asked for. Offers created before an asset got blocked can't be swapped anymore
+
+
+
+/// Fails if the mode of the registry doesn't allow the asset to be traded
+pub fn ensure_allowed(storage: &dyn Storage, asset: &AssetInfo) -> Result<(), ContractError> {
+    let registered = || ASSET_LIST.has(storage, asset.to_string());
+
+    let allowed = match ASSET_LIST_MODE.may_load(storage)?.unwrap_or_default() {
+        AssetListMode::Open => true,
+        AssetListMode::Allowlist => registered(),
+        AssetListMode::Denylist => !registered(),
+    };
+
+    if !allowed {
+        return Err(ContractError::AssetNotAllowed { asset: asset.to_string() });
+    }
+
+    Ok(())
+}
+
+
+
+pub fn try_set_asset_list_mode(
+    deps: DepsMut,
+    sender: &Addr,
+    mode: AssetListMode,
+) -> Result<Response, ContractError> {
+
+    ensure_admin(deps.as_ref(), sender)?;
+
+    ASSET_LIST_MODE.save(deps.storage, &mode)?;
+
+    Ok(Response::new()
+        .add_event(
+            Event::new("peerswap_asset_list_mode_set")
+            .add_attributes(vec![
+                ("mode", format!("{:?}", mode).to_lowercase()),
+                ("method", "set_asset_list_mode".to_string())
+            ])
+        )
+    )
+}
+
+
+
+pub fn try_register_assets(
+    deps: DepsMut,
+    sender: &Addr,
+    assets: Vec<AssetInfo>,
+) -> Result<Response, ContractError> {
+
+    ensure_admin(deps.as_ref(), sender)?;
+
+    let mut registered : Vec<String> = vec![];
+
+    for asset in assets {
+        let asset = asset.validate(deps.api)?;
+        ASSET_LIST.save(deps.storage, asset.to_string(), &asset)?;
+        registered.push(asset.to_string());
+    }
+
+    Ok(Response::new()
+        .add_event(
+            Event::new("peerswap_assets_registered")
+            .add_attributes(vec![
+                ("assets", registered.join(",")),
+                ("method", "register_assets".to_string())
+            ])
+        )
+    )
+}
+
+
+
+pub fn try_remove_assets(
+    deps: DepsMut,
+    sender: &Addr,
+    assets: Vec<AssetInfo>,
+) -> Result<Response, ContractError> {
+
+    ensure_admin(deps.as_ref(), sender)?;
+
+    let removed = assets
+        .iter()
+        .map(|asset| {
+            ASSET_LIST.remove(deps.storage, asset.to_string());
+            asset.to_string()
+        })
+        .collect::<Vec<String>>();
+
+    Ok(Response::new()
+        .add_event(
+            Event::new("peerswap_assets_removed")
+            .add_attributes(vec![
+                ("assets", removed.join(",")),
+                ("method", "remove_assets".to_string())
+            ])
+        )
+    )
+}
+
+
+
+pub fn try_set_cw20_code_ids(
+    deps: DepsMut,
+    sender: &Addr,
+    code_ids: Vec<u64>,
+) -> Result<Response, ContractError> {
+
+    ensure_admin(deps.as_ref(), sender)?;
+
+    let mut config = STATE.load(deps.storage)?;
+    config.cw20_code_ids = code_ids;
+    STATE.save(deps.storage, &config)?;
+
+    Ok(Response::new()
+        .add_event(
+            Event::new("peerswap_cw20_code_ids_set")
+            .add_attributes(vec![
+                ("code_ids", config.cw20_code_ids.iter().map(u64::to_string).collect::<Vec<_>>().join(",")),
+                ("method", "set_cw20_code_ids".to_string())
+            ])
+        )
+    )
+}
+
+
+
+pub fn query_asset_list(
+    deps: Deps,
+    start_after: Option<String>,
+    limit: Option<u32>,
+) -> StdResult<GetAssetListResponse> {
+
+    let limit = limit.unwrap_or(DEFAULT_LIMIT).min(MAX_LIMIT) as usize;
+    let start = start_after.map(Bound::exclusive);
+
+    let assets = ASSET_LIST
+        .range(deps.storage, start, None, Order::Ascending)
+        .take(limit)
+        .map(|item| item.map(|(_, asset)| asset))
+        .collect::<StdResult<Vec<_>>>()?;
+
+    Ok(GetAssetListResponse {
+        mode: ASSET_LIST_MODE.may_load(deps.storage)?.unwrap_or_default(),
+        assets,
+    })
+}
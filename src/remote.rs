@@ -0,0 +1,355 @@
+use cosmwasm_std::{
+    Deps, DepsMut, Env, Response, StdResult, Event, Addr, Decimal, Coin, Uint128, CosmosMsg,
+    IbcMsg, IbcTimeout, IbcPacket, IbcBasicResponse, IbcReceiveResponse, Binary,
+    to_binary, from_binary
+};
+use cw20::Balance;
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+
+use crate::asset::{AssetInfo, parse_single_balance, transfer_tokens};
+use crate::contract::remove_otc;
+use crate::error::ContractError;
+use crate::msg::RemoteSwapMsg;
+use crate::registry::ensure_allowed;
+use crate::state::{STATE, OTCS, IBC_CHANNELS, REMOTE_FILLS, REMOTE_FILL_INDEX, RemoteFill};
+
+// Offers listed with a `remote` channel are filled by takers on the counterparty chain.
+// The PeerSwap there escrows the payment and sends a fill packet, this side releases the sold
+// tokens and acknowledges with the seller address so that the escrow goes to the seller.
+// Error acks, timeouts and sellers that are invalid on this chain refund the taker while failed
+// fills don't change the offer
+
+pub const PACKET_VERSION: u32 = 1;
+
+const BECH32_CHARSET: &str = "qpzry9x8gf2tvdw0s3jn54khce6mua7l";
+
+
+/// Whether an address of another chain is shaped like bech32 since it can't be validated here
+pub fn is_remote_address(address: &str) -> bool {
+    match address.rfind('1') {
+        Some(separator) => separator > 0 && address.len() <= 90 && address.len() - separator > 6 &&
+            address[..separator].chars().all(|c| c.is_ascii_lowercase() || c.is_ascii_digit()) &&
+            address[separator + 1..].chars().all(|c| BECH32_CHARSET.contains(c)),
+        None => false
+    }
+}
+
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+pub struct PeerSwapPacket {
+    pub version: u32,
+    pub msg: PacketMsg,
+}
+
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum PacketMsg {
+    Fill {
+        // id of the escrowed payment on the sending chain
+        fill_id: u32,
+        otc_id: u32,
+        taker: String,
+        // receives the sold tokens on the chain of the offer
+        recipient: String,
+        // denom on the sending chain
+        paid: Coin,
+    },
+}
+
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum PacketAck {
+    Result(FillAck),
+    Error(String),
+}
+
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+pub struct FillAck {
+    // receives the escrowed payment on the sending chain
+    pub seller: String,
+    pub given_amount: Uint128,
+    pub given_token: String,
+}
+
+
+
+pub fn try_remote_swap(
+    deps: DepsMut,
+    env: Env,
+    taker: &Addr,
+    msg: RemoteSwapMsg,
+    balance: Balance,
+    ) -> Result<Response, ContractError> {
+
+    let config = STATE.load(deps.storage)?;
+
+    if !config.active {
+        return Err(ContractError::Stopped {});
+    }
+
+    if !IBC_CHANNELS.has(deps.storage, &msg.channel) {
+        return Err(ContractError::WrongChannel {});
+    }
+
+    if msg.recipient.is_empty() || msg.timeout == 0 {
+        return Err(ContractError::InvalidIbcRecipient {});
+    }
+
+    let (native, denom, amount) = parse_single_balance(balance)?;
+
+    if !native {
+        return Err(ContractError::WrongDenom {});
+    }
+
+    let paid = Coin { denom, amount };
+
+    let id = REMOTE_FILL_INDEX.may_load(deps.storage)?.unwrap_or_default();
+
+    REMOTE_FILLS.save(deps.storage, id, &RemoteFill {
+        channel: msg.channel.clone(),
+        otc_id: msg.otc_id,
+        taker: taker.clone(),
+        paid: paid.clone(),
+    })?;
+    REMOTE_FILL_INDEX.save(deps.storage, &(id + 1))?;
+
+    let packet = PeerSwapPacket {
+        version: PACKET_VERSION,
+        msg: PacketMsg::Fill {
+            fill_id: id,
+            otc_id: msg.otc_id,
+            taker: taker.to_string(),
+            recipient: msg.recipient.clone(),
+            paid: paid.clone(),
+        }
+    };
+
+    Ok(Response::new()
+        .add_message(IbcMsg::SendPacket {
+            channel_id: msg.channel.clone(),
+            data: to_binary(&packet)?,
+            timeout: IbcTimeout::with_timestamp(env.block.time.plus_seconds(msg.timeout)),
+        })
+        .add_event(
+            Event::new("peerswap_remote_swap_sent")
+            .add_attributes(vec![
+                ("fill_id", id.to_string()),
+                ("channel", msg.channel),
+                ("otc_id", msg.otc_id.to_string()),
+                ("taker", taker.to_string()),
+                ("recipient", msg.recipient),
+                ("sent_amount", paid.amount.to_string()),
+                ("sent_token", paid.denom),
+                ("method", "remote_swap".to_string())
+            ])
+        )
+    )
+}
+
+
+
+/// Fills an offer of this chain for a taker on the other end of the channel
+fn receive_fill(
+    deps: DepsMut,
+    env: Env,
+    channel: &str,
+    msg: PacketMsg,
+) -> Result<IbcReceiveResponse, ContractError> {
+
+    let PacketMsg::Fill { fill_id, otc_id, taker, recipient, paid } = msg;
+
+    let config = STATE.load(deps.storage)?;
+
+    if !config.active {
+        return Err(ContractError::Stopped {});
+    }
+
+    let mut otc = OTCS.load(deps.storage, otc_id)
+        .map_err(|_| ContractError::NotFound {})?;
+
+    let remote = otc.remote.clone()
+        .filter(|remote| remote.channel == channel)
+        .ok_or(ContractError::WrongChannel {})?;
+
+    if otc.expires.is_expired(&env.block) {
+        return Err(ContractError::Expired {});
+    }
+
+    if !otc.is_allowed_taker(&Addr::unchecked(&taker)) {
+        return Err(ContractError::NotAllowedTaker {});
+    }
+
+    if paid.amount != otc.sell_amount && paid.amount < Uint128::from(10000u128) {
+        return Err(ContractError::TooSmall {});
+    }
+
+    let ask_index = otc.ask_for
+        .iter()
+        .position(|ask| ask.info == AssetInfo::Native(paid.denom.clone()))
+        .ok_or(ContractError::WrongDenom {})?;
+
+    ensure_allowed(deps.storage, &otc.sell)?;
+    ensure_allowed(deps.storage, &otc.ask_for[ask_index].info)?;
+
+    let recipient = deps.api.addr_validate(&recipient)?;
+
+    let to_sell_amount = otc.fill(ask_index, paid.amount, env.block.time.seconds());
+
+    if to_sell_amount.is_zero() {
+        return Err(ContractError::TooSmall {});
+    }
+    let maker_fee = to_sell_amount * Decimal::from_ratio(config.maker_fee, 10000u16);
+
+    let mut payments : Vec<CosmosMsg> = vec![
+        otc.sell.transfer(&env, recipient.as_str(), to_sell_amount - maker_fee)?
+    ];
+
+    if !maker_fee.is_zero() {
+        let admin = deps.api.addr_humanize(&config.admin)?;
+        payments.push(otc.sell.transfer(&env, admin.as_str(), maker_fee)?);
+    }
+
+    if otc.sell_amount.is_zero() {
+        remove_otc(deps.storage, otc_id)?;
+    } else {
+        OTCS.save(deps.storage, otc_id, &otc)?;
+    }
+
+    let ack = PacketAck::Result(FillAck {
+        seller: remote.seller,
+        given_amount: to_sell_amount,
+        given_token: otc.sell.token(),
+    });
+
+    Ok(IbcReceiveResponse::new()
+        .set_ack(to_binary(&ack)?)
+        .add_messages(payments)
+        .add_event(
+            Event::new("peerswap_remote_swap")
+            .add_attributes(vec![
+                ("otc_id", otc_id.to_string()),
+                ("fill_id", fill_id.to_string()),
+                ("channel", channel.to_string()),
+                ("taker", taker),
+                ("recipient", recipient.to_string()),
+                ("given_amount", to_sell_amount.to_string()),
+                ("given_token", otc.sell.token()),
+                ("sent_amount", paid.amount.to_string()),
+                ("sent_token", paid.denom),
+                ("method", "receive_fill".to_string())
+            ])
+        )
+    )
+}
+
+
+
+/// Handles a packet of a PeerSwap channel. Failures are acknowledged as errors
+/// instead of aborting so that the sending chain can refund the taker
+pub fn receive_packet(
+    deps: DepsMut,
+    env: Env,
+    packet: IbcPacket,
+) -> IbcReceiveResponse {
+
+    let res = match from_binary::<PeerSwapPacket>(&packet.data) {
+        Ok(PeerSwapPacket { version: PACKET_VERSION, msg }) => receive_fill(deps, env, &packet.dest.channel_id, msg),
+        _ => Err(ContractError::InvalidPacket {})
+    };
+
+    res.unwrap_or_else(|err| {
+        let ack = PacketAck::Error(err.to_string());
+        IbcReceiveResponse::new()
+            .set_ack(to_binary(&ack).unwrap_or_default())
+            .add_attribute("method", "receive_fill")
+            .add_attribute("error", err.to_string())
+    })
+}
+
+
+
+/// Pays the escrowed payment to the seller once the fill succeeded on the chain of the offer
+/// or refunds it to the taker if it failed
+pub fn settle_remote_fill(
+    deps: DepsMut,
+    packet: PeerSwapPacket,
+    ack: Option<Binary>,
+) -> Result<IbcBasicResponse, ContractError> {
+
+    let PacketMsg::Fill { fill_id, .. } = packet.msg;
+
+    let fill = match REMOTE_FILLS.may_load(deps.storage, fill_id)? {
+        Some(fill) => fill,
+        None => return Ok(IbcBasicResponse::new())
+    };
+
+    REMOTE_FILLS.remove(deps.storage, fill_id);
+
+    let filled = ack.and_then(|ack| match from_binary::<PacketAck>(&ack) {
+        Ok(PacketAck::Result(filled)) => Some(filled),
+        _ => None
+    });
+
+    let mut payments : Vec<CosmosMsg> = vec![];
+
+    // an ack that can't be processed is never retried so the escrow is always released
+    let seller = filled.as_ref().map(|filled| deps.api.addr_validate(&filled.seller));
+
+    let event = match (filled, seller) {
+        (Some(filled), Some(Ok(seller))) => {
+            let config = STATE.load(deps.storage)?;
+            let taker_fee = fill.paid.amount * Decimal::from_ratio(config.taker_fee, 10000u16);
+
+            payments.push(transfer_tokens(true, &fill.paid.denom, seller.as_str(), fill.paid.amount - taker_fee)?);
+
+            if !taker_fee.is_zero() {
+                let admin = deps.api.addr_humanize(&config.admin)?;
+                payments.push(transfer_tokens(true, &fill.paid.denom, admin.as_str(), taker_fee)?);
+            }
+
+            Event::new("peerswap_remote_swap_completed")
+            .add_attributes(vec![
+                ("fill_id", fill_id.to_string()),
+                ("otc_id", fill.otc_id.to_string()),
+                ("seller", seller.to_string()),
+                ("given_amount", filled.given_amount.to_string()),
+                ("given_token", filled.given_token),
+                ("sent_amount", fill.paid.amount.to_string()),
+                ("sent_token", fill.paid.denom),
+                ("method", "remote_swap_completed".to_string())
+            ])
+        },
+        // failed fills and sellers that can't be paid here
+        _ => {
+            payments.push(transfer_tokens(true, &fill.paid.denom, fill.taker.as_str(), fill.paid.amount)?);
+
+            Event::new("peerswap_remote_swap_refunded")
+            .add_attributes(vec![
+                ("fill_id", fill_id.to_string()),
+                ("otc_id", fill.otc_id.to_string()),
+                ("taker", fill.taker.to_string()),
+                ("amount", fill.paid.amount.to_string()),
+                ("token", fill.paid.denom),
+                ("method", "remote_swap_refunded".to_string())
+            ])
+        }
+    };
+
+    Ok(IbcBasicResponse::new()
+        .add_messages(payments)
+        .add_event(event)
+    )
+}
+
+
+
+pub fn query_remote_fill(
+    deps: Deps,
+    fill_id: u32
+) -> StdResult<RemoteFill> {
+    REMOTE_FILLS.load(deps.storage, fill_id)
+}
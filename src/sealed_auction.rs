@@ -0,0 +1,409 @@
+use cosmwasm_std::{
+    Deps, DepsMut, Env, Response, StdResult, Event, Addr, Decimal, Order, Storage, Uint128,
+    CosmosMsg, to_binary
+};
+use cw20::Balance;
+
+use crate::asset::Asset;
+use crate::contract::parse_sell_balance;
+use crate::error::ContractError;
+use crate::msg::{NewSealedAuction, NewSealedAuctionResponse};
+use crate::registry::ensure_allowed;
+use crate::state::{STATE, AUCTION_INDEX, SEALED_AUCTIONS, SEALED_BIDS, SealedAuctionInfo, SealedBid, Bid};
+use crate::utils::{decode_hash, sha256};
+
+
+
+/// Commitment a bidder has to submit for hiding their bid, the sha256 of
+/// the JSON array `[bidder, amount, salt]`
+pub fn bid_commitment(bidder: &Addr, amount: Uint128, salt: &str) -> StdResult<String> {
+    Ok(hex::encode(sha256(&to_binary(&(bidder, amount, salt))?)))
+}
+
+
+
+pub fn try_create_sealed_auction(
+    deps: DepsMut,
+    env: Env,
+    seller: &Addr,
+    sell_balance: Balance,
+    msg: NewSealedAuction,
+    ) -> Result<Response, ContractError> {
+
+    let config = STATE.load(deps.storage)?;
+
+    if !config.active {
+        return Err(ContractError::Stopped {});
+    }
+
+    if msg.commit_end <= env.block.time.seconds() {
+        return Err(ContractError::Expired {});
+    }
+
+    if msg.reveal_end <= msg.commit_end {
+        return Err(ContractError::InvalidAuction {});
+    }
+
+    let sell = parse_sell_balance(sell_balance)?;
+    let min_bid = Asset::from_balance(msg.min_bid, None)
+        .map_err(|_| ContractError::NoAskTokens {})?;
+
+    if sell.info == min_bid.info {
+        return Err(ContractError::SameToken {});
+    }
+
+    let bid = min_bid.info.validate(deps.api)?;
+
+    ensure_allowed(deps.storage, &sell.info)?;
+    ensure_allowed(deps.storage, &bid)?;
+
+    let auction = SealedAuctionInfo {
+        seller: deps.api.addr_canonicalize(seller.as_str())?,
+        sell: sell.info,
+        sell_amount: sell.amount,
+        bid,
+        min_bid: min_bid.amount,
+        commit_end: msg.commit_end,
+        reveal_end: msg.reveal_end,
+        second_price: msg.second_price,
+        refund_unrevealed: msg.refund_unrevealed,
+        highest_bid: None,
+        second_bid: None,
+        description: msg.description,
+        settled: false,
+    };
+
+    let id = AUCTION_INDEX.may_load(deps.storage)?.unwrap_or_default();
+
+    SEALED_AUCTIONS.save(deps.storage, id, &auction)?;
+    AUCTION_INDEX.save(deps.storage, &(id + 1))?;
+
+    let data = NewSealedAuctionResponse {
+        id,
+        auction: auction.clone()
+    };
+
+    Ok(Response::new()
+        .set_data(to_binary(&data)?)
+        .add_event(
+            Event::new("peerswap_sealed_auction_created")
+            .add_attributes(vec![
+                ("auction_id", id.to_string()),
+                ("seller", seller.to_string()),
+                ("amount", auction.sell_amount.to_string()),
+                ("token", auction.sell.token()),
+                ("bid_token", auction.bid.token()),
+                ("min_bid", auction.min_bid.to_string()),
+                ("commit_end", auction.commit_end.to_string()),
+                ("reveal_end", auction.reveal_end.to_string()),
+                ("method", "create_sealed_auction".to_string())
+            ])
+        )
+    )
+}
+
+
+
+pub fn try_commit_bid(
+    deps: DepsMut,
+    env: Env,
+    bidder: &Addr,
+    auction_id: u32,
+    commitment: String,
+    balance: Balance,
+    ) -> Result<Response, ContractError> {
+
+    let auction = SEALED_AUCTIONS.load(deps.storage, auction_id)
+        .map_err(|_| ContractError::NotFound {})?;
+
+    if env.block.time.seconds() >= auction.commit_end {
+        return Err(ContractError::WrongPhase {});
+    }
+
+    decode_hash(&commitment).ok_or(ContractError::InvalidHash {})?;
+
+    if SEALED_BIDS.has(deps.storage, (auction_id, bidder)) {
+        return Err(ContractError::AlreadyCommitted {});
+    }
+
+    let deposit = Asset::from_balance(balance, None)
+        .ok()
+        .filter(|deposit| deposit.info == auction.bid)
+        .ok_or(ContractError::WrongDenom {})?
+        .amount;
+
+    // a deposit that can't cover the reserve price can never be a valid bid
+    if deposit < auction.min_bid {
+        return Err(ContractError::BidTooLow {});
+    }
+
+    SEALED_BIDS.save(deps.storage, (auction_id, bidder), &SealedBid {
+        commitment: commitment.clone(),
+        deposit,
+        revealed: None,
+    })?;
+
+    Ok(Response::new()
+        .add_event(
+            Event::new("peerswap_bid_committed")
+            .add_attributes(vec![
+                ("auction_id", auction_id.to_string()),
+                ("bidder", bidder.to_string()),
+                ("deposit", deposit.to_string()),
+                ("token", auction.bid.token()),
+                ("commitment", commitment),
+                ("method", "commit_bid".to_string())
+            ])
+        )
+    )
+}
+
+
+
+pub fn try_reveal_bid(
+    deps: DepsMut,
+    env: Env,
+    bidder: &Addr,
+    auction_id: u32,
+    amount: Uint128,
+    salt: String,
+    ) -> Result<Response, ContractError> {
+
+    let mut auction = SEALED_AUCTIONS.load(deps.storage, auction_id)
+        .map_err(|_| ContractError::NotFound {})?;
+
+    let now = env.block.time.seconds();
+
+    if now < auction.commit_end || now >= auction.reveal_end {
+        return Err(ContractError::WrongPhase {});
+    }
+
+    let mut bid = SEALED_BIDS.load(deps.storage, (auction_id, bidder))
+        .map_err(|_| ContractError::NotFound {})?;
+
+    if bid.revealed.is_some() {
+        return Err(ContractError::AlreadyCommitted {});
+    }
+
+    if bid_commitment(bidder, amount, &salt)? != bid.commitment.to_lowercase() {
+        return Err(ContractError::WrongSecret {});
+    }
+
+    bid.revealed = Some(amount);
+    SEALED_BIDS.save(deps.storage, (auction_id, bidder), &bid)?;
+
+    // revealed bids that the deposit doesn't cover are only refunded
+    let valid = amount >= auction.min_bid && amount <= bid.deposit;
+
+    if valid {
+        match auction.highest_bid.as_ref() {
+            Some(highest) if amount <= highest.amount => {
+                auction.second_bid = Some(auction.second_bid.unwrap_or_default().max(amount));
+            },
+            _ => {
+                auction.second_bid = auction.highest_bid.as_ref().map(|highest| highest.amount);
+                auction.highest_bid = Some(Bid {
+                    bidder: bidder.clone(),
+                    amount
+                });
+            }
+        }
+        SEALED_AUCTIONS.save(deps.storage, auction_id, &auction)?;
+    }
+
+    Ok(Response::new()
+        .add_event(
+            Event::new("peerswap_bid_revealed")
+            .add_attributes(vec![
+                ("auction_id", auction_id.to_string()),
+                ("bidder", bidder.to_string()),
+                ("amount", amount.to_string()),
+                ("valid", valid.to_string()),
+                ("method", "reveal_bid".to_string())
+            ])
+        )
+    )
+}
+
+
+
+pub fn try_settle_sealed_auction(
+    deps: DepsMut,
+    env: Env,
+    auction_id: u32,
+    ) -> Result<Response, ContractError> {
+
+    let config = STATE.load(deps.storage)?;
+
+    let mut auction = SEALED_AUCTIONS.load(deps.storage, auction_id)
+        .map_err(|_| ContractError::NotFound {})?;
+
+    if env.block.time.seconds() < auction.reveal_end {
+        return Err(ContractError::NotExpired {});
+    }
+
+    if auction.settled {
+        return Err(ContractError::WrongPhase {});
+    }
+
+    let seller = deps.api.addr_humanize(&auction.seller)?;
+    let admin = deps.api.addr_humanize(&config.admin)?.to_string();
+
+    let mut payments : Vec<CosmosMsg> = vec![];
+
+    let price = match auction.highest_bid.as_ref() {
+        Some(_) if auction.second_price => auction.second_bid.unwrap_or(auction.min_bid).max(auction.min_bid),
+        Some(highest) => highest.amount,
+        None => Uint128::zero()
+    };
+
+    // the other bidders withdraw their deposits themselves
+    let winner = match auction.highest_bid.as_ref() {
+        Some(highest) => {
+            let taker_fee = price * Decimal::from_ratio(config.taker_fee, 10000u16);
+            let maker_fee = auction.sell_amount * Decimal::from_ratio(config.maker_fee, 10000u16);
+
+            payments.push(auction.bid.transfer(&env, seller.as_str(), price - taker_fee)?);
+            payments.push(auction.sell.transfer(&env, highest.bidder.as_str(), auction.sell_amount - maker_fee)?);
+
+            // fees of low prices and small lots round down to nothing
+            if !taker_fee.is_zero() {
+                payments.push(auction.bid.transfer(&env, &admin, taker_fee)?);
+            }
+            if !maker_fee.is_zero() {
+                payments.push(auction.sell.transfer(&env, &admin, maker_fee)?);
+            }
+
+            let bid = SEALED_BIDS.load(deps.storage, (auction_id, &highest.bidder))?;
+            if bid.deposit > price {
+                payments.push(auction.bid.transfer(&env, highest.bidder.as_str(), bid.deposit - price)?);
+            }
+            SEALED_BIDS.remove(deps.storage, (auction_id, &highest.bidder));
+
+            highest.bidder.clone()
+        },
+        // no valid bids so the lot goes back
+        None => {
+            payments.push(auction.sell.transfer(&env, seller.as_str(), auction.sell_amount)?);
+            seller.clone()
+        }
+    };
+
+    auction.settled = true;
+    save_or_remove_settled(deps.storage, auction_id, &auction)?;
+
+    Ok(Response::new()
+        .add_messages(payments)
+        .add_event(
+            Event::new("peerswap_auction_settled")
+            .add_attributes(vec![
+                ("auction_id", auction_id.to_string()),
+                ("seller", seller.to_string()),
+                ("winner", winner.to_string()),
+                ("amount", auction.sell_amount.to_string()),
+                ("token", auction.sell.token()),
+                ("price", price.to_string()),
+                ("bid_token", auction.bid.token()),
+                ("method", "settle".to_string())
+            ])
+        )
+    )
+}
+
+
+
+/// Returns the deposit of a losing bid after the reveal phase, or pays it
+/// to the seller if the bid was forfeited by not revealing it
+pub fn try_withdraw_sealed_bid(
+    deps: DepsMut,
+    env: Env,
+    auction_id: u32,
+    bidder: String,
+    ) -> Result<Response, ContractError> {
+
+    let auction = SEALED_AUCTIONS.load(deps.storage, auction_id)
+        .map_err(|_| ContractError::NotFound {})?;
+
+    if env.block.time.seconds() < auction.reveal_end {
+        return Err(ContractError::WrongPhase {});
+    }
+
+    let bidder = deps.api.addr_validate(&bidder)?;
+
+    let bid = SEALED_BIDS.load(deps.storage, (auction_id, &bidder))
+        .map_err(|_| ContractError::NotFound {})?;
+
+    // the deposit of the winner is only released by the settlement
+    if auction.highest_bid.as_ref().is_some_and(|highest| highest.bidder == bidder) {
+        return Err(ContractError::WrongPhase {});
+    }
+
+    let forfeited = bid.revealed.is_none() && !auction.refund_unrevealed;
+
+    let recipient = if forfeited {
+        deps.api.addr_humanize(&auction.seller)?
+    } else {
+        bidder.clone()
+    };
+
+    SEALED_BIDS.remove(deps.storage, (auction_id, &bidder));
+    save_or_remove_settled(deps.storage, auction_id, &auction)?;
+
+    Ok(Response::new()
+        .add_message(auction.bid.transfer(&env, recipient.as_str(), bid.deposit)?)
+        .add_event(
+            Event::new("peerswap_sealed_bid_withdrawn")
+            .add_attributes(vec![
+                ("auction_id", auction_id.to_string()),
+                ("bidder", bidder.to_string()),
+                ("recipient", recipient.to_string()),
+                ("amount", bid.deposit.to_string()),
+                ("token", auction.bid.token()),
+                ("forfeited", forfeited.to_string()),
+                ("method", "withdraw_sealed_bid".to_string())
+            ])
+        )
+    )
+}
+
+
+
+// settled auctions are kept until every deposit has been withdrawn
+fn save_or_remove_settled(
+    storage: &mut dyn Storage,
+    auction_id: u32,
+    auction: &SealedAuctionInfo,
+) -> StdResult<()> {
+
+    let has_bids = SEALED_BIDS
+        .prefix(auction_id)
+        .keys(storage, None, None, Order::Ascending)
+        .next()
+        .is_some();
+
+    if auction.settled && !has_bids {
+        SEALED_AUCTIONS.remove(storage, auction_id);
+        Ok(())
+    } else {
+        SEALED_AUCTIONS.save(storage, auction_id, auction)
+    }
+}
+
+
+
+pub fn query_sealed_auction(
+    deps: Deps,
+    auction_id: u32
+) -> StdResult<SealedAuctionInfo> {
+    SEALED_AUCTIONS.load(deps.storage, auction_id)
+}
+
+
+
+pub fn query_sealed_bid(
+    deps: Deps,
+    auction_id: u32,
+    bidder: Addr
+) -> StdResult<SealedBid> {
+    SEALED_BIDS.load(deps.storage, (auction_id, &bidder))
+}
@@ -0,0 +1,201 @@
+use cosmwasm_std::{
+    Deps, DepsMut, Env, Response, StdResult, Event, Addr, Decimal, Binary, CosmosMsg,
+    to_binary
+};
+use cw20::Balance;
+
+use crate::allowance::{available_allowance, transfer_from};
+use crate::asset::{AssetInfo, parse_single_balance, transfer_tokens};
+use crate::error::ContractError;
+use crate::msg::{SignedOrder, GetOrderStatusResponse};
+use crate::registry::ensure_allowed;
+use crate::state::{STATE, ORDER_KEYS, ORDER_NONCES, OrderStatus};
+use crate::utils::sha256;
+
+// Makers can post orders off-chain instead of locking tokens in an offer.
+// An order is signed over the sha256 of its JSON encoding and filled in full by a taker
+// submitting it, the sold cw20s are pulled from the maker with the allowance given to the contract
+
+
+
+pub fn try_set_order_key(
+    deps: DepsMut,
+    maker: &Addr,
+    pubkey: Binary,
+    ) -> Result<Response, ContractError> {
+
+    // compressed or uncompressed
+    if pubkey.len() != 33 && pubkey.len() != 65 {
+        return Err(ContractError::InvalidOrderKey {});
+    }
+
+    ORDER_KEYS.save(deps.storage, maker, &pubkey)?;
+
+    Ok(Response::new()
+        .add_event(
+            Event::new("peerswap_order_key_set")
+            .add_attributes(vec![
+                ("maker", maker.to_string()),
+                ("pubkey", pubkey.to_base64()),
+                ("method", "set_order_key".to_string())
+            ])
+        )
+    )
+}
+
+
+
+/// Checks that the order was signed by its maker for this contract
+fn verify_order(
+    deps: Deps,
+    env: &Env,
+    maker: &Addr,
+    order: &SignedOrder,
+    signature: &Binary,
+) -> Result<(), ContractError> {
+
+    if order.chain_id != env.block.chain_id || order.contract != env.contract.address {
+        return Err(ContractError::InvalidSignature {});
+    }
+
+    let pubkey = ORDER_KEYS.may_load(deps.storage, maker)?
+        .ok_or(ContractError::InvalidSignature {})?;
+
+    let hash = sha256(&to_binary(order)?);
+
+    let valid = deps.api.secp256k1_verify(&hash, signature, &pubkey)
+        .unwrap_or(false);
+
+    if !valid {
+        return Err(ContractError::InvalidSignature {});
+    }
+
+    Ok(())
+}
+
+
+
+pub fn try_fill_signed_order(
+    deps: DepsMut,
+    env: Env,
+    taker: &Addr,
+    order: SignedOrder,
+    signature: Binary,
+    balance: Balance,
+    ) -> Result<Response, ContractError> {
+
+    let config = STATE.load(deps.storage)?;
+
+    if !config.active {
+        return Err(ContractError::Stopped {});
+    }
+
+    let maker = deps.api.addr_validate(&order.maker)?;
+
+    verify_order(deps.as_ref(), &env, &maker, &order, &signature)?;
+
+    if order.expires.is_expired(&env.block) {
+        return Err(ContractError::Expired {});
+    }
+
+    if ORDER_NONCES.has(deps.storage, (&maker, order.nonce)) {
+        return Err(ContractError::NonceUsed {});
+    }
+
+    if order.taker.as_ref().is_some_and(|allowed| allowed != taker.as_str()) {
+        return Err(ContractError::NotAllowedTaker {});
+    }
+
+    let (paid_native, paid_token, paid_amount) = parse_single_balance(balance)?;
+    let (ask_native, ask_token, ask_amount) = parse_single_balance(order.ask.clone())?;
+
+    if paid_native != ask_native || paid_token != ask_token || paid_amount != ask_amount {
+        return Err(ContractError::WrongDenom {});
+    }
+
+    let sell_token = deps.api.addr_validate(&order.sell.address)?;
+    let sell_amount = order.sell.amount;
+
+    ensure_allowed(deps.storage, &AssetInfo::Cw20(sell_token.clone()))?;
+    ensure_allowed(deps.storage, &AssetInfo::from_parts(paid_native, paid_token.clone(), None))?;
+
+    if available_allowance(deps.as_ref(), &env, sell_token.as_str(), &maker)? < sell_amount {
+        return Err(ContractError::InsufficientAllowance {});
+    }
+
+    ORDER_NONCES.save(deps.storage, (&maker, order.nonce), &OrderStatus::Filled)?;
+
+    let admin = deps.api.addr_humanize(&config.admin)?;
+    let taker_fee = paid_amount * Decimal::from_ratio(config.taker_fee, 10000u16);
+    let maker_fee = sell_amount * Decimal::from_ratio(config.maker_fee, 10000u16);
+
+    let mut payments : Vec<CosmosMsg> = vec![
+        transfer_tokens(paid_native, &paid_token, maker.as_str(), paid_amount - taker_fee)?,
+        transfer_from(sell_token.as_str(), &maker, taker.as_str(), sell_amount - maker_fee)?,
+    ];
+
+    if !taker_fee.is_zero() {
+        payments.push(transfer_tokens(paid_native, &paid_token, admin.as_str(), taker_fee)?);
+    }
+
+    if !maker_fee.is_zero() {
+        payments.push(transfer_from(sell_token.as_str(), &maker, admin.as_str(), maker_fee)?);
+    }
+
+    Ok(Response::new()
+        .add_messages(payments)
+        .add_event(
+            Event::new("peerswap_signed_order_filled")
+            .add_attributes(vec![
+                ("maker", maker.to_string()),
+                ("nonce", order.nonce.to_string()),
+                ("taker", taker.to_string()),
+                ("given_amount", sell_amount.to_string()),
+                ("given_token", sell_token.to_string()),
+                ("sent_amount", paid_amount.to_string()),
+                ("sent_token", paid_token),
+                ("method", "fill_signed_order".to_string())
+            ])
+        )
+    )
+}
+
+
+
+pub fn try_cancel_signed_order(
+    deps: DepsMut,
+    maker: &Addr,
+    nonce: u64,
+    ) -> Result<Response, ContractError> {
+
+    if ORDER_NONCES.has(deps.storage, (maker, nonce)) {
+        return Err(ContractError::NonceUsed {});
+    }
+
+    ORDER_NONCES.save(deps.storage, (maker, nonce), &OrderStatus::Cancelled)?;
+
+    Ok(Response::new()
+        .add_event(
+            Event::new("peerswap_signed_order_cancelled")
+            .add_attributes(vec![
+                ("maker", maker.to_string()),
+                ("nonce", nonce.to_string()),
+                ("method", "cancel_signed_order".to_string())
+            ])
+        )
+    )
+}
+
+
+
+pub fn query_order_status(
+    deps: Deps,
+    maker: String,
+    nonce: u64,
+) -> StdResult<GetOrderStatusResponse> {
+    let maker = deps.api.addr_validate(&maker)?;
+
+    Ok(GetOrderStatusResponse {
+        status: ORDER_NONCES.may_load(deps.storage, (&maker, nonce))?
+    })
+}
@@ -1,9 +1,11 @@
 use cw_utils::Expiration;
 use schemars::JsonSchema;
 use serde::{Deserialize, Serialize};
-use cosmwasm_std::{CanonicalAddr, Addr, Uint128};
+use cosmwasm_std::{CanonicalAddr, Addr, Uint128, Decimal, Coin, IbcChannel, Binary, Empty};
 use cw_storage_plus::{Item, Map};
 
+use crate::asset::AssetInfo;
+
 
 #[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
 pub struct State {
@@ -12,6 +14,38 @@ pub struct State {
     pub active: bool,
     pub taker_fee: u16, // 2nd decimal, e.g. 5 = 0.05%
     pub maker_fee: u16, // 2nd decimal 
+    #[serde(default)]
+    pub keeper_reward: u16, // share of the collected fees paid to a matcher, 2nd decimal
+    #[serde(default)]
+    pub arbitrator: Option<Addr>, // resolves fiat escrows the parties disagree on
+    #[serde(default)]
+    pub cw20_code_ids: Vec<u64>, // code ids asked cw20s must be instances of, any if empty
+}
+
+
+/// How the registered assets restrict what offers can sell and ask for
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, Eq, JsonSchema, Default)]
+#[serde(rename_all = "snake_case")]
+pub enum AssetListMode {
+    // any asset can be traded
+    #[default]
+    Open,
+    // only the registered assets can be traded
+    Allowlist,
+    // the registered assets can't be traded
+    Denylist,
+}
+
+
+/// Display information of an asset for the UI
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+pub struct TokenMetadata {
+    pub name: String,
+    pub symbol: String,
+    pub decimals: u8,
+    pub logo: Option<String>,
+    // set by the admin rather than fetched from the cw20 contract
+    pub verified: bool,
 }
 
 
@@ -25,9 +59,206 @@ pub struct UserInfo {
 pub struct AskFor {
     pub initial_amount: Uint128,
     pub amount: Uint128,
-    pub denom: Option<String>,
-    pub address: Option<Addr>,
-    pub native: bool,
+    pub info: AssetInfo,
+}
+
+
+
+/// Price of the asks decaying from a multiple of the amounts down to the amounts themselves
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+pub struct DutchAuction {
+    // seconds since epoch
+    pub start_time: u64,
+    pub end_time: u64,
+    // the asks are multiplied by it at the start, e.g. 2 for twice the floor price
+    pub start_multiplier: Decimal,
+    // seconds between price drops, the price decays linearly if not set
+    pub step: Option<u64>,
+}
+
+
+impl DutchAuction {
+    /// Multiplier of the asks at the given time
+    pub fn price_multiplier(&self, now: u64) -> Decimal {
+        if now <= self.start_time {
+            return self.start_multiplier;
+        }
+        if now >= self.end_time {
+            return Decimal::one();
+        }
+
+        let mut elapsed = now - self.start_time;
+        if let Some(step) = self.step.filter(|step| *step > 0) {
+            elapsed -= elapsed % step;
+        }
+
+        self.start_multiplier - (self.start_multiplier - Decimal::one()) 
+            * Decimal::from_ratio(elapsed, self.end_time - self.start_time)
+    }
+}
+
+
+
+/// Delivery of the bought tokens over time instead of at once
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+pub struct Vesting {
+    // seconds after the swap before anything can be claimed
+    pub cliff: u64,
+    // seconds after the swap over which the tokens vest linearly
+    pub duration: u64,
+}
+
+
+impl Vesting {
+    /// Part of the amount vested at the given time for a position started at `start`
+    pub fn vested(&self, amount: Uint128, start: u64, now: u64) -> Uint128 {
+        let elapsed = now.saturating_sub(start);
+
+        if elapsed < self.cliff {
+            return Uint128::zero();
+        }
+        if elapsed >= self.duration {
+            return amount;
+        }
+
+        amount.multiply_ratio(elapsed, self.duration)
+    }
+}
+
+
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+pub struct VestingPosition {
+    pub otc_id: u32,
+    pub asset: AssetInfo,
+    pub amount: Uint128,
+    pub claimed: Uint128,
+    // seconds since epoch
+    pub start: u64,
+    pub vesting: Vesting,
+}
+
+
+impl VestingPosition {
+    /// Vested tokens that haven't been claimed yet
+    pub fn claimable(&self, now: u64) -> Uint128 {
+        self.vesting.vested(self.amount, self.start, now) - self.claimed
+    }
+}
+
+
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum EscrowStatus {
+    // waiting for the buyer to pay off-chain
+    Locked,
+    // waiting for the seller to release or the arbitrator to resolve
+    PaymentSent,
+    // frozen until the arbitrator resolves
+    Disputed,
+}
+
+
+/// Crypto locked for a buyer paying fiat off-chain
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+pub struct FiatEscrow {
+    pub buyer: Addr,
+    pub status: EscrowStatus,
+    // seconds the arbitrator has to resolve after the payment is marked as sent
+    pub dispute_window: u64,
+    // falls back to the arbitrator of the config if not set
+    #[serde(default)]
+    pub arbitrator: Option<Addr>,
+    // share of the escrow paid to the arbitrator on resolution, 2nd decimal
+    #[serde(default)]
+    pub arbitrator_fee: u16,
+}
+
+
+/// Address on another chain receiving native tokens over an ICS-20 channel
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+pub struct IbcRecipient {
+    pub channel: String,
+    pub address: String,
+    // seconds from the block time until the transfer times out
+    pub timeout: u64,
+}
+
+
+/// Offer filled through a PeerSwap channel with the asks paid on the counterparty chain
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+pub struct RemoteOffer {
+    pub channel: String,
+    // address on the counterparty chain receiving the payments
+    pub seller: String,
+}
+
+
+/// Payment escrowed for an offer on the counterparty chain until the fill is acknowledged
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+pub struct RemoteFill {
+    pub channel: String,
+    pub otc_id: u32,
+    pub taker: Addr,
+    pub paid: Coin,
+}
+
+
+/// Payout sent over IBC that is refunded locally if the transfer fails
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+pub struct PendingTransfer {
+    pub refund_address: Addr,
+    pub coin: Coin,
+    // sent back over IBC instead when the refund address is an ibc-hooks intermediary
+    #[serde(default)]
+    pub refund_recipient: Option<IbcRecipient>,
+}
+
+
+/// Why the nonce of a signed order can't be used anymore
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum OrderStatus {
+    Filled,
+    Cancelled,
+}
+
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+pub struct Milestone {
+    pub amount: Uint128,
+    pub description: Option<String>,
+    pub released: bool,
+}
+
+
+/// Tokens paid out in tranches as the seller approves milestones of a deal
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+pub struct MilestoneEscrow {
+    // receives the tranches
+    pub payee: Addr,
+    pub milestones: Vec<Milestone>,
+    // can approve milestones in place of the seller
+    pub arbitrator: Option<Addr>,
+}
+
+
+/// Arbitrator chosen for an offer that isn't a fiat escrow
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+pub struct Arbitration {
+    pub arbitrator: Addr,
+    // share of the escrow paid to the arbitrator on resolution, 2nd decimal
+    pub arbitrator_fee: u16,
+    // frozen until the arbitrator resolves or the dispute times out
+    pub disputed: bool,
+}
+
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+pub struct ArbitratorInfo {
+    pub fee: u16, // 2nd decimal
+    pub description: Option<String>,
 }
 
 
@@ -35,17 +266,335 @@ pub struct AskFor {
 #[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
 pub struct OTCInfo {
     pub seller: CanonicalAddr,
-    pub sell_native: bool,
+    pub sell: AssetInfo,
     pub sell_amount: Uint128,
     pub initial_sell_amount: Uint128,
-    pub sell_denom: Option<String>,
-    pub sell_address: Option<Addr>,
     pub ask_for: Vec<AskFor>,
     pub expires: Expiration,
     pub user_info: Option<UserInfo>,
     pub description: Option<String>,
+    // only these addresses can take a private offer, empty for a public one
+    #[serde(default)]
+    pub allowed_takers: Vec<Addr>,
+    // hex encoded merkle root of the whitelisted takers and their allocations,
+    // the leaves are sha256 of the JSON array [address, allocation]
+    #[serde(default)]
+    pub whitelist_root: Option<String>,
+    // hex encoded sha256 of the passcode required to take the offer
+    #[serde(default)]
+    pub secret_hash: Option<String>,
+    // the asks are the floor price of the auction
+    #[serde(default)]
+    pub dutch_auction: Option<DutchAuction>,
+    // takers receive the tokens over this schedule
+    #[serde(default)]
+    pub vesting: Option<Vesting>,
+    // settled off-chain instead of with the asks
+    #[serde(default)]
+    pub fiat_escrow: Option<FiatEscrow>,
+    // released in tranches instead of sold for the asks
+    #[serde(default)]
+    pub milestones: Option<MilestoneEscrow>,
+    // the proceeds and refunds of the seller are sent over IBC
+    #[serde(default)]
+    pub ibc_recipient: Option<IbcRecipient>,
+    // the native asks are denoms of the counterparty chain and only paid through the channel
+    #[serde(default)]
+    pub remote: Option<RemoteOffer>,
+    // the sold cw20s stay with the seller and are pulled with an allowance on fills
+    #[serde(default)]
+    pub allowance: bool,
+    // the allowance no longer covered a fill
+    #[serde(default)]
+    pub invalid: bool,
+    // resolves disputes with the only allowed taker or the payee of the milestones
+    #[serde(default)]
+    pub arbitration: Option<Arbitration>,
+}
+
+
+impl OTCInfo {
+    /// The sold asset followed by the asked ones
+    pub fn assets(&self) -> impl Iterator<Item = &AssetInfo> {
+        std::iter::once(&self.sell).chain(self.ask_for.iter().map(|ask| &ask.info))
+    }
+
+    /// Whether the address can take the offer
+    pub fn is_allowed_taker(&self, taker: &Addr) -> bool {
+        self.allowed_takers.is_empty() || self.allowed_takers.contains(taker)
+    }
+
+    /// The party other than the seller of an offer that can be disputed
+    pub fn counterparty(&self) -> Option<&Addr> {
+        match (self.fiat_escrow.as_ref(), self.milestones.as_ref(), self.arbitration.as_ref()) {
+            (Some(escrow), _, _) => Some(&escrow.buyer),
+            (None, Some(milestones), Some(_)) => Some(&milestones.payee),
+            (None, None, Some(_)) => self.allowed_takers.first(),
+            _ => None
+        }
+    }
+
+    /// Whether the offer is frozen until its arbitrator resolves
+    pub fn is_disputed(&self) -> bool {
+        self.fiat_escrow.as_ref().is_some_and(|escrow| escrow.status == EscrowStatus::Disputed) ||
+            self.arbitration.as_ref().is_some_and(|arbitration| arbitration.disputed)
+    }
+
+    /// Asks at the current price of the offer
+    pub fn current_asks(&self, now: u64) -> Vec<AskFor> {
+        match self.dutch_auction.as_ref() {
+            Some(auction) => {
+                let multiplier = auction.price_multiplier(now);
+                self.ask_for
+                    .iter()
+                    .map(|ask| AskFor { amount: ask.amount * multiplier, ..ask.clone() })
+                    .collect()
+            },
+            None => self.ask_for.clone()
+        }
+    }
+
+    /// Releases the share of the offer bought with `paid` tokens of the ask at `ask_index`
+    /// and scales the remaining asks down by the same proportion. Returns the released amount
+    pub fn fill(&mut self, ask_index: usize, paid: Uint128, now: u64) -> Uint128 {
+        let to_pay = self.current_asks(now)[ask_index].amount;
+
+        // rounds down so that paying less than a whole unit releases nothing
+        let released = if paid >= to_pay {
+            self.sell_amount
+        } else {
+            self.sell_amount.multiply_ratio(paid, to_pay)
+        };
+
+        self.release(released)
+    }
+
+    /// Releases `amount` of the offer, reducing the asks by the same proportion
+    /// rounded down so the remaining asks never get cheaper. Returns the released amount
+    pub fn release(&mut self, amount: Uint128) -> Uint128 {
+        for ask in self.ask_for.iter_mut() {
+            ask.amount -= ask.amount.multiply_ratio(amount, self.sell_amount);
+        }
+
+        self.sell_amount -= amount;
+
+        amount
+    }
+}
+
+
+/// Escrowed proposal to buy from an offer at a different price
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+pub struct Counter {
+    pub otc_id: u32,
+    pub proposer: Addr,
+    // one of the ask tokens of the offer
+    pub asset: AssetInfo,
+    pub paid: Uint128,
+    // sell tokens asked for in exchange
+    pub amount: Uint128,
+    // the offer the counter was made against, ids of removed offers are reused
+    pub sell: AssetInfo,
+    pub initial_sell_amount: Uint128,
+}
+
+
+/// Hash time-locked offer for atomic swaps with other chains
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+pub struct HTLCInfo {
+    pub seller: CanonicalAddr,
+    pub taker: Addr,
+    pub sell: AssetInfo,
+    pub sell_amount: Uint128,
+    // hex encoded sha256 of the preimage
+    pub hashlock: String,
+    // can be claimed before and refunded after
+    pub timelock: Expiration,
+    pub description: Option<String>,
+}
+
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+pub struct Bid {
+    pub bidder: Addr,
+    pub amount: Uint128,
+}
+
+
+/// Ascending-bid auction with the bids held in escrow
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+pub struct AuctionInfo {
+    pub seller: CanonicalAddr,
+    // the lot
+    pub sell: AssetInfo,
+    pub sell_amount: Uint128,
+    // token of the bids
+    pub bid: AssetInfo,
+    pub min_bid: Uint128,
+    pub min_increment: Uint128,
+    pub highest_bid: Option<Bid>,
+    // seconds since epoch
+    pub end_time: u64,
+    // bids placed within this many seconds before the end push it back by the same amount
+    pub extension: u64,
+    pub description: Option<String>,
+}
+
+
+/// Commit-reveal auction where bids stay hidden until the bidding is over
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+pub struct SealedAuctionInfo {
+    pub seller: CanonicalAddr,
+    // the lot
+    pub sell: AssetInfo,
+    pub sell_amount: Uint128,
+    // token of the bids
+    pub bid: AssetInfo,
+    pub min_bid: Uint128,
+    // seconds since epoch
+    pub commit_end: u64,
+    pub reveal_end: u64,
+    // the winner pays the second highest bid instead of their own
+    pub second_price: bool,
+    // return deposits of bids that weren't revealed instead of giving them to the seller
+    pub refund_unrevealed: bool,
+    pub highest_bid: Option<Bid>,
+    pub second_bid: Option<Uint128>,
+    pub description: Option<String>,
+    // the winner has been paid and the other deposits are being withdrawn
+    #[serde(default)]
+    pub settled: bool,
+}
+
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+pub struct SealedBid {
+    // hex encoded sha256 of the JSON array [bidder, amount, salt]
+    pub commitment: String,
+    // must cover the bid to be valid
+    pub deposit: Uint128,
+    pub revealed: Option<Uint128>,
+}
+
+
+/// Escrowed fundraising selling tokens pro rata to the contributors
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+pub struct RaiseInfo {
+    pub creator: CanonicalAddr,
+    // tokens distributed to the contributors
+    pub sell: AssetInfo,
+    pub sell_amount: Uint128,
+    // token of the contributions
+    pub raise: AssetInfo,
+    // minimum to raise for the tokens to be distributed
+    pub soft_cap: Uint128,
+    // contributions above it are partially refunded
+    pub hard_cap: Uint128,
+    pub raised: Uint128,
+    // seconds since epoch
+    pub deadline: u64,
+    pub finalized: bool,
+    pub description: Option<String>,
+}
+
+
+impl RaiseInfo {
+    pub fn succeeded(&self) -> bool {
+        self.raised >= self.soft_cap
+    }
+
+    /// Amount raised that goes to the creator
+    pub fn accepted(&self) -> Uint128 {
+        self.raised.min(self.hard_cap)
+    }
+
+    /// Part of a contribution refunded when the raise is oversubscribed
+    pub fn excess(&self, contribution: Uint128) -> Uint128 {
+        if self.raised.is_zero() {
+            return Uint128::zero();
+        }
+        // rounded down so that the refunds never exceed the oversubscription
+        contribution.multiply_ratio(self.raised - self.accepted(), self.raised)
+    }
+
+    /// Tokens owed to a contributor
+    pub fn allocation(&self, contribution: Uint128) -> Uint128 {
+        if self.raised.is_zero() {
+            return Uint128::zero();
+        }
+        self.sell_amount.multiply_ratio(contribution, self.raised)
+    }
+}
+
+
+/// Pari-mutuel market where the stakes on the winning outcome share the losing pools
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+pub struct MarketInfo {
+    pub creator: CanonicalAddr,
+    pub question: String,
+    pub outcomes: Vec<String>,
+    // the only address that can post the result
+    pub resolver: Addr,
+    // seconds since epoch when staking closes
+    pub deadline: u64,
+    // token of the stakes
+    pub stake: AssetInfo,
+    pub min_stake: Uint128,
+    // total staked on each outcome
+    pub pools: Vec<Uint128>,
+    // index of the winning outcome
+    pub result: Option<u32>,
+}
+
+
+impl MarketInfo {
+    pub fn total_staked(&self) -> Uint128 {
+        self.pools.iter().sum()
+    }
 }
 
 
 pub const STATE: Item<State> = Item::new("state");
-pub const OTCS: Map<u32, OTCInfo> = Map::new("otcs");
\ No newline at end of file
+pub const OTCS: Map<u32, OTCInfo> = Map::new("otcs");
+// hash time-locked offers sharing ids with the regular ones
+pub const HTLCS: Map<u32, HTLCInfo> = Map::new("htlcs");
+pub const AUCTIONS: Map<u32, AuctionInfo> = Map::new("auctions");
+// shared by both kinds of auctions
+pub const AUCTION_INDEX: Item<u32> = Item::new("auction_index");
+pub const SEALED_AUCTIONS: Map<u32, SealedAuctionInfo> = Map::new("sealed_auctions");
+pub const SEALED_BIDS: Map<(u32, &Addr), SealedBid> = Map::new("sealed_bids");
+pub const RAISES: Map<u32, RaiseInfo> = Map::new("raises");
+pub const RAISE_INDEX: Item<u32> = Item::new("raise_index");
+pub const CONTRIBUTIONS: Map<(u32, &Addr), Uint128> = Map::new("raise_contributions");
+pub const VESTING_POSITIONS: Map<(&Addr, u32), VestingPosition> = Map::new("vesting_positions");
+pub const VESTING_INDEX: Item<u32> = Item::new("vesting_index");
+pub const COUNTERS: Map<u32, Counter> = Map::new("counters");
+pub const COUNTER_INDEX: Item<u32> = Item::new("counter_index");
+// (otc id, counter id) and (proposer, counter id) of the open counters
+pub const OTC_COUNTERS: Map<(u32, u32), Empty> = Map::new("otc_counters");
+pub const PROPOSER_COUNTERS: Map<(&Addr, u32), Empty> = Map::new("proposer_counters");
+pub const ARBITRATORS: Map<&Addr, ArbitratorInfo> = Map::new("arbitrators");
+pub const MARKETS: Map<u32, MarketInfo> = Map::new("markets");
+pub const MARKET_INDEX: Item<u32> = Item::new("market_index");
+// amounts staked by an address on each outcome of a market
+pub const STAKES: Map<(u32, &Addr), Vec<Uint128>> = Map::new("market_stakes");
+// amount of the sold token received by a whitelisted taker of an offer
+pub const WHITELIST_FILLED: Map<(u32, &Addr), Uint128> = Map::new("whitelist_filled");
+// connected PeerSwap channels
+pub const IBC_CHANNELS: Map<&str, IbcChannel> = Map::new("ibc_channels");
+pub const REMOTE_FILLS: Map<u32, RemoteFill> = Map::new("remote_fills");
+pub const REMOTE_FILL_INDEX: Item<u32> = Item::new("remote_fill_index");
+// payouts of the current message waiting for the sequences of their packets, in order
+pub const PENDING_TRANSFERS: Item<Vec<PendingTransfer>> = Item::new("pending_transfers");
+// (source channel, sequence) of payouts waiting for an acknowledgement
+pub const IBC_TRANSFERS: Map<(&str, u64), PendingTransfer> = Map::new("ibc_transfers");
+// secp256k1 public keys makers sign their off-chain orders with
+pub const ORDER_KEYS: Map<&Addr, Binary> = Map::new("order_keys");
+// (maker, nonce) of signed orders that were filled or cancelled
+pub const ORDER_NONCES: Map<(&Addr, u64), OrderStatus> = Map::new("order_nonces");
+
+pub const ASSET_LIST_MODE: Item<AssetListMode> = Item::new("asset_list_mode");
+// registered assets keyed by how they are displayed
+pub const ASSET_LIST: Map<String, AssetInfo> = Map::new("asset_list");
+// keyed by how the assets are displayed
+pub const TOKEN_METADATA: Map<String, TokenMetadata> = Map::new("token_metadata");
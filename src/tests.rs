@@ -1,16 +1,32 @@
 #[cfg(test)]
+#[allow(clippy::module_inception)]
 mod tests {
-    use crate::contract::{execute, instantiate, query};
+    use crate::contract::{execute, instantiate, query, reply, migrate};
+    use crate::ibc::{
+        ibc_channel_open, ibc_channel_connect, ibc_packet_receive, ibc_packet_ack, ibc_packet_timeout, sudo,
+        MsgTransfer, IBC_TRANSFER_REPLY, IBC_VERSION, MSG_TRANSFER_TYPE_URL
+    };
+    use crate::remote::{PeerSwapPacket, PacketAck, PACKET_VERSION};
     use crate::error::ContractError;
-    use crate::msg::{NewOTC, NewOTCResponse, ExecuteMsg, InstantiateMsg, QueryMsg, GetOTCsResponse};
-    use crate::state::{OTCInfo};
+    use crate::msg::{NewOTC, NewOTCResponse, ExecuteMsg, InstantiateMsg, QueryMsg, SudoMsg, IBCLifecycleComplete, GetOTCsResponse, SwapMsg, WhitelistProof, SimulateSwapResponse, RemoteSwapMsg, GetAssetListResponse};
+    use crate::utils::sha256;
+    use crate::asset::AssetInfo;
+    use crate::legacy::{LegacyOTCInfo, LegacyAskFor, LEGACY_OTCS};
+    use crate::state::{OTCInfo, DutchAuction, IbcRecipient, RemoteOffer, AssetListMode, PendingTransfer, IBC_TRANSFERS};
 
     use cosmwasm_std::testing::{
         mock_dependencies, mock_env, mock_info, mock_dependencies_with_balances, 
+        mock_ibc_packet_ack, mock_ibc_packet_timeout, mock_ibc_packet_recv,
+        mock_ibc_channel_open_init, mock_ibc_channel_open_try, mock_ibc_channel_connect_ack,
+    };
+    use cosmwasm_std::{coins, from_binary, DepsMut, Response, Uint128,  Coin, Deps, Api, Env, Addr, Decimal };
+    use cosmwasm_std::{
+        to_binary, BankMsg, CosmosMsg, Empty, Event, IbcMsg, IbcAcknowledgement, Reply, SubMsgResponse, SubMsgResult,
+        IbcOrder, IbcReceiveResponse, IbcBasicResponse
     };
-    use cosmwasm_std::{coins, from_binary, DepsMut, Response, Uint128,  Coin, Deps, Api, Env, Addr  };
     use cw20::Balance;
     use cw_utils::{NativeBalance, Expiration};
+    use prost::Message;
 
 
     fn sell_native_ask_native(deps : DepsMut, count: u32, expires: Option<Expiration>, sender : Option<&str>) {
@@ -27,15 +43,16 @@ mod tests {
         let info = mock_info(
             sender.unwrap_or("alice"),
             &coins(
-            sell_amount.clone(), 
-            sell_denom.clone()
+            sell_amount, 
+            sell_denom
         ));
         
         let msg = ExecuteMsg::Create(NewOTC {
-            ask_balances: vec![Balance::Native(NativeBalance(coins(ask_amount.clone(), ask_denom.clone())))],
+            ask_balances: vec![Balance::Native(NativeBalance(coins(ask_amount, ask_denom)))],
             expires,
             user_info: None,
             description: None,
+            ..Default::default()
         });
         
 
@@ -58,11 +75,10 @@ mod tests {
                 let info = res.otc;
 
                 assert_eq!(info.ask_for[0].amount, Uint128::from(ask_amount));
-                assert_eq!(info.ask_for[0].denom, Some(ask_denom.to_string()));
+                assert_eq!(info.ask_for[0].info, AssetInfo::Native(ask_denom.to_string()));
 
-                assert_eq!(info.sell_native, true);
                 assert_eq!(info.sell_amount, Uint128::from(sell_amount));
-                assert_eq!(info.sell_denom, Some(sell_denom.to_string()));
+                assert_eq!(info.sell, AssetInfo::Native(sell_denom.to_string()));
 
                 assert_eq!(info.expires, expires.unwrap_or_default() );
 
@@ -91,9 +107,10 @@ mod tests {
         
         
         let res = query(deps, env, QueryMsg::GetOtcs {
-            include_expired: include_expired,
-            limit: limit,
-            start_after: start_after,
+            include_expired,
+            limit,
+            start_after,
+            querier: None,
         }).unwrap();
         let value: GetOTCsResponse = from_binary(&res).unwrap();
         value
@@ -106,20 +123,20 @@ mod tests {
         for msg in res.messages.iter() {
             println!("{:?}", msg);
         }
-        println!("");
+        println!();
 
         println!("Attributes:");
         for attr in res.attributes.iter() {
             println!("{:?}", attr);
         }
-        println!("");
+        println!();
 
 
         println!("Events:");
         for event in res.events.iter() {
             println!("{:?}", event);
         }
-        println!("");
+        println!();
 
         println!("Data:");
         println!("{:?}", res.data);
@@ -143,6 +160,18 @@ mod tests {
         assert_eq!("admin", owner);
     }
 
+    #[test]
+    fn keeper_reward_within_fees() {
+        let mut deps = mock_dependencies();
+
+        let msg = InstantiateMsg { taker_fee: None, maker_fee: None, keeper_reward: Some(10001), arbitrator: None };
+        let err = instantiate(deps.as_mut(), mock_env(), mock_info("admin", &[]), msg).unwrap_err();
+        assert_eq!(err.to_string(), ContractError::InvalidSplit {}.to_string());
+
+        let msg = InstantiateMsg { taker_fee: None, maker_fee: None, keeper_reward: Some(10000), arbitrator: None };
+        instantiate(deps.as_mut(), mock_env(), mock_info("admin", &[]), msg).unwrap();
+    }
+
     #[test]
     fn can_create_and_swap_native() {
         let mut deps = mock_dependencies_with_balances(&[
@@ -160,7 +189,7 @@ mod tests {
         assert_eq!(query_otcs(deps.as_ref(), env.clone(), None, None, None).otcs.len(), 0);
 
 
-        sell_native_ask_native(deps.as_mut(), count.clone(), None, None);
+        sell_native_ask_native(deps.as_mut(), count, None, None);
 
 
         let otcs = query_otcs(deps.as_ref(), env.clone(), None, None, None).otcs;
@@ -170,7 +199,7 @@ mod tests {
         assert_eq!(id, &count);
 
         assert!(
-            otc.ask_for[0].amount == Uint128::from(10_000_000 as u128 ) &&
+            otc.ask_for[0].amount == Uint128::from(10_000_000_u128 ) &&
             deps.api.addr_humanize(&otc.seller).unwrap() == "alice",
         );
 
@@ -178,19 +207,17 @@ mod tests {
         let smaller_amount_info = mock_info("bob", &coins(1_000_000, "token_2"));
         let wrong_denom_info = mock_info("bob", &coins(10_000_000, "token_3"));
         let multiple_tokens_info = mock_info("bob", 
-            &vec!(
-                Coin { 
-                    amount: Uint128::from(10 as u8), 
+            &[Coin { 
+                    amount: Uint128::from(10_u8), 
                     denom: "token_2".to_string() 
                 }, 
                 Coin { 
-                    amount: Uint128::from(10 as u8), 
+                    amount: Uint128::from(10_u8), 
                     denom: "token_3".to_string() 
-                }
-            )
+                }]
         );
         //let bigger_amount_info = mock_info("bob", &coins(100, "token_2"));
-        let msg = ExecuteMsg::Swap { otc_id: count.clone() };
+        let msg = ExecuteMsg::Swap(SwapMsg { otc_id: count, ..Default::default() });
 
    
 
@@ -215,7 +242,7 @@ mod tests {
 
         // reduced amount
         assert!(
-            otc.ask_for[0].amount == Uint128::from(9_000_000 as u128 ) &&
+            otc.ask_for[0].amount == Uint128::from(9_000_000_u128 ) &&
             deps.api.addr_humanize(&otc.seller).unwrap() == "alice",
         );
 
@@ -365,9 +392,668 @@ mod tests {
     }
 
 
+    fn sell_token_2_ask_token_1(deps: DepsMut, sell_amount: u128, ask_amount: u128) {
+        let info = mock_info("bob", &coins(sell_amount, "token_2"));
+
+        let msg = ExecuteMsg::Create(NewOTC {
+            ask_balances: vec![Balance::Native(NativeBalance(coins(ask_amount, "token_1")))],
+            expires: None,
+            user_info: None,
+            description: None,
+            ..Default::default()
+        });
+
+        execute(deps, mock_env(), info, msg).unwrap();
+    }
+
+
+    #[test]
+    fn can_match() {
+        let mut deps = mock_dependencies();
+        let env = mock_env();
+        instantiate_contract(deps.as_mut());
+
+        // alice sells 5M token_1 for 10M token_2 (0.5 token_1 per token_2)
+        sell_native_ask_native(deps.as_mut(), 0, None, None);
+
+        // bob wants 0.75 token_1 per token_2 - too expensive
+        sell_token_2_ask_token_1(deps.as_mut(), 4_000_000, 3_000_000);
+
+        // bob wants 0.375 token_1 per token_2 - crosses alice's price
+        sell_token_2_ask_token_1(deps.as_mut(), 4_000_000, 1_500_000);
+
+        let keeper = mock_info("carol", &[]);
+
+        let res = execute(deps.as_mut(), env.clone(), keeper.clone(), ExecuteMsg::Match { maker_otc: 0, taker_otc: 1 }).unwrap_err();
+        assert_eq!(res.to_string(), ContractError::NotMatching {}.to_string());
+
+        let res = execute(deps.as_mut(), env.clone(), keeper.clone(), ExecuteMsg::Match { maker_otc: 0, taker_otc: 0 }).unwrap_err();
+        assert_eq!(res.to_string(), ContractError::NotMatching {}.to_string());
+
+        let res = execute(deps.as_mut(), env.clone(), keeper, ExecuteMsg::Match { maker_otc: 0, taker_otc: 2 }).unwrap();
+
+        assert_eq!(res.events[0].ty, "peerswap_match");
+        assert_eq!(res.events[0].attributes[2].value, "2000000");
+        assert_eq!(res.events[0].attributes[4].value, "4000000");
+
+        // two settlements plus the fees split between the admin and the keeper
+        assert_eq!(res.messages.len(), 6);
+
+        // bob's offer is fully taken and alice's one is filled at her price
+        let otcs = query_otcs(deps.as_ref(), env, None, None, None).otcs;
+        assert_eq!(otcs.len(), 2);
+
+        let (id, otc) = &otcs[0];
+        assert_eq!(id, &0);
+        assert_eq!(otc.sell_amount, Uint128::from(3_000_000u128));
+        assert_eq!(otc.ask_for[0].amount, Uint128::from(6_000_000u128));
+    }
+
+
+
+    #[test]
+    fn private_offers() {
+        let mut deps = mock_dependencies();
+        let env = mock_env();
+        instantiate_contract(deps.as_mut());
+
+        let msg = ExecuteMsg::Create(NewOTC {
+            ask_balances: vec![Balance::Native(NativeBalance(coins(10_000_000, "token_2")))],
+            allowed_takers: Some(vec![Addr::unchecked("bob")]),
+            ..Default::default()
+        });
+        execute(deps.as_mut(), env.clone(), mock_info("alice", &coins(5_000_000, "token_1")), msg).unwrap();
+
+        // hidden from the public listing
+        assert_eq!(query_otcs(deps.as_ref(), env.clone(), None, None, None).otcs.len(), 0);
+
+        for (querier, visible) in [("bob", 1), ("alice", 1), ("carol", 0)] {
+            let res = query(deps.as_ref(), env.clone(), QueryMsg::GetOtcs {
+                include_expired: None,
+                limit: None,
+                start_after: None,
+                querier: Some(Addr::unchecked(querier)),
+            }).unwrap();
+            let otcs: GetOTCsResponse = from_binary(&res).unwrap();
+            assert_eq!(otcs.otcs.len(), visible);
+        }
+
+        let msg = ExecuteMsg::Swap(SwapMsg { otc_id: 0, ..Default::default() });
+
+        let res = execute(deps.as_mut(), env.clone(), mock_info("carol", &coins(10_000_000, "token_2")), msg.clone()).unwrap_err();
+        assert_eq!(res.to_string(), ContractError::NotAllowedTaker {}.to_string());
+
+        let res = execute(deps.as_mut(), env, mock_info("bob", &coins(10_000_000, "token_2")), msg).unwrap();
+        assert_eq!(res.events[0].ty, "peerswap_swap_completed");
+    }
+
+
+
+    #[test]
+    fn whitelisted_offers() {
+        let mut deps = mock_dependencies();
+        let env = mock_env();
+        instantiate_contract(deps.as_mut());
+
+        let bob_leaf = sha256(&to_binary(&("bob", Uint128::from(1_000_000u128))).unwrap());
+        let carol_leaf = sha256(&to_binary(&("carol", Uint128::from(2_000_000u128))).unwrap());
+
+        let mut pair = [bob_leaf, carol_leaf];
+        pair.sort_unstable();
+        let root = sha256(&pair.concat());
+
+        let msg = ExecuteMsg::Create(NewOTC {
+            ask_balances: vec![Balance::Native(NativeBalance(coins(10_000_000, "token_2")))],
+            whitelist_root: Some("not a hash".to_string()),
+            ..Default::default()
+        });
+        let res = execute(deps.as_mut(), env.clone(), mock_info("alice", &coins(5_000_000, "token_1")), msg).unwrap_err();
+        assert_eq!(res.to_string(), ContractError::InvalidHash {}.to_string());
+
+        let msg = ExecuteMsg::Create(NewOTC {
+            ask_balances: vec![Balance::Native(NativeBalance(coins(10_000_000, "token_2")))],
+            whitelist_root: Some(hex::encode(root)),
+            ..Default::default()
+        });
+        execute(deps.as_mut(), env.clone(), mock_info("alice", &coins(5_000_000, "token_1")), msg).unwrap();
+
+        let swap = |allocation: u128, proof: &[u8; 32]| ExecuteMsg::Swap(SwapMsg {
+            otc_id: 0,
+            whitelist: Some(WhitelistProof {
+                allocation: Uint128::from(allocation),
+                proof: vec![hex::encode(proof)]
+            }),
+            ..Default::default()
+        });
+
+        // no proof
+        let res = execute(deps.as_mut(), env.clone(), mock_info("bob", &coins(2_000_000, "token_2")), ExecuteMsg::Swap(SwapMsg { otc_id: 0, ..Default::default() })).unwrap_err();
+        assert_eq!(res.to_string(), ContractError::NotAllowedTaker {}.to_string());
+
+        // claiming a bigger allocation
+        let res = execute(deps.as_mut(), env.clone(), mock_info("bob", &coins(2_000_000, "token_2")), swap(2_000_000, &carol_leaf)).unwrap_err();
+        assert_eq!(res.to_string(), ContractError::InvalidProof {}.to_string());
+
+        // 10M token_2 buys the whole 5M token_1 which is more than the allocation
+        let res = execute(deps.as_mut(), env.clone(), mock_info("bob", &coins(10_000_000, "token_2")), swap(1_000_000, &carol_leaf)).unwrap_err();
+        assert_eq!(res.to_string(), ContractError::AllocationExceeded {}.to_string());
+
+        let res = execute(deps.as_mut(), env.clone(), mock_info("bob", &coins(2_000_000, "token_2")), swap(1_000_000, &carol_leaf)).unwrap();
+        assert_eq!(res.events[0].attributes[2].value, "1000000");
+
+        // the allocation has been used up
+        let res = execute(deps.as_mut(), env.clone(), mock_info("bob", &coins(20_000, "token_2")), swap(1_000_000, &carol_leaf)).unwrap_err();
+        assert_eq!(res.to_string(), ContractError::AllocationExceeded {}.to_string());
+
+        execute(deps.as_mut(), env.clone(), mock_info("carol", &coins(4_000_000, "token_2")), swap(2_000_000, &bob_leaf)).unwrap();
+
+        // a new offer reusing the id starts with fresh allocations
+        execute(deps.as_mut(), env.clone(), mock_info("alice", &[]), ExecuteMsg::Cancel { otc_id: 0 }).unwrap();
+
+        let msg = ExecuteMsg::Create(NewOTC {
+            ask_balances: vec![Balance::Native(NativeBalance(coins(10_000_000, "token_2")))],
+            whitelist_root: Some(hex::encode(root)),
+            ..Default::default()
+        });
+        execute(deps.as_mut(), env.clone(), mock_info("alice", &coins(5_000_000, "token_1")), msg).unwrap();
+
+        execute(deps.as_mut(), env, mock_info("bob", &coins(2_000_000, "token_2")), swap(1_000_000, &carol_leaf)).unwrap();
+    }
+
+
+
+    #[test]
+    fn passcode_offers() {
+        let mut deps = mock_dependencies();
+        let env = mock_env();
+        instantiate_contract(deps.as_mut());
+
+        let msg = ExecuteMsg::Create(NewOTC {
+            ask_balances: vec![Balance::Native(NativeBalance(coins(10_000_000, "token_2")))],
+            secret_hash: Some(hex::encode(sha256(b"open sesame"))),
+            ..Default::default()
+        });
+        execute(deps.as_mut(), env.clone(), mock_info("alice", &coins(5_000_000, "token_1")), msg).unwrap();
+
+        let swap = |secret: Option<&str>| ExecuteMsg::Swap(SwapMsg {
+            otc_id: 0,
+            secret: secret.map(String::from),
+            ..Default::default()
+        });
+
+        for secret in [None, Some("open sesame!")] {
+            let res = execute(deps.as_mut(), env.clone(), mock_info("bob", &coins(10_000_000, "token_2")), swap(secret)).unwrap_err();
+            assert_eq!(res.to_string(), ContractError::WrongSecret {}.to_string());
+        }
+
+        let res = execute(deps.as_mut(), env, mock_info("bob", &coins(10_000_000, "token_2")), swap(Some("open sesame"))).unwrap();
+        assert_eq!(res.events[0].ty, "peerswap_swap_completed");
+    }
+
+
+
+    #[test]
+    fn dutch_auction_price_decays() {
+        let mut deps = mock_dependencies();
+        let mut env = mock_env();
+        instantiate_contract(deps.as_mut());
+
+        let start = env.block.time.seconds();
+
+        let auction = |start_multiplier: u64, step: Option<u64>| ExecuteMsg::Create(NewOTC {
+            ask_balances: vec![Balance::Native(NativeBalance(coins(10_000_000, "token_2")))],
+            dutch_auction: Some(DutchAuction {
+                start_time: start,
+                end_time: start + 1000,
+                start_multiplier: Decimal::from_ratio(start_multiplier, 1u8),
+                step
+            }),
+            ..Default::default()
+        });
+
+        let res = execute(deps.as_mut(), env.clone(), mock_info("alice", &coins(5_000_000, "token_1")), auction(0, None)).unwrap_err();
+        assert_eq!(res.to_string(), ContractError::InvalidAuction {}.to_string());
+
+        execute(deps.as_mut(), env.clone(), mock_info("alice", &coins(5_000_000, "token_1")), auction(2, None)).unwrap();
+        execute(deps.as_mut(), env.clone(), mock_info("alice", &coins(5_000_000, "token_1")), auction(2, Some(300))).unwrap();
+
+        let simulate = |env: &Env, otc_id: u32, amount: u128| -> SimulateSwapResponse {
+            from_binary(&query(deps.as_ref(), env.clone(), QueryMsg::SimulateSwap { 
+                otc_id, 
+                token: "token_2".to_string(), 
+                amount: Uint128::from(amount) 
+            }).unwrap()).unwrap()
+        };
+
+        // twice the floor price at the start
+        let res = simulate(&env, 0, 10_000_000);
+        assert_eq!(res.ask_for[0].amount, Uint128::from(20_000_000u128));
+        assert_eq!(res.given_amount, Uint128::from(2_500_000u128));
+
+        // linear decay halfway through
+        env.block.time = env.block.time.plus_seconds(500);
+        let res = simulate(&env, 0, 15_000_000);
+        assert_eq!(res.ask_for[0].amount, Uint128::from(15_000_000u128));
+        assert_eq!(res.given_amount, Uint128::from(5_000_000u128));
+
+        // stepwise decay only dropped once
+        let res = simulate(&env, 1, 10_000_000);
+        assert_eq!(res.ask_for[0].amount, Uint128::from(17_000_000u128));
+
+        // the floor price after the end
+        env.block.time = env.block.time.plus_seconds(1000);
+        let msg = ExecuteMsg::Swap(SwapMsg { otc_id: 0, ..Default::default() });
+        let res = execute(deps.as_mut(), env, mock_info("bob", &coins(5_000_000, "token_2")), msg).unwrap();
+        assert_eq!(res.events[0].attributes[2].value, "2500000");
+    }
+
+
+    // channel, receiver, coin and memo of a MsgTransfer
+    fn ibc_transfer(msg: &CosmosMsg) -> (String, String, Coin, String) {
+        match msg {
+            CosmosMsg::Stargate { type_url, value } if type_url == MSG_TRANSFER_TYPE_URL => {
+                let transfer = MsgTransfer::decode(value.as_slice()).unwrap();
+                let token = transfer.token.unwrap();
+                (
+                    transfer.source_channel, 
+                    transfer.receiver, 
+                    Coin::new(token.amount.parse().unwrap(), token.denom), 
+                    transfer.memo
+                )
+            },
+            _ => panic!("not an ibc transfer")
+        }
+    }
+
+
+    #[test]
+    fn failed_ibc_payouts_refunded() {
+        let mut deps = mock_dependencies();
+        let env = mock_env();
+        instantiate_contract(deps.as_mut());
+
+        let msg = ExecuteMsg::Create(NewOTC {
+            ask_balances: vec![Balance::Native(NativeBalance(coins(15_000_000, "token_2")))],
+            ..Default::default()
+        });
+        execute(deps.as_mut(), env.clone(), mock_info("alice", &coins(7_500_000, "token_1")), msg).unwrap();
+
+        let swap = |otc_id: u32, channel: &str| ExecuteMsg::Swap(SwapMsg {
+            otc_id,
+            ibc_recipient: Some(IbcRecipient { channel: channel.to_string(), address: "osmo1bob".to_string(), timeout: 600 }),
+            ..Default::default()
+        });
+
+        let sent = |deps: DepsMut, channel: &str, sequence: u64| reply(deps, mock_env(), Reply {
+            id: IBC_TRANSFER_REPLY,
+            result: SubMsgResult::Ok(SubMsgResponse {
+                events: vec![Event::new("send_packet")
+                    .add_attribute("packet_src_channel", channel)
+                    .add_attribute("packet_sequence", sequence.to_string())],
+                data: None
+            })
+        }).unwrap();
+
+        let ack = |deps: DepsMut, channel: &str, sequence: u64, success: bool| sudo(deps, mock_env(), 
+            SudoMsg::IBCLifecycleComplete(IBCLifecycleComplete::IBCAck { 
+                channel: channel.to_string(), 
+                sequence, 
+                ack: String::new(), 
+                success 
+            })
+        ).unwrap();
+
+        // the refund matches the coin sent over IBC
+        let refund = |res: &Response, to: &str| {
+            let (_, _, coin, _) = ibc_transfer(&res.messages.iter().find(|msg| msg.id == IBC_TRANSFER_REPLY).unwrap().msg);
+            CosmosMsg::from(BankMsg::Send { to_address: to.to_string(), amount: vec![coin] })
+        };
+
+        // the payout is sent over IBC with a callback of its outcome and refunded on an error ack
+        let res = execute(deps.as_mut(), env.clone(), mock_info("bob", &coins(5_000_000, "token_2")), swap(0, "channel-1")).unwrap();
+        let (channel, receiver, _, memo) = ibc_transfer(&res.messages.iter().find(|msg| msg.id == IBC_TRANSFER_REPLY).unwrap().msg);
+        assert_eq!((channel.as_str(), receiver.as_str()), ("channel-1", "osmo1bob"));
+        assert_eq!(memo, format!(r#"{{"ibc_callback":"{}"}}"#, env.contract.address));
+        let refunded = refund(&res, "bob");
+        sent(deps.as_mut(), "channel-1", 29);
+
+        let res = ack(deps.as_mut(), "channel-1", 29, false);
+        assert_eq!(res.messages[0].msg, refunded);
+
+        // only refunded once
+        let res = ack(deps.as_mut(), "channel-1", 29, false);
+        assert!(res.messages.is_empty());
+
+        // and on a timeout
+        let res = execute(deps.as_mut(), env.clone(), mock_info("bob", &coins(5_000_000, "token_2")), swap(0, "channel-2")).unwrap();
+        let refunded = refund(&res, "bob");
+        sent(deps.as_mut(), "channel-2", 29);
+
+        let timeout = SudoMsg::IBCLifecycleComplete(IBCLifecycleComplete::IBCTimeout { channel: "channel-2".to_string(), sequence: 29 });
+        let res = sudo(deps.as_mut(), env.clone(), timeout).unwrap();
+        assert_eq!(res.messages[0].msg, refunded);
+
+        // nothing to refund after a successful transfer
+        execute(deps.as_mut(), env.clone(), mock_info("bob", &coins(5_000_000, "token_2")), swap(0, "channel-3")).unwrap();
+        sent(deps.as_mut(), "channel-3", 29);
+
+        let res = ack(deps.as_mut(), "channel-3", 29, true);
+        assert!(res.messages.is_empty());
+        assert_eq!(res.attributes[0].value, "ibc_transfer_acknowledged");
+
+        // the payouts of sellers with a remote recipient are tracked as well, in the order they are sent
+        let msg = ExecuteMsg::Create(NewOTC {
+            ask_balances: vec![Balance::Native(NativeBalance(coins(15_000_000, "token_2")))],
+            ibc_recipient: Some(IbcRecipient { channel: "channel-0".to_string(), address: "cosmos1alice".to_string(), timeout: 600 }),
+            ..Default::default()
+        });
+        let res = execute(deps.as_mut(), env.clone(), mock_info("alice", &coins(7_500_000, "token_1")), msg).unwrap();
+        let otc: NewOTCResponse = from_binary(&res.data.unwrap()).unwrap();
+
+        let res = execute(deps.as_mut(), env.clone(), mock_info("bob", &coins(5_000_000, "token_2")), swap(otc.id, "channel-1")).unwrap();
+        let payouts: Vec<_> = res.messages.iter().filter(|msg| msg.id == IBC_TRANSFER_REPLY).collect();
+        assert_eq!(payouts.len(), 2);
+        let (channel, receiver, paid, _) = ibc_transfer(&payouts[0].msg);
+        assert_eq!((channel.as_str(), receiver.as_str()), ("channel-0", "cosmos1alice"));
+        sent(deps.as_mut(), "channel-0", 30);
+        sent(deps.as_mut(), "channel-1", 31);
+
+        let res = ack(deps.as_mut(), "channel-0", 30, false);
+        assert_eq!(res.messages[0].msg, CosmosMsg::from(BankMsg::Send { to_address: "alice".to_string(), amount: vec![paid] }));
+        let res = ack(deps.as_mut(), "channel-1", 31, true);
+        assert_eq!(res.attributes[0].value, "ibc_transfer_acknowledged");
+
+        // payouts of ibc-hooks intermediaries are refunded over IBC
+        let recipient = IbcRecipient { channel: "channel-4".to_string(), address: "osmo1bob".to_string(), timeout: 600 };
+        IBC_TRANSFERS.save(deps.as_mut().storage, ("channel-4", 29), &PendingTransfer {
+            refund_address: Addr::unchecked("intermediary"),
+            coin: Coin::new(1_000, "token_1"),
+            refund_recipient: Some(recipient),
+        }).unwrap();
+
+        let res = ack(deps.as_mut(), "channel-4", 29, false);
+        let (channel, receiver, coin, _) = ibc_transfer(&res.messages[0].msg);
+        assert_eq!((channel.as_str(), receiver.as_str(), coin), ("channel-4", "osmo1bob", Coin::new(1_000, "token_1")));
+    }
+
+
+    #[test]
+    fn cross_chain_fills_settled_and_rolled_back() {
+        let mut chain_a = mock_dependencies();
+        let mut chain_b = mock_dependencies();
+        let env = mock_env();
+        instantiate_contract(chain_a.as_mut());
+        instantiate_contract(chain_b.as_mut());
+
+        // only unordered channels of the same version
+        let err = ibc_channel_open(chain_a.as_mut(), env.clone(), mock_ibc_channel_open_try("channel-a", IbcOrder::Ordered, IBC_VERSION)).unwrap_err();
+        assert_eq!(err.to_string(), ContractError::InvalidIbcChannel {}.to_string());
+        let err = ibc_channel_open(chain_a.as_mut(), env.clone(), mock_ibc_channel_open_init("channel-a", IbcOrder::Unordered, "ics20-1")).unwrap_err();
+        assert_eq!(err.to_string(), ContractError::InvalidIbcChannel {}.to_string());
+
+        for (deps, channel) in [(chain_a.as_mut(), "channel-a"), (chain_b.as_mut(), "channel-b")] {
+            ibc_channel_open(deps, env.clone(), mock_ibc_channel_open_try(channel, IbcOrder::Unordered, IBC_VERSION)).unwrap();
+        }
+        ibc_channel_connect(chain_a.as_mut(), env.clone(), mock_ibc_channel_connect_ack("channel-a", IbcOrder::Unordered, IBC_VERSION)).unwrap();
+        ibc_channel_connect(chain_b.as_mut(), env.clone(), mock_ibc_channel_connect_ack("channel-b", IbcOrder::Unordered, IBC_VERSION)).unwrap();
+
+        // listed on chain a for takers on chain b
+        let alice_b = "cosmos1qypqxpq9qcrsszg2pvxq6rs0zqg3yyc5lzv7xu";
+        let offer = |channel: &str, seller: &str| ExecuteMsg::Create(NewOTC {
+            ask_balances: vec![Balance::Native(NativeBalance(coins(10_000_000, "ub")))],
+            remote: Some(RemoteOffer { channel: channel.to_string(), seller: seller.to_string() }),
+            ..Default::default()
+        });
+
+        let err = execute(chain_a.as_mut(), env.clone(), mock_info("alice", &coins(5_000_000, "token_1")), offer("channel-z", alice_b)).unwrap_err();
+        assert_eq!(err.to_string(), ContractError::WrongChannel {}.to_string());
+
+        // the seller can't be validated on this chain but must look like an address
+        for seller in ["", "alice_b", "cosmos1", "Cosmos1qypqxpq9qcrsszg2pvxq6rs0zqg3yyc5lzv7xu", "cosmos1qypqxpq9qcrsszg2pvxq6rs0zqg3yyc5lzv7xi"] {
+            let err = execute(chain_a.as_mut(), env.clone(), mock_info("alice", &coins(5_000_000, "token_1")), offer("channel-a", seller)).unwrap_err();
+            assert_eq!(err.to_string(), ContractError::InvalidRemoteOffer {}.to_string());
+        }
+
+        execute(chain_a.as_mut(), env.clone(), mock_info("alice", &coins(5_000_000, "token_1")), offer("channel-a", alice_b)).unwrap();
+
+        let err = execute(chain_a.as_mut(), env.clone(), mock_info("bob", &coins(10_000_000, "ub")), 
+            ExecuteMsg::Swap(SwapMsg { otc_id: 0, ..Default::default() })).unwrap_err();
+        assert_eq!(err.to_string(), ContractError::RemoteOnly {}.to_string());
+
+        let remote_swap = |deps: DepsMut, funds: &[Coin]| -> PeerSwapPacket {
+            let msg = ExecuteMsg::RemoteSwap(RemoteSwapMsg {
+                channel: "channel-b".to_string(),
+                otc_id: 0,
+                recipient: "bob_a".to_string(),
+                timeout: 600
+            });
+            let res = execute(deps, mock_env(), mock_info("bob", funds), msg).unwrap();
+            match &res.messages[0].msg {
+                CosmosMsg::Ibc(IbcMsg::SendPacket { channel_id, data, .. }) if channel_id == "channel-b" => from_binary(data).unwrap(),
+                _ => panic!("no packet sent")
+            }
+        };
+
+        let receive = |deps: DepsMut, packet: &PeerSwapPacket| -> (IbcReceiveResponse, PacketAck) {
+            let res = ibc_packet_receive(deps, mock_env(), mock_ibc_packet_recv("channel-a", packet).unwrap()).unwrap();
+            let ack = from_binary(&res.acknowledgement).unwrap();
+            (res, ack)
+        };
+
+        let acknowledge = |deps: DepsMut, packet: &PeerSwapPacket, ack: &PacketAck| -> IbcBasicResponse {
+            let ack = IbcAcknowledgement::new(to_binary(ack).unwrap());
+            ibc_packet_ack(deps, mock_env(), mock_ibc_packet_ack("channel-b", packet, ack).unwrap()).unwrap()
+        };
+
+        // half filled on chain a and the escrow released to the seller on chain b
+        let packet = remote_swap(chain_b.as_mut(), &coins(5_000_000, "ub"));
+        assert_eq!(packet.version, PACKET_VERSION);
+
+        let (res, ack) = receive(chain_a.as_mut(), &packet);
+        let given = match &ack {
+            PacketAck::Result(filled) => {
+                assert_eq!(filled.seller, alice_b);
+                filled.given_amount
+            },
+            PacketAck::Error(err) => panic!("{}", err)
+        };
+        let fee = given * Decimal::from_ratio(2u8, 10000u16);
+        assert_eq!(res.messages[0].msg, BankMsg::Send { to_address: "bob_a".to_string(), amount: coins((given - fee).u128(), "token_1") }.into());
+
+        let res = acknowledge(chain_b.as_mut(), &packet, &ack);
+        assert_eq!(res.messages[0].msg, BankMsg::Send { to_address: alice_b.to_string(), amount: coins(4_999_000, "ub") }.into());
+        assert!(query(chain_b.as_ref(), env.clone(), QueryMsg::GetRemoteFill { fill_id: 0 }).is_err());
+
+        let otc : OTCInfo = from_binary(&query(chain_a.as_ref(), env.clone(), QueryMsg::GetOtc { otc_id: 0 }).unwrap()).unwrap();
+        let remaining = otc.sell_amount;
+        assert_eq!(remaining, Uint128::from(5_000_000u128) - given);
+
+        // a failed fill leaves the offer untouched and refunds the taker
+        let packet = remote_swap(chain_b.as_mut(), &coins(5_000_000, "uother"));
+        let (res, ack) = receive(chain_a.as_mut(), &packet);
+        assert!(res.messages.is_empty());
+        assert_eq!(ack, PacketAck::Error(ContractError::WrongDenom {}.to_string()));
+
+        let otc : OTCInfo = from_binary(&query(chain_a.as_ref(), env.clone(), QueryMsg::GetOtc { otc_id: 0 }).unwrap()).unwrap();
+        assert_eq!(otc.sell_amount, remaining);
+
+        let res = acknowledge(chain_b.as_mut(), &packet, &ack);
+        assert_eq!(res.messages[0].msg, BankMsg::Send { to_address: "bob".to_string(), amount: coins(5_000_000, "uother") }.into());
+
+        // and so does a timeout
+        let packet = remote_swap(chain_b.as_mut(), &coins(5_000_000, "ub"));
+        let timeout = mock_ibc_packet_timeout("channel-b", &packet).unwrap();
+        let res = ibc_packet_timeout(chain_b.as_mut(), env.clone(), timeout).unwrap();
+        assert_eq!(res.messages[0].msg, BankMsg::Send { to_address: "bob".to_string(), amount: coins(5_000_000, "ub") }.into());
+
+        // unknown packet versions are rejected
+        let (_, ack) = receive(chain_a.as_mut(), &PeerSwapPacket { version: 2, ..packet });
+        assert_eq!(ack, PacketAck::Error(ContractError::InvalidPacket {}.to_string()));
+
+        // acks with a seller that can't be paid still release the escrow
+        let packet = remote_swap(chain_b.as_mut(), &coins(5_000_000, "ub"));
+        let ack = match receive(chain_a.as_mut(), &packet).1 {
+            PacketAck::Result(mut filled) => {
+                filled.seller = "x".to_string();
+                PacketAck::Result(filled)
+            },
+            PacketAck::Error(err) => panic!("{}", err)
+        };
+        let res = acknowledge(chain_b.as_mut(), &packet, &ack);
+        assert_eq!(res.messages[0].msg, BankMsg::Send { to_address: "bob".to_string(), amount: coins(5_000_000, "ub") }.into());
+    }
+
+
+
+    #[test]
+    fn legacy_offers_migrated_to_typed_assets() {
+        let mut deps = mock_dependencies();
+        instantiate_contract(deps.as_mut());
+
+        cw2::set_contract_version(deps.as_mut().storage, "crates.io:peerswap", "0.3.3").unwrap();
+
+        let legacy = LegacyOTCInfo {
+            seller: deps.api.addr_canonicalize("alice").unwrap(),
+            sell_native: true,
+            sell_amount: Uint128::from(5_000_000u128),
+            initial_sell_amount: Uint128::from(5_000_000u128),
+            sell_denom: Some("token_1".to_string()),
+            sell_address: None,
+            ask_for: vec![LegacyAskFor {
+                initial_amount: Uint128::from(10_000_000u128),
+                amount: Uint128::from(10_000_000u128),
+                denom: None,
+                address: Some(Addr::unchecked("cw20")),
+                native: false,
+            }],
+            expires: Expiration::Never {},
+            user_info: None,
+            description: Some("legacy".to_string()),
+        };
+        LEGACY_OTCS.save(deps.as_mut().storage, 0, &legacy).unwrap();
+
+        // the new layout can't read it before the migration
+        assert!(query(deps.as_ref(), mock_env(), QueryMsg::GetOtc { otc_id: 0 }).is_err());
+
+        let res = migrate(deps.as_mut(), mock_env(), Empty {}).unwrap();
+        assert_eq!(res.attributes[0].value, "1");
+
+        let otc : OTCInfo = from_binary(&query(deps.as_ref(), mock_env(), QueryMsg::GetOtc { otc_id: 0 }).unwrap()).unwrap();
+        assert_eq!(otc.sell, AssetInfo::Native("token_1".to_string()));
+        assert_eq!(otc.ask_for[0].info, AssetInfo::Cw20(Addr::unchecked("cw20")));
+        assert_eq!(otc.ask_for[0].amount, Uint128::from(10_000_000u128));
+        assert_eq!(otc.description, Some("legacy".to_string()));
+
+        // migrating again leaves the offers alone
+        let res = migrate(deps.as_mut(), mock_env(), Empty {}).unwrap();
+        assert!(res.attributes.is_empty());
+    }
+
+
+    #[test]
+    fn asset_list_restricts_offers() {
+        let mut deps = mock_dependencies();
+        let env = mock_env();
+        instantiate_contract(deps.as_mut());
+
+        let scam = AssetInfo::Native("ibc/SCAM".to_string());
+
+        let msg = ExecuteMsg::RegisterAssets { assets: vec![scam.clone()] };
+        let err = execute(deps.as_mut(), env.clone(), mock_info("alice", &[]), msg.clone()).unwrap_err();
+        assert_eq!(err.to_string(), ContractError::Unauthorized {}.to_string());
+        execute(deps.as_mut(), env.clone(), mock_info("admin", &[]), msg).unwrap();
+
+        // registered assets aren't enforced while the list is open
+        sell_native_ask_native(deps.as_mut(), 0, None, None);
+
+        let msg = ExecuteMsg::SetAssetListMode { mode: AssetListMode::Denylist };
+        execute(deps.as_mut(), env.clone(), mock_info("admin", &[]), msg).unwrap();
+
+        let create = |ask: &str| ExecuteMsg::Create(NewOTC {
+            ask_balances: vec![Balance::Native(NativeBalance(coins(10_000_000, ask)))],
+            ..Default::default()
+        });
+
+        let err = execute(deps.as_mut(), env.clone(), mock_info("alice", &coins(5_000_000, "token_1")), create("ibc/SCAM")).unwrap_err();
+        assert_eq!(err.to_string(), ContractError::AssetNotAllowed { asset: "ibc/SCAM".to_string() }.to_string());
+
+        let err = execute(deps.as_mut(), env.clone(), mock_info("alice", &coins(5_000_000, "ibc/SCAM")), create("token_2")).unwrap_err();
+        assert_eq!(err.to_string(), ContractError::AssetNotAllowed { asset: "ibc/SCAM".to_string() }.to_string());
+
+        // and so does every other kind of offer
+        let auction = ExecuteMsg::CreateAuction(crate::msg::NewAuction {
+            min_bid: Balance::Native(NativeBalance(coins(1_000_000, "ibc/SCAM"))),
+            min_increment: None,
+            end_time: env.block.time.seconds() + 100,
+            extension: None,
+            description: None,
+        });
+        let err = execute(deps.as_mut(), env.clone(), mock_info("alice", &coins(5_000_000, "token_1")), auction).unwrap_err();
+        assert_eq!(err.to_string(), ContractError::AssetNotAllowed { asset: "ibc/SCAM".to_string() }.to_string());
+
+        let raise = ExecuteMsg::CreateRaise(crate::msg::NewRaise {
+            soft_cap: Balance::Native(NativeBalance(coins(1_000_000, "token_2"))),
+            hard_cap: Uint128::from(2_000_000u128),
+            deadline: env.block.time.seconds() + 100,
+            description: None,
+        });
+        let err = execute(deps.as_mut(), env.clone(), mock_info("alice", &coins(5_000_000, "ibc/SCAM")), raise).unwrap_err();
+        assert_eq!(err.to_string(), ContractError::AssetNotAllowed { asset: "ibc/SCAM".to_string() }.to_string());
+
+        // only the registered assets can be traded with an allowlist
+        let msg = ExecuteMsg::SetAssetListMode { mode: AssetListMode::Allowlist };
+        execute(deps.as_mut(), env.clone(), mock_info("admin", &[]), msg).unwrap();
+
+        let swap = ExecuteMsg::Swap(SwapMsg { otc_id: 0, ..Default::default() });
+        let err = execute(deps.as_mut(), env.clone(), mock_info("bob", &coins(10_000_000, "token_2")), swap.clone()).unwrap_err();
+        assert_eq!(err.to_string(), ContractError::AssetNotAllowed { asset: "token_1".to_string() }.to_string());
+
+        let assets = vec![AssetInfo::Native("token_1".to_string()), AssetInfo::Native("token_2".to_string())];
+        let msg = ExecuteMsg::RegisterAssets { assets };
+        execute(deps.as_mut(), env.clone(), mock_info("admin", &[]), msg).unwrap();
+
+        let res = execute(deps.as_mut(), env.clone(), mock_info("bob", &coins(10_000_000, "token_2")), swap).unwrap();
+        assert_eq!(res.events[0].ty, "peerswap_swap_completed");
+
+        let msg = ExecuteMsg::RemoveAssets { assets: vec![scam] };
+        execute(deps.as_mut(), env.clone(), mock_info("admin", &[]), msg).unwrap();
+
+        let res = query(deps.as_ref(), env, QueryMsg::GetAssetList { start_after: None, limit: None }).unwrap();
+        let list : GetAssetListResponse = from_binary(&res).unwrap();
+        assert_eq!(list.mode, AssetListMode::Allowlist);
+        assert_eq!(list.assets, vec![AssetInfo::Native("token_1".to_string()), AssetInfo::Native("token_2".to_string())]);
+    }
+
+
+
+    #[test]
+    fn blocked_assets_cant_be_matched() {
+        let mut deps = mock_dependencies();
+        let env = mock_env();
+        instantiate_contract(deps.as_mut());
+
+        sell_native_ask_native(deps.as_mut(), 0, None, None);
+        sell_token_2_ask_token_1(deps.as_mut(), 4_000_000, 1_500_000);
+
+        // blocked after both offers were listed
+        let msg = ExecuteMsg::SetAssetListMode { mode: AssetListMode::Denylist };
+        execute(deps.as_mut(), env.clone(), mock_info("admin", &[]), msg).unwrap();
+        let msg = ExecuteMsg::RegisterAssets { assets: vec![AssetInfo::Native("token_2".to_string())] };
+        execute(deps.as_mut(), env.clone(), mock_info("admin", &[]), msg).unwrap();
+
+        let match_msg = ExecuteMsg::Match { maker_otc: 0, taker_otc: 1 };
+        let err = execute(deps.as_mut(), env.clone(), mock_info("carol", &[]), match_msg.clone()).unwrap_err();
+        assert_eq!(err.to_string(), ContractError::AssetNotAllowed { asset: "token_2".to_string() }.to_string());
+
+        let msg = ExecuteMsg::RemoveAssets { assets: vec![AssetInfo::Native("token_2".to_string())] };
+        execute(deps.as_mut(), env.clone(), mock_info("admin", &[]), msg).unwrap();
+
+        execute(deps.as_mut(), env, mock_info("carol", &[]), match_msg).unwrap();
+    }
+
 
     fn instantiate_contract(deps: DepsMut) -> Response {
-        let msg = InstantiateMsg { taker_fee: Some(2), maker_fee: Some(2)};
+        let msg = InstantiateMsg { taker_fee: Some(2), maker_fee: Some(2), keeper_reward: Some(5000), arbitrator: None };
         let info = mock_info("admin", &[]);
         instantiate(deps, mock_env(), info, msg).unwrap()
     } 
@@ -7,4 +7,41 @@ pub fn parse_otcs(item: StdResult<(u8, OTCInfo)>) -> StdResult<(u32, OTCInfo)> {
         (u32::from_le_bytes(key),
         info)
     })
-} */
\ No newline at end of file
+} */
+
+use sha2::{Digest, Sha256};
+
+use crate::error::ContractError;
+
+
+pub fn sha256(data: &[u8]) -> [u8; 32] {
+    Sha256::digest(data).into()
+}
+
+
+/// Decodes a hex string of exactly 32 bytes
+pub fn decode_hash(hash: &str) -> Option<[u8; 32]> {
+    let mut buf = [0u8; 32];
+    hex::decode_to_slice(hash, &mut buf).ok()?;
+    Some(buf)
+}
+
+
+/// Checks that the leaf belongs to the tree with the given root.
+/// Pairs are sorted before hashing so the proof doesn't need to carry positions
+pub fn verify_merkle_proof(root: &str, leaf: &[u8], proof: &[String]) -> Result<(), ContractError> {
+    let root = decode_hash(root).ok_or(ContractError::InvalidProof {})?;
+
+    let hash = proof.iter().try_fold(sha256(leaf), |hash, sibling| -> Result<[u8; 32], ContractError> {
+        let sibling = decode_hash(sibling).ok_or(ContractError::InvalidProof {})?;
+        let mut pair = [hash, sibling];
+        pair.sort_unstable();
+        Ok(sha256(&pair.concat()))
+    })?;
+
+    if hash != root {
+        return Err(ContractError::InvalidProof {});
+    }
+
+    Ok(())
+}
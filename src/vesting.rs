@@ -0,0 +1,125 @@
+use cosmwasm_std::{
+    Deps, DepsMut, Env, Response, StdResult, Event, Addr, Order, Storage, Uint128, CosmosMsg
+};
+use cw_storage_plus::Bound;
+
+use crate::contract::{DEFAULT_LIMIT, MAX_LIMIT};
+use crate::error::ContractError;
+use crate::msg::{GetVestingPositionsResponse, VestingPositionResponse};
+use crate::state::{VESTING_POSITIONS, VESTING_INDEX, OTCInfo, VestingPosition};
+
+
+
+/// Records tokens bought from a vesting offer for the taker and returns the id of the position
+pub fn new_vesting_position(
+    storage: &mut dyn Storage,
+    owner: &Addr,
+    otc_id: u32,
+    otc: &OTCInfo,
+    amount: Uint128,
+    now: u64,
+) -> StdResult<u32> {
+
+    let position = VestingPosition {
+        otc_id,
+        asset: otc.sell.clone(),
+        amount,
+        claimed: Uint128::zero(),
+        start: now,
+        vesting: otc.vesting.clone().unwrap(),
+    };
+
+    let id = VESTING_INDEX.may_load(storage)?.unwrap_or_default();
+
+    VESTING_POSITIONS.save(storage, (owner, id), &position)?;
+    VESTING_INDEX.save(storage, &(id + 1))?;
+
+    Ok(id)
+}
+
+
+
+pub fn try_claim_vested(
+    deps: DepsMut,
+    env: Env,
+    owner: &Addr,
+    ) -> Result<Response, ContractError> {
+
+    let now = env.block.time.seconds();
+
+    let positions = VESTING_POSITIONS
+        .prefix(owner)
+        .range(deps.storage, None, None, Order::Ascending)
+        .collect::<StdResult<Vec<_>>>()?;
+
+    let mut payments : Vec<CosmosMsg> = vec![];
+    let mut events : Vec<Event> = vec![];
+
+    for (id, mut position) in positions {
+        let claimable = position.claimable(now);
+
+        if claimable.is_zero() {
+            continue;
+        }
+
+        payments.push(position.asset.transfer(&env, owner.as_str(), claimable)?);
+
+        position.claimed += claimable;
+
+        if position.claimed == position.amount {
+            VESTING_POSITIONS.remove(deps.storage, (owner, id));
+        } else {
+            VESTING_POSITIONS.save(deps.storage, (owner, id), &position)?;
+        }
+
+        events.push(
+            Event::new("peerswap_vested_claimed")
+            .add_attributes(vec![
+                ("vesting_id", id.to_string()),
+                ("otc_id", position.otc_id.to_string()),
+                ("owner", owner.to_string()),
+                ("amount", claimable.to_string()),
+                ("token", position.asset.token()),
+                ("remaining", (position.amount - position.claimed).to_string()),
+                ("method", "claim_vested".to_string())
+            ])
+        );
+    }
+
+    if payments.is_empty() {
+        return Err(ContractError::NothingToClaim {});
+    }
+
+    Ok(Response::new()
+        .add_messages(payments)
+        .add_events(events)
+    )
+}
+
+
+
+pub fn query_vesting_positions(
+    deps: Deps,
+    env: Env,
+    owner: Addr,
+    start_after: Option<u32>,
+    limit: Option<u32>,
+) -> StdResult<GetVestingPositionsResponse> {
+
+    let now = env.block.time.seconds();
+    let limit = limit.unwrap_or(DEFAULT_LIMIT).min(MAX_LIMIT) as usize;
+    let start = start_after.map(Bound::exclusive);
+
+    let positions = VESTING_POSITIONS
+        .prefix(&owner)
+        .range(deps.storage, start, None, Order::Ascending)
+        .take(limit)
+        .map(|item| item.map(|(id, position)| VestingPositionResponse {
+            id,
+            claimable: position.claimable(now),
+            position,
+        }))
+        .collect::<StdResult<Vec<_>>>()?;
+
+    Ok(GetVestingPositionsResponse { positions })
+}